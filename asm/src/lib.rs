@@ -2,20 +2,82 @@ extern crate machine;
 use machine::inst::*;
 use std::collections::HashMap;
 
-#[derive(Debug, PartialEq, Eq)]
-pub enum AsmError<'a> {
-    InvalidAInstruction(&'a str),
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    InvalidAInstruction,
     EmptyComputation,
     EmptyLabel,
-    InvalidLine(&'a str),
-    InvalidComputation(&'a str),
-    InvalidDestination(&'a str),
-    InvalidJump(&'a str),
+    InvalidLine,
+    InvalidComputation,
+    InvalidDestination,
+    InvalidJump,
+}
+
+pub use AsmErrorKind::*;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError<'a> {
+    pub kind: AsmErrorKind,
+    pub line: usize,            // 1-origin
+    pub column: usize,          // 1-origin
+    pub token: &'a str,         // the offending part of the line
+    pub source_line: &'a str,   // the whole line which contains the error
 }
 
-pub use AsmError::*;
 pub type Result<'a, T> = std::result::Result<T, AsmError<'a>>;
 
+// an error kind and the offending part of a line, which is located by AsmError::new
+type LineResult<'a, T> = std::result::Result<T, (AsmErrorKind, &'a str)>;
+
+impl std::fmt::Display for AsmErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let msg = match self {
+            InvalidAInstruction => "invalid A-instruction",
+            EmptyComputation    => "empty computation",
+            EmptyLabel          => "empty label",
+            InvalidLine         => "invalid line",
+            InvalidComputation  => "invalid computation",
+            InvalidDestination  => "invalid destination",
+            InvalidJump         => "invalid jump",
+        };
+        f.write_str(msg)
+    }
+}
+
+impl<'a> AsmError<'a> {
+    fn new(kind: AsmErrorKind, line: usize, source_line: &'a str, token: &'a str) -> Self {
+        // 'token' is a slice of 'source_line', so its column is the offset between them
+        let offset = token.as_ptr() as usize - source_line.as_ptr() as usize;
+        let column = source_line[..offset].chars().count() + 1;
+        Self{ kind, line, column, token, source_line: source_line.trim_end() }
+    }
+
+    // render the error like rustc does:
+    //
+    //   error: invalid computation `D+2`
+    //    --> Prog.asm:12:6
+    //      |
+    //   12 |    D=D+2
+    //      |      ^^^
+    pub fn render(&self, filename: &str) -> String {
+        let lineno = self.line.to_string();
+        let margin = " ".repeat(lineno.len());
+        let carets = "^".repeat(self.token.chars().count().max(1));
+        format!("error: {} `{}`\n{}--> {}:{}:{}\n{} |\n{} | {}\n{} | {}{}\n",
+            self.kind, self.token,
+            margin, filename, self.line, self.column,
+            margin,
+            lineno, self.source_line,
+            margin, " ".repeat(self.column - 1), carets)
+    }
+}
+
+impl<'a> std::fmt::Display for AsmError<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}: {} `{}`", self.line, self.column, self.kind, self.token)
+    }
+}
+
 #[derive(PartialEq, Eq, Debug)]
 enum Command<'a> {
     AValue(i16),
@@ -24,23 +86,25 @@ enum Command<'a> {
     Computation{ comp: &'a str, dest: Option<&'a str>, jump: Option<&'a str> }
 }
 
-fn line_to_command(line: &str) -> Result<Command> {
+fn line_to_command(line: &str) -> LineResult<'_, Command<'_>> {
     assert!(!line.is_empty());
     if let Some(i) = line.find('@') {
-        if i != 0 { return Err(InvalidLine(line)); }
+        if i != 0 { return Err((InvalidLine, line)); }
         let a = &line[i+1 ..];
-        if a.len() == 0 { Err(InvalidAInstruction(line)) } else {
-            match line[i+1..].parse::<i16>() {
-                Ok(a) => Ok(Command::AValue(a)),
-                _ => Ok(Command::ASymbol(&line[i+1..]))
+        if a.is_empty() { Err((InvalidAInstruction, line)) } else {
+            match a.parse::<i16>() {
+                Ok(n) if n >= 0 => Ok(Command::AValue(n)),
+                // a symbol must not begin with a digit
+                _ if a.starts_with(|c: char| c.is_ascii_digit() || c == '-') => Err((InvalidAInstruction, a)),
+                _ => Ok(Command::ASymbol(a))
             }
         }
     }
-    else if let Some(i) = line.find("(") {
-        let j = line.find(")").ok_or(InvalidLine(line))?;
-        if i > j { return Err(InvalidLine(line)) }
+    else if let Some(i) = line.find('(') {
+        let j = line.find(')').ok_or((InvalidLine, line))?;
+        if i > j { return Err((InvalidLine, line)) }
         let label = line[i+1 .. j].trim();
-        if label.is_empty() { Err(EmptyLabel) } else { Ok(Command::Label(label)) }
+        if label.is_empty() { Err((EmptyLabel, &line[i ..= j])) } else { Ok(Command::Label(label)) }
     }
     else {
         let (dest, comp, jump) = match (line.find('='), line.find(';')) {
@@ -49,20 +113,109 @@ fn line_to_command(line: &str) -> Result<Command> {
             (None, Some(j)) => (None, line[..j].trim(), Some(line[j+1..].trim())),
             (None, None) => (None, line, None)
         };
-        if comp.is_empty() { Err(EmptyComputation) } else {
-            Ok(Command::Computation{ comp: comp, dest: dest, jump: jump })
+        if comp.is_empty() { Err((EmptyComputation, line)) } else {
+            Ok(Command::Computation{ comp, dest, jump })
         }
     }
 }
 
-// translate assembly program into machine language
-pub fn asm(program: &str) -> Result<Vec<i16>> {
-    let commands = program.split("\n")
-        .map(|line| if let Some(i) = line.find("//") { &line[..i] } else { line })  // remove comment
-        .map(|line| line.trim())  // remove white spaces of head and tail
-        .filter(|line| !line.is_empty())    // filter empty line
-        .map(line_to_command)
-        .collect::<Result<Vec<_>>>()?;
+fn computation<'a>(comp: &'a str, dest: Option<&'a str>, jump: Option<&'a str>) -> LineResult<'a, Instruction> {
+    use Computation::*;
+    let comp = match comp {
+        "0"   => Zero, "1" => One, "-1" => MinusOne,
+        "D"   => D, "A" => X(false), "M" => X(true),
+        "!D"  => NotD,   "!A" => NotX(false),   "!M" => NotX(true),
+        "-D"  => MinusD, "-A" => MinusX(false), "-M" => MinusX(true),
+        "D+1" => DPlusOne,  "A+1" => XPlusOne(false),  "M+1" => XPlusOne(true),
+        "D-1" => DMinusOne, "A-1" => XMinusOne(false), "M-1" => XMinusOne(true),
+        "D+A" => DPlusX(false),  "D+M" => DPlusX(true),
+        "D-A" => DMinusX(false), "D-M" => DMinusX(true),
+        "A-D" => XMinusD(false), "M-D" => XMinusD(true),
+        "D&A" => DAndX(false),   "D&M" => DAndX(true),
+        "D|A" => DOrX(false),    "D|M" => DOrX(true),
+        #[cfg(feature = "extended")] "D<<" => ShiftLeftD,
+        #[cfg(feature = "extended")] "A<<" => ShiftLeftX(false),
+        #[cfg(feature = "extended")] "M<<" => ShiftLeftX(true),
+        #[cfg(feature = "extended")] "D>>" => ShiftRightD,
+        #[cfg(feature = "extended")] "A>>" => ShiftRightX(false),
+        #[cfg(feature = "extended")] "M>>" => ShiftRightX(true),
+        _ => return Err((InvalidComputation, comp)),
+    };
+    let dest = match dest {
+        None => 0,
+        Some("A")   => dest::A,
+        Some("D")   => dest::D,
+        Some("M")   => dest::M,
+        Some("MD")  => dest::M | dest::D,
+        Some("AM")  => dest::A | dest::M,
+        Some("AD")  => dest::A | dest::D,
+        Some("AMD") => dest::A | dest::M | dest::D,
+        Some(dest) => return Err((InvalidDestination, dest)),
+    };
+    let jump = match jump {
+        None => Jump::Null,
+        Some("JGT") => Jump::JGT,
+        Some("JEQ") => Jump::JEQ,
+        Some("JGE") => Jump::JGE,
+        Some("JLT") => Jump::JLT,
+        Some("JNE") => Jump::JNE,
+        Some("JLE") => Jump::JLE,
+        Some("JMP") => Jump::JMP,
+        Some(jump) => return Err((InvalidJump, jump))
+    };
+    Ok(CInstruction(comp, dest, jump))
+}
+
+// where an instruction in ROM comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugInfo<'a> {
+    pub line: usize,                // 1-origin
+    pub source_line: &'a str,       // without the trailing white spaces
+    pub label: Option<&'a str>,     // the last label defined before the instruction
+}
+
+// (line number, source line, command)
+type SourceCommand<'a> = (usize, &'a str, Command<'a>);
+
+fn parse(program: &str) -> (Vec<SourceCommand<'_>>, Vec<AsmError<'_>>) {
+    let mut errors = Vec::new();
+    let mut commands = Vec::new();
+    for (i, source_line) in program.split('\n').enumerate() {
+        let line = if let Some(i) = source_line.find("//") { &source_line[..i] } else { source_line };  // remove comment
+        let line = line.trim();  // remove white spaces of head and tail
+        if line.is_empty() { continue; }  // skip empty line
+        match line_to_command(line) {
+            Ok(command) => commands.push((i + 1, source_line, command)),
+            Err((kind, token)) => errors.push(AsmError::new(kind, i + 1, source_line, token))
+        }
+    }
+    (commands, errors)
+}
+
+// labels and their ROM addresses in order of appearance
+fn label_addresses<'a>(commands: &[SourceCommand<'a>]) -> Vec<(&'a str, i16)> {
+    let mut labels = Vec::new();
+    let mut rom_address = 0;
+    for (_, _, command) in commands {
+        match command {
+            Command::Label(label) => labels.push((*label, rom_address)),
+            _ => rom_address += 1
+        }
+    }
+    labels
+}
+
+// translate assembly program into machine language, collecting all errors in the program.
+// the returned errors are sorted by line.
+fn assemble(program: &str) -> (Vec<i16>, Vec<DebugInfo<'_>>, Vec<AsmError<'_>>) {
+    let (instructions, debug_info, _, errors) = assemble_with_variables(program);
+    (instructions, debug_info, errors)
+}
+
+// same as assemble(), and also returns the variables allocated in RAM from 0x10, in order of allocation
+#[allow(clippy::type_complexity)]
+fn assemble_with_variables(program: &str) -> (Vec<i16>, Vec<DebugInfo<'_>>, Vec<(&str, i16)>, Vec<AsmError<'_>>) {
+    let (commands, mut errors) = parse(program);
 
     // predefined symbols
     let mut symbols = [
@@ -75,81 +228,114 @@ pub fn asm(program: &str) -> Result<Vec<i16>> {
             ("KBD",     0x6000)
         ].iter().cloned().collect::<HashMap<&str, i16>>();
     let r = ["R0", "R1", "R2", "R3", "R4", "R5", "R6", "R7", "R8", "R9", "R10", "R11", "R12", "R13", "R14", "R15"];
-    for i in 0 .. 16 {
-        symbols.insert(r[i], i as i16);
+    for (i, r) in r.iter().enumerate() {
+        symbols.insert(r, i as i16);
     }
 
     // 1st pass: add labels to symbol table
-    let mut rom_address = 0;
-    for command in &commands {
-        match command {
-            Command::Label(label) => { symbols.insert(label, rom_address); },
-            _ => rom_address += 1
-        }
-    }
+    symbols.extend(label_addresses(&commands));
 
     // 2nd pass:
     let mut ram_address = 0x10;
-    commands.iter().filter_map(|command| {
-        match command {
-            Command::Label(_) => None,  // skip label, since label is pseudo command.
-            Command::AValue(a) => Some(Ok(AInstruction(*a))),
+    let mut variables = Vec::new();
+    let mut instructions = Vec::new();
+    let mut debug_info = Vec::new();
+    let mut label = None;
+    for (lineno, source_line, command) in &commands {
+        let inst = match command {
+            Command::Label(l) => {  // skip label, since label is pseudo command.
+                label = Some(*l);
+                continue;
+            },
+            Command::AValue(a) => AInstruction(*a),
             Command::ASymbol(a) => {
-                let a = *symbols.entry(*a).or_insert_with(|| {
+                let a = *symbols.entry(a).or_insert_with(|| {
+                    variables.push((*a, ram_address));
                     ram_address += 1;
                     ram_address - 1
                 });
-                Some(Ok(AInstruction(a)))
+                AInstruction(a)
             },
-            Command::Computation{ comp, dest, jump } => {
-                use Computation::*;
-                let comp = match *comp {
-                    "0"   => Zero, "1" => One, "-1" => MinusOne,
-                    "D"   => D, "A" => X(false), "M" => X(true),
-                    "!D"  => NotD,   "!A" => NotX(false),   "!M" => NotX(true),
-                    "-D"  => MinusD, "-A" => MinusX(false), "-M" => MinusX(true),
-                    "D+1" => DPlusOne,  "A+1" => XPlusOne(false),  "M+1" => XPlusOne(true),
-                    "D-1" => DMinusOne, "A-1" => XMinusOne(false), "M-1" => XMinusOne(true),
-                    "D+A" => DPlusX(false),  "D+M" => DPlusX(true),
-                    "D-A" => DMinusX(false), "D-M" => DMinusX(true),
-                    "A-D" => XMinusD(false), "M-D" => XMinusD(true),
-                    "D&A" => DAndX(false),   "D&M" => DAndX(true),
-                    "D|A" => DOrX(false),    "D|M" => DOrX(true),
-                    _ => return Some(Err(InvalidComputation(comp))),
-                };
-                let dest = match *dest {
-                    None => 0,
-                    Some("A")   => dest::A,
-                    Some("D")   => dest::D,
-                    Some("M")   => dest::M,
-                    Some("MD")  => dest::M | dest::D,
-                    Some("AM")  => dest::A | dest::M,
-                    Some("AD")  => dest::A | dest::D,
-                    Some("AMD") => dest::A | dest::M | dest::D,
-                    Some(dest) => return Some(Err(InvalidDestination(dest))),
-                };
-                let jump = match *jump {
-                    None => Jump::Null,
-                    Some("JGT") => Jump::JGT,
-                    Some("JEQ") => Jump::JEQ,
-                    Some("JGE") => Jump::JGE,
-                    Some("JLT") => Jump::JLT,
-                    Some("JNE") => Jump::JNE,
-                    Some("JLE") => Jump::JLE,
-                    Some("JMP") => Jump::JMP,
-                    Some(jump) => return Some(Err(InvalidJump(jump)))
-                };
-                Some(Ok(CInstruction(comp, dest, jump)))
+            Command::Computation{ comp, dest, jump } => match computation(comp, *dest, *jump) {
+                Ok(inst) => inst,
+                Err((kind, token)) => {
+                    errors.push(AsmError::new(kind, *lineno, source_line, token));
+                    continue;
+                }
             }
+        };
+        instructions.push(inst.encode());
+        debug_info.push(DebugInfo{ line: *lineno, source_line: source_line.trim_end(), label });
+    }
+    errors.sort_by_key(|e| (e.line, e.column));
+    (instructions, debug_info, variables, errors)
+}
+
+// translate assembly program into machine language
+pub fn asm(program: &str) -> Result<'_, Vec<i16>> {
+    let (instructions, _, mut errors) = assemble(program);
+    if errors.is_empty() { Ok(instructions) } else { Err(errors.swap_remove(0)) }
+}
+
+// same as asm(), but reports every error in the program at once
+pub fn asm_all(program: &str) -> std::result::Result<Vec<i16>, Vec<AsmError<'_>>> {
+    let (instructions, _, errors) = assemble(program);
+    if errors.is_empty() { Ok(instructions) } else { Err(errors) }
+}
+
+// same as asm_all(), and also returns the debug info of each ROM address
+pub fn asm_with_debug_info(program: &str) -> std::result::Result<(Vec<i16>, Vec<DebugInfo<'_>>), Vec<AsmError<'_>>> {
+    let (instructions, debug_info, errors) = assemble(program);
+    if errors.is_empty() { Ok((instructions, debug_info)) } else { Err(errors) }
+}
+
+// labels defined in the program and their ROM addresses, in order of appearance.
+// lines with errors are ignored.
+pub fn labels(program: &str) -> Vec<(&str, i16)> {
+    label_addresses(&parse(program).0)
+}
+
+// variables of the program and their RAM addresses from 0x10, in order of allocation.
+// lines with errors are ignored.
+pub fn variables(program: &str) -> Vec<(&str, i16)> {
+    assemble_with_variables(program).2
+}
+
+// the final symbol table: the labels (ROM addresses) and the variables (RAM addresses)
+pub fn symbol_table(program: &str) -> String {
+    let mut table = String::from("labels:\n");
+    for (label, address) in labels(program) {
+        table += &format!("  {:<24} {:5}\n", label, address);
+    }
+    table += "variables:\n";
+    for (variable, address) in variables(program) {
+        table += &format!("  {:<24} {:5}  (0x{:04x})\n", variable, address, address);
+    }
+    table
+}
+
+// the listing of the program: ROM address, binary, hex and source line side by side
+pub fn listing(program: &str) -> std::result::Result<String, Vec<AsmError<'_>>> {
+    let instructions = asm_all(program)?;
+    let mut listing = format!("{:>5}  {:<16}  {:<4}  {:>5}  source\n", "ROM", "binary", "hex", "line");
+    let mut address = 0;
+    for (lineno, source_line, command) in parse(program).0 {
+        if let Command::Label(_) = command {
+            listing += &format!("{:5}  {:16}  {:4}  {:5}  {}\n", "", "", "", lineno, source_line.trim_end());
+        } else {
+            let inst = instructions[address] as u16;
+            listing += &format!("{:5}  {:016b}  {:04x}  {:5}  {}\n", address, inst, inst, lineno, source_line.trim_end());
+            address += 1;
         }
-    }).map(|inst| inst.map(|inst| inst.encode())).collect::<Result<Vec<_>>>()
+    }
+    Ok(listing)
 }
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::machine::Machine;
+    use crate::machine::{Machine, Computer};
     
     #[test]
     fn test_to_lines() {
@@ -178,12 +364,95 @@ mod tests {
         assert_eq!(line_to_command("@123"), Ok(Command::AValue(123)));
         assert_eq!(line_to_command("@foo"), Ok(Command::ASymbol("foo")));
         assert!(line_to_command("@").is_err());
+        assert!(line_to_command("@-1").is_err());
+        assert!(line_to_command("@40000").is_err());
         assert_eq!(line_to_command("D"), Ok(Command::Computation{ comp: "D", dest: None, jump: None }));
         assert_eq!(line_to_command("D=A"), Ok(Command::Computation{ comp: "A", dest: Some("D"), jump: None }));
         assert_eq!(line_to_command("M;JGT"), Ok(Command::Computation{ comp: "M", dest: None, jump: Some("JGT") }));
         assert_eq!(line_to_command("(BUZZ)"), Ok(Command::Label("BUZZ")));
     }
 
+    #[test]
+    fn test_error_location() {
+        let program = "@2\n   D=D+2   // comment\n(LOOP)\n@LOOP\n  AMX=D;JMP\n0;JUMP\n()";
+        let errors = asm_all(program).unwrap_err();
+        let errors = errors.iter().map(|e| (e.kind, e.line, e.column, e.token)).collect::<Vec<_>>();
+        assert_eq!(errors, vec![
+            (InvalidComputation, 2, 6, "D+2"),
+            (InvalidDestination, 5, 3, "AMX"),
+            (InvalidJump, 6, 3, "JUMP"),
+            (EmptyLabel, 7, 1, "()"),
+        ]);
+        assert_eq!(asm(program).unwrap_err().kind, InvalidComputation);
+        assert_eq!(asm("@1\n@-1").unwrap_err().to_string(), "2:2: invalid A-instruction `-1`");
+    }
+
+    #[cfg(feature = "extended")]
+    #[test]
+    fn test_shifts() {
+        let bin = asm("@5\nD=A\nD=D<<\nAM=M>>\n").unwrap();
+        let decoded = bin.iter().map(|&code| disassemble(code)).collect::<Vec<_>>();
+        assert_eq!(decoded, vec!["@5", "D=A", "D=D<<", "AM=M>>"]);
+    }
+
+    #[test]
+    fn test_symbols() {
+        let program = "@i\nM=1\n(LOOP)\n@sum\nM=0\n@i\n@LOOP\n0;JMP\n(END)\n";
+        assert_eq!(labels(program), vec![("LOOP", 2), ("END", 7)]);
+        assert_eq!(variables(program), vec![("i", 0x10), ("sum", 0x11)]);
+        assert_eq!(symbol_table(program).lines().collect::<Vec<_>>(), vec![
+            "labels:",
+            "  LOOP                         2",
+            "  END                          7",
+            "variables:",
+            "  i                           16  (0x0010)",
+            "  sum                         17  (0x0011)",
+        ]);
+    }
+
+    #[test]
+    fn test_listing() {
+        let text = listing("// comment\n@2\n(LOOP)\n  D=A   // two\n").unwrap();
+        assert_eq!(text.lines().collect::<Vec<_>>(), vec![
+            "  ROM  binary            hex    line  source",
+            "    0  0000000000000010  0002      2  @2",
+            "                                   3  (LOOP)",
+            "    1  1110110000010000  ec10      4    D=A   // two",
+        ]);
+        assert_eq!(listing("@-1\n").unwrap_err().len(), 1);
+    }
+
+    #[test]
+    fn test_render() {
+        let errors = asm_all("@2\n   D=D+2   // comment\n").unwrap_err();
+        assert_eq!(errors[0].render("Prog.asm"), "\
+error: invalid computation `D+2`
+ --> Prog.asm:2:6
+  |
+2 |    D=D+2   // comment
+  |      ^^^
+");
+    }
+
+    #[test]
+    fn test_labels() {
+        let program = "(START)\n@i\nM=1\n(LOOP)\n(LOOP2)\n@LOOP\n0;JMP\n@x\n(END)";
+        assert_eq!(labels(program), vec![("START", 0), ("LOOP", 2), ("LOOP2", 2), ("END", 5)]);
+    }
+
+    #[test]
+    fn test_debug_info() {
+        let program = "@i\nM=1\n\n(LOOP)  // comment\n  @LOOP   \n0;JMP";
+        let (instructions, debug_info) = asm_with_debug_info(program).unwrap();
+        assert_eq!(instructions.len(), debug_info.len());
+        assert_eq!(debug_info, vec![
+            DebugInfo{ line: 1, source_line: "@i", label: None },
+            DebugInfo{ line: 2, source_line: "M=1", label: None },
+            DebugInfo{ line: 5, source_line: "  @LOOP", label: Some("LOOP") },
+            DebugInfo{ line: 6, source_line: "0;JMP", label: Some("LOOP") },
+        ]);
+    }
+
     fn run_machine(program: &str, nclock: usize, address: i16) -> i16 {
         let bin = asm(program).unwrap();
         let mut machine = Machine::new(&bin);
@@ -0,0 +1,59 @@
+// pacing of the simulation, which runs in time slices between the frames
+use std::time::{Duration, Instant};
+
+// a frame is drawn at this interval (vsync may make it follow the display instead)
+pub const FRAME: Duration = Duration::from_millis(16);
+
+// how much of a frame the simulation may use, so that the events and the drawing keep up
+const BUDGET: Duration = Duration::from_millis(12);
+
+pub struct Clock {
+    ips: f64,           // target instructions (cycles) per second
+    pub paused: bool,
+    pub turbo: bool,    // as many cycles as the budget allows, ignoring ips
+    last: Instant,
+    debt: f64,          // cycles which are due but not yet run
+}
+
+impl Clock {
+    pub fn new(ips: f64) -> Self {
+        Self{ ips, paused: false, turbo: false, last: Instant::now(), debt: 0.0 }
+    }
+    pub fn ips(&self) -> f64 {
+        self.ips
+    }
+    pub fn set_ips(&mut self, ips: f64) {
+        self.ips = ips.max(1.0);
+    }
+
+    // run the cycles due since the last call. 'clock' runs a cycle and returns false if the machine cannot go on.
+    // returns the number of cycles run.
+    pub fn run(&mut self, mut clock: impl FnMut() -> bool) -> u64 {
+        let start = Instant::now();
+        let elapsed = start.duration_since(self.last);
+        self.last = start;
+        if self.paused {
+            self.debt = 0.0;
+            return 0;
+        }
+        let due = if self.turbo { u64::MAX } else {
+            self.debt += self.ips * elapsed.as_secs_f64();
+            self.debt as u64
+        };
+        let mut cycles = 0;
+        while cycles < due {
+            // checking the time at every cycle would cost more than a cycle of the emulator
+            for _ in 0 .. (due - cycles).min(256) {
+                if !clock() {
+                    self.debt = 0.0;
+                    return cycles;
+                }
+                cycles += 1;
+            }
+            if start.elapsed() >= BUDGET { break; }
+        }
+        // the machine which cannot keep up with ips runs as fast as it can, without accumulating the delay
+        self.debt = if cycles < due { 0.0 } else { self.debt - cycles as f64 };
+        cycles
+    }
+}
@@ -0,0 +1,112 @@
+// mapping from the keyboard events of glutin to the Hack key codes
+use glutin::{ElementState, VirtualKeyCode, WindowEvent};
+use machine::keys::{self, HeldKeys};
+
+// the keys which do not produce printable characters
+fn special_key(keycode: VirtualKeyCode) -> Option<i16> {
+    use glutin::VirtualKeyCode::*;
+    let code = match keycode {
+        Return | NumpadEnter => keys::NEWLINE,
+        Back => keys::BACKSPACE,
+        Left => keys::LEFT,
+        Up => keys::UP,
+        Right => keys::RIGHT,
+        Down => keys::DOWN,
+        Home => keys::HOME,
+        End => keys::END,
+        PageUp => keys::PAGE_UP,
+        PageDown => keys::PAGE_DOWN,
+        Insert => keys::INSERT,
+        Delete => keys::DELETE,
+        Escape => keys::ESC,
+        F1 => keys::function_key(1),
+        F2 => keys::function_key(2),
+        F3 => keys::function_key(3),
+        F4 => keys::function_key(4),
+        F5 => keys::function_key(5),
+        F6 => keys::function_key(6),
+        F7 => keys::function_key(7),
+        F8 => keys::function_key(8),
+        F9 => keys::function_key(9),
+        F10 => keys::function_key(10),
+        F11 => keys::function_key(11),
+        F12 => keys::function_key(12),
+        _ => return None
+    };
+    Some(code)
+}
+
+// keys with ctrl which control the simulation instead of being input to the machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hotkey {
+    Pause,      // ctrl+P: pause or resume
+    Step,       // ctrl+N: run a cycle while paused
+    Turbo,      // ctrl+T: run as fast as possible, or back to the target speed
+    Faster,     // ctrl+Up: double the target speed
+    Slower,     // ctrl+Down: halve the target speed
+    Screenshot, // ctrl+S: save the screen to a PNG file
+    Record,     // ctrl+R: start or stop recording the screen to an animated GIF
+}
+
+pub fn hotkey(event: &WindowEvent) -> Option<Hotkey> {
+    match event {
+        WindowEvent::KeyboardInput{ input, .. } if input.modifiers.ctrl => {
+            let hotkey = match input.virtual_keycode? {
+                VirtualKeyCode::P => Hotkey::Pause,
+                VirtualKeyCode::N => Hotkey::Step,
+                VirtualKeyCode::T => Hotkey::Turbo,
+                VirtualKeyCode::Up => Hotkey::Faster,
+                VirtualKeyCode::Down => Hotkey::Slower,
+                VirtualKeyCode::S => Hotkey::Screenshot,
+                VirtualKeyCode::R => Hotkey::Record,
+                _ => return None
+            };
+            if input.state == ElementState::Pressed { Some(hotkey) } else { None }
+        },
+        _ => None
+    }
+}
+
+// tracks the held keys. the printable keys are identified by their scan codes on press,
+// and their codes come with the ReceivedCharacter event which follows (so that shift etc. are applied).
+pub struct Keyboard {
+    held: HeldKeys,
+    pending: Option<u32>,   // scan code of the pressed key waiting for its character
+}
+
+impl Keyboard {
+    pub fn new() -> Self {
+        Self{ held: HeldKeys::default(), pending: None }
+    }
+
+    // the new value of KBD if the event changes it
+    pub fn event(&mut self, event: &WindowEvent) -> Option<i16> {
+        let before = self.held.code();
+        match event {
+            WindowEvent::KeyboardInput{ input, .. } => match input.state {
+                ElementState::Pressed => match input.virtual_keycode.and_then(special_key) {
+                    Some(code) => {
+                        self.held.press(input.scancode, code);
+                        self.pending = None;
+                    },
+                    None => self.pending = Some(input.scancode)
+                },
+                ElementState::Released => {
+                    self.held.release(input.scancode);
+                    if self.pending == Some(input.scancode) { self.pending = None; }
+                }
+            },
+            WindowEvent::ReceivedCharacter(c) => {
+                if let (Some(scancode), Some(code)) = (self.pending, keys::char_code(*c)) {
+                    self.held.press(scancode, code);
+                    self.pending = None;
+                }
+            },
+            // the release events are lost while the window is not focused
+            WindowEvent::Focused(false) => *self = Self::new(),
+            _ => ()
+        }
+        let after = self.held.code();
+        if after != before { Some(after) } else { None }
+    }
+}
@@ -2,10 +2,59 @@ extern crate glutin;
 extern crate machine;
 extern crate asm;
 extern crate vm_translator;
+extern crate jack_compiler;
 use machine::*;
 use std::env;
-use std::io::{Read, Write};
+use std::io::Read;
 mod window;
+mod keyboard;
+mod clock;
+mod font;
+mod panels;
+use keyboard::Hotkey;
+use machine::debugger::{Debugger, StopReason};
+use machine::screen_recorder::Recorder;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+// the target speed of the simulation when --ips is not given
+const DEFAULT_IPS: f64 = 100_000.0;
+
+// centiseconds per frame of a recorded GIF, which is close to the frame interval
+const FRAME_DELAY: u16 = 2;
+
+// the .jack files in a directory, sorted by file name
+fn jack_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let mut paths = entries.map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()
+        .map_err(|e| format!("{}: {}", dir.display(), e))?;
+    paths.retain(|path| path.extension().and_then(|s| s.to_str()) == Some("jack"));
+    paths.sort();
+    Ok(paths)
+}
+
+// compile each .jack file into a VM module named after its class, which is the namespace of the static variables
+fn compile_jack(paths: &[PathBuf]) -> Result<Vec<vm_translator::VmModule>, String> {
+    paths.iter().map(|path| {
+        let source = read_source(&path.display().to_string());
+        let class = jack_compiler::parse(&source).map_err(|e| format!("{}:{}", path.display(), e))?;
+        let mut vm_source = String::new();
+        jack_compiler::compile_class(&mut vm_source, &class).map_err(|e| format!("{}:{}", path.display(), e))?;
+        println!("*** Jack to VM ***\n{}", vm_source);
+        Ok(vm_translator::VmModule{ name: class.name.name, source: vm_source })
+    }).collect()
+}
+
+// link the VM modules with the bootstrap code. a call of an undefined function (e.g. of the OS not given) is an error,
+// since the assembler would take its label for a variable.
+fn link(modules: &[vm_translator::VmModule]) -> Result<Vec<i16>, String> {
+    vm_translator::check_calls(modules, true).map_err(|e| e.to_string())?;
+    let mut asm_source = String::new();
+    vm_translator::compile_modules(&mut asm_source, modules).map_err(|e| e.to_string())?;
+    println!("*** VM to ASM ***\n{}", asm_source);
+    Ok(asm::asm(&asm_source).expect("failed to compile asm to binary instructions."))
+}
 
 fn read_source(filepath: &str) -> String {
     println!("input file is '{}'", filepath);
@@ -18,76 +67,262 @@ fn read_source(filepath: &str) -> String {
 
 fn main() {
     let args = env::args().collect::<Vec<_>>();
+
+    // '--fast' runs the program on the word-level emulator instead of the gate-level machine
+    let fast = args.iter().any(|arg| arg == "--fast");
+    let mut args = args.into_iter().filter(|arg| arg != "--fast").collect::<Vec<_>>();
+
+    // '--ips N' is the target speed in instructions per second
+    let ips = match args.iter().position(|arg| arg == "--ips") {
+        Some(i) if i + 1 < args.len() => match args[i + 1].parse::<f64>() {
+            Ok(ips) if ips >= 1.0 => {
+                args.drain(i ..= i + 1);
+                ips
+            },
+            _ => {
+                println!("invalid speed: '{}'", args[i + 1]);
+                return;
+            }
+        },
+        Some(_) => {
+            println!("--ips needs a number");
+            return;
+        },
+        None => DEFAULT_IPS
+    };
+
+    // '--keys script' inputs the keys of a key script (see machine::keys::KeyScript) at the cycles written in it
+    let key_script = match args.iter().position(|arg| arg == "--keys") {
+        Some(i) if i + 1 < args.len() => {
+            let file = args.remove(i + 1);
+            args.remove(i);
+            match std::fs::read_to_string(&file).map_err(|e| e.to_string()).and_then(|source| keys::KeyScript::parse(&source)) {
+                Ok(script) => Some(script),
+                Err(e) => {
+                    println!("{}: {}", file, e);
+                    return;
+                }
+            }
+        },
+        Some(_) => {
+            println!("--keys needs a filename");
+            return;
+        },
+        None => None
+    };
+
     if args.len() < 2 {
-        println!("usage: {} filename.asm", args[0]);
+        println!("usage: {} [--fast] [--ips N] [--keys script] (filename.hack | filename.bin | filename.asm | filename.vm | filename.jack | directory of .jack and .vm files)", args[0]);
         return;
     }
 
     let path = std::path::Path::new(&args[1]);
     let instructions = match path.extension().and_then(|s| s.to_str()) {
-        Some("hack") => {
-            let mut f = std::fs::File::open(&args[1]).expect("cannot open the input file.");
-            let mut buf = Vec::<u8>::new();
-            let _ = f.read_to_end(&mut buf).expect("failed to read file");
-            buf.chunks(2).map(|a| (a[1] as i16) << 8 | a[0] as i16).collect()
+        Some("hack") | Some("bin") => match hack_format::read_file(path) {
+            Ok(instructions) => instructions,
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
         },
         Some("asm") => {
             // read assembly source codes from input file specified with args[1]
             let asm_source = read_source(&args[1]);
-            asm::asm(&asm_source).expect("failed to compile asm to binary instructions.")
+            let result = asm::asm_all(&asm_source);
+            match result {
+                Ok(instructions) => instructions,
+                Err(errors) => {
+                    for e in errors { println!("{}", e.render(&args[1])); }
+                    return;
+                }
+            }
         },
         Some("vm") => {
             let vm_source = read_source(&args[1]);
             let mut asm_source = String::new();
-            vm_translator::compile(&mut asm_source, &args[1], &vm_source);
+            if let Err(e) = vm_translator::compile(&mut asm_source, &args[1], &vm_source) {
+                println!("{}", e);
+                return;
+            }
             println!("*** VM to ASM ***\n{}", asm_source);
             asm::asm(&asm_source).expect("failed to compile asm to binary instructions.")
         },
+        None if path.is_dir() => {
+            // the .jack files are compiled and linked with the .vm files (e.g. the OS) not compiled from them
+            let result = jack_files(path).and_then(|paths| compile_jack(&paths)).and_then(|mut modules| {
+                let vm_modules = vm_translator::read_dir(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                let vm_modules = vm_modules.into_iter().filter(|vm| !modules.iter().any(|jack| jack.name == vm.name)).collect::<Vec<_>>();
+                modules.extend(vm_modules);
+                link(&modules)
+            });
+            match result {
+                Ok(instructions) => instructions,
+                Err(e) => {
+                    println!("{}", e);
+                    return;
+                }
+            }
+        },
+        Some("jack") => match compile_jack(&[path.to_path_buf()]).and_then(|modules| link(&modules)) {
+            Ok(instructions) => instructions,
+            Err(e) => {
+                println!("{}", e);
+                println!("(give the directory of the program to link it with the other classes and the OS)");
+                return;
+            }
+        },
         _ => panic!("unknown extension")
     };
 
     // dump instructions
     println!("*** decoded instructions ***");
     for &i in &instructions {
-        println!("{}", inst::Instruction::decode(i));
+        println!("{}", inst::disassemble(i));
     }
 
-    // construct a machine with the instructions
-    let mut machine = machine::Machine::new(&instructions);
-    machine.print_status_header();
+    // construct a machine with the instructions, driven by the debugger of the panels
+    let machine: Box<dyn Computer> = if fast {
+        Box::new(Emulator::new(&instructions))
+    } else {
+        Box::new(machine::Machine::new(&instructions))
+    };
+    let mut debugger = Debugger::new(machine, &instructions);
 
     // start events loop
     let mut events_loop = glutin::EventsLoop::new();
     let window = window::Window::new(&events_loop);
-    let mut counter = 0;
+    let mut keyboard = keyboard::Keyboard::new();
+    let mut clock = clock::Clock::new(ips);
+    let mut panels = panels::Panels::new(&instructions);
+    let mut canvas = panels::Canvas::new(panels::WIDTH, panels::HEIGHT);
+    let mut cursor = (0, 0);
+    let mut stop: Option<StopReason> = None;
+    let mut recorder: Option<Recorder> = None;
+    let mut cycle = 0;
     let mut running = true;
     while running {
+        let frame_start = Instant::now();
+        let mut step = false;
         events_loop.poll_events(|event| {
             match event {
                 glutin::Event::WindowEvent{ event, .. } => match event {
                     glutin::WindowEvent::CloseRequested => running = false,
                     glutin::WindowEvent::Resized(logical_size) => window.resize(logical_size),
-                    glutin::WindowEvent::KeyboardInput{ input, .. } => {
-                        println!("key event: {:?}, {:?}", input.virtual_keycode, input.state);
-                        machine.keyboard_input(0);
+                    glutin::WindowEvent::CursorMoved{ position, .. } => cursor = window.to_canvas(position),
+                    glutin::WindowEvent::MouseInput{ state: glutin::ElementState::Pressed, button: glutin::MouseButton::Left, .. } => {
+                        if let Some(address) = panels.click(cursor.0, cursor.1, &mut debugger) {
+                            println!("breakpoint at {}: {}", address, if debugger.is_breakpoint(address) { "on" } else { "off" });
+                        }
+                    },
+                    glutin::WindowEvent::MouseWheel{ delta, .. } => {
+                        let lines = match delta {
+                            glutin::MouseScrollDelta::LineDelta(_, y) => y.round() as i32,
+                            glutin::MouseScrollDelta::PixelDelta(position) => (position.y / 10.0).round() as i32
+                        };
+                        panels.scroll(cursor.0, cursor.1, lines);
                     },
-                    _ => ()
+                    event => match keyboard::hotkey(&event) {
+                        Some(Hotkey::Pause) => {
+                            clock.paused = !clock.paused;
+                            if !clock.paused { stop = None; }
+                            panels.follow_pc();
+                        },
+                        Some(Hotkey::Step) => {
+                            clock.paused = true;
+                            step = true;
+                            panels.follow_pc();
+                        },
+                        Some(Hotkey::Turbo) => clock.turbo = !clock.turbo,
+                        Some(Hotkey::Faster) => clock.set_ips(clock.ips() * 2.0),
+                        Some(Hotkey::Slower) => clock.set_ips(clock.ips() / 2.0),
+                        Some(Hotkey::Screenshot) => {
+                            let path = format!("screen-{}.png", cycle);
+                            match screen_image::write_file(Path::new(&path), debugger.computer().screen_image()) {
+                                Ok(()) => println!("saved the screen to {}", path),
+                                Err(e) => println!("{}", e)
+                            }
+                        },
+                        Some(Hotkey::Record) => match recorder.take() {
+                            Some(recording) => finish_recording(recording),
+                            None => {
+                                let path = format!("screen-{}.gif", cycle);
+                                println!("recording the screen to {}", path);
+                                recorder = Recorder::new(Path::new(&path), FRAME_DELAY).ok();
+                            }
+                        },
+                        None => if let Some(key) = keyboard.event(&event) {
+                            debugger.computer_mut().keyboard_input(key);
+                        }
+                    }
                 },
                 _ => ()
             }
         });
 
-        // send a clock signal to the machine
-        machine.clock(false);
+        // send clock signals to the machine for the time since the last frame.
+        // it stops before the instruction at a breakpoint, and the first cycle after a pause executes it.
+        let mut tick = || {
+            if let Some(script) = &key_script {
+                script.apply(cycle, debugger.computer_mut());
+            }
+            let reason = debugger.step();
+            cycle += 1;
+            let pc = debugger.computer().pc();
+            stop = match reason {
+                StopReason::Step if debugger.computer().is_terminated() => Some(StopReason::Terminated),
+                StopReason::Step if debugger.is_breakpoint(pc) => Some(StopReason::Breakpoint(pc)),
+                StopReason::Step => None,
+                reason => Some(reason)
+            };
+            stop.is_none()
+        };
+        if step {
+            tick();
+        } else {
+            clock.run(&mut tick);
+        }
+        if let (Some(reason), false) = (&stop, clock.paused) {
+            println!("{}: {}", reason, debugger.state());
+            clock.paused = true;
+            panels.follow_pc();
+        }
 
-        // refresh screen
-        if counter % 128 == 0 {
-            //machine.print_status();
-            window.draw(machine.screen_image());
+        // the machine does not run while drawing, so the screen image is a snapshot between cycles.
+        // the recording takes the same snapshots, in which the frames with no change are merged.
+        if let Some(recording) = &mut recorder {
+            if let Err(e) = recording.record(debugger.computer().screen_image()) {
+                println!("{}", e);
+                recorder = None;
+            }
+        }
+        let status = if clock.paused {
+            match &stop {
+                Some(reason) => format!("paused ({})", reason),
+                None => "paused".to_string()
+            }
+        } else if clock.turbo {
+            "turbo".to_string()
+        } else {
+            format!("running at {} instructions/s", clock.ips())
+        };
+        let status = match &recorder {
+            Some(recording) => format!("{}   recording ({} frames)", status, recording.count()),
+            None => status
+        };
+        panels.draw(&mut canvas, &debugger, &status);
+        window.draw(debugger.computer().screen_image(), &canvas);
+        if let Some(rest) = clock::FRAME.checked_sub(frame_start.elapsed()) {
+            std::thread::sleep(rest);
         }
-        machine.print_status();
-        std::io::stdout().flush();
+    }
+    if let Some(recording) = recorder {
+        finish_recording(recording);
+    }
+}
 
-        counter = counter + 1;
+fn finish_recording(recorder: Recorder) {
+    match recorder.finish() {
+        Ok(frames) => println!("recorded {} frames", frames),
+        Err(e) => println!("{}", e)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,230 @@
+// debugger panels drawn beside the screen: registers, RAM and disassembled ROM with breakpoints
+use crate::font;
+use machine::Computer;
+use machine::debugger::Debugger;
+use machine::inst;
+
+pub type Color = [u8; 4];   // RGBA
+
+const BACKGROUND: Color = [32, 32, 32, 255];
+const TEXT: Color = [220, 220, 220, 255];
+const DIM: Color = [128, 128, 128, 255];
+const PC_ROW: Color = [50, 80, 150, 255];
+const BREAKPOINT: Color = [220, 60, 60, 255];
+
+// SP, LCL, ARG, THIS and THAT: RAM[0] to RAM[4], and the colors of the cells they point to
+const POINTERS: [(&str, Color); 5] = [
+    ("SP", [140, 120, 20, 255]),
+    ("LCL", [30, 120, 60, 255]),
+    ("ARG", [150, 80, 20, 255]),
+    ("THIS", [20, 110, 130, 255]),
+    ("THAT", [120, 40, 120, 255]),
+];
+
+// RGBA image of the whole window, in which the panels are drawn
+pub struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self{ width, height, pixels: vec![BACKGROUND; width * height] }
+    }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn fill(&mut self, rect: &Rect, color: Color) {
+        for y in rect.y .. (rect.y + rect.height).min(self.height) {
+            for x in rect.x .. (rect.x + rect.width).min(self.width) {
+                self.pixels[y * self.width + x] = color;
+            }
+        }
+    }
+
+    // draw a line of text with its top left corner at (x, y). the pixels out of the canvas are clipped.
+    pub fn text(&mut self, x: usize, y: usize, text: &str, color: Color) {
+        for (i, c) in text.chars().enumerate() {
+            let left = x + i * CHAR_WIDTH;
+            for (row, bits) in font::glyph(c).iter().enumerate() {
+                for col in 0 .. font::WIDTH {
+                    let (px, py) = (left + col, y + row);
+                    if bits & (0x10 >> col) != 0 && px < self.width && py < self.height {
+                        self.pixels[py * self.width + px] = color;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        (self.x .. self.x + self.width).contains(&x) && (self.y .. self.y + self.height).contains(&y)
+    }
+}
+
+// layout of the window in the pixels of the canvas. the screen is drawn by the window itself.
+pub const WIDTH: usize = 1024;
+pub const HEIGHT: usize = 512;
+pub const SCREEN: Rect = Rect{ x: 0, y: 0, width: 512, height: 256 };
+const REGISTERS: Rect = Rect{ x: 0, y: 256, width: 512, height: 40 };
+const RAM: Rect = Rect{ x: 0, y: 296, width: 512, height: 216 };
+const ROM: Rect = Rect{ x: 512, y: 0, width: 512, height: 512 };
+
+const CHAR_WIDTH: usize = font::WIDTH + 1;
+const LINE_HEIGHT: usize = font::HEIGHT + 3;
+const HEADER: usize = LINE_HEIGHT + 4;    // the title line of a panel
+const RAM_COLUMNS: usize = 3;
+const RAM_ROWS: usize = (RAM.height - HEADER) / LINE_HEIGHT;
+const ROM_ROWS: usize = (ROM.height - HEADER) / LINE_HEIGHT;
+const LAST_ADDRESS: i32 = 0x6000;   // KBD
+
+pub struct Panels {
+    rom: Vec<String>,   // disassembly of each instruction
+    ram_top: i32,       // the first address in the RAM view
+    rom_top: i32,       // the first address in the ROM view
+    follow_pc: bool,    // scroll the ROM view to keep PC visible
+}
+
+impl Panels {
+    pub fn new(rom: &[i16]) -> Self {
+        Self{
+            rom: rom.iter().map(|&word| inst::disassemble(word)).collect(),
+            ram_top: 0,
+            rom_top: 0,
+            follow_pc: true
+        }
+    }
+
+    // show PC in the ROM view again after it is scrolled by hand
+    pub fn follow_pc(&mut self) {
+        self.follow_pc = true;
+    }
+
+    // 'status' is shown under the registers, e.g. whether the simulation is paused
+    pub fn draw(&mut self, canvas: &mut Canvas, debugger: &Debugger, status: &str) {
+        canvas.fill(&Rect{ x: 0, y: 0, width: canvas.width(), height: canvas.height() }, BACKGROUND);
+        self.draw_registers(canvas, debugger, status);
+        self.draw_ram(canvas, debugger.computer());
+        self.draw_rom(canvas, debugger);
+    }
+
+    fn draw_registers(&self, canvas: &mut Canvas, debugger: &Debugger, status: &str) {
+        let state = debugger.state();
+        let (x, y) = (REGISTERS.x + 4, REGISTERS.y + 4);
+        canvas.text(x, y, &format!("PC {:5}   A {:6} (0x{:04x})   D {:6} (0x{:04x})",
+            state.pc, state.a, state.a as u16, state.d, state.d as u16), TEXT);
+        let next = self.rom.get(state.pc as u16 as usize).map_or("(out of ROM)", |line| line.as_str());
+        canvas.text(x, y + LINE_HEIGHT, &format!("next: {}", next), TEXT);
+        canvas.text(x, y + 2 * LINE_HEIGHT, &format!("cycle {}   {}", state.cycle, status), DIM);
+    }
+
+    fn draw_ram(&self, canvas: &mut Canvas, computer: &dyn Computer) {
+        // the title doubles as the legend of the pointer colors
+        let (x, y) = (RAM.x + 4, RAM.y + 2);
+        canvas.text(x, y, "RAM", TEXT);
+        let mut legend = x + 6 * CHAR_WIDTH;
+        for &(name, color) in &POINTERS {
+            let width = (name.len() + 2) * CHAR_WIDTH;
+            canvas.fill(&Rect{ x: legend - 2, y: y - 1, width: width - 2, height: LINE_HEIGHT - 1 }, color);
+            canvas.text(legend + CHAR_WIDTH / 2, y, name, TEXT);
+            legend += width + CHAR_WIDTH;
+        }
+        canvas.text(x + 60 * CHAR_WIDTH, y, "(wheel to scroll)", DIM);
+
+        let pointers = (0 .. POINTERS.len()).map(|i| computer.read_memory(i as i16) as i32).collect::<Vec<_>>();
+        let column_width = RAM.width / RAM_COLUMNS;
+        for i in 0 .. RAM_COLUMNS * RAM_ROWS {
+            let address = self.ram_top + i as i32;
+            if address > LAST_ADDRESS { break; }
+            let cell = Rect{
+                x: RAM.x + (i / RAM_ROWS) * column_width,
+                y: RAM.y + HEADER + (i % RAM_ROWS) * LINE_HEIGHT,
+                width: column_width - 4,
+                height: LINE_HEIGHT
+            };
+            let targets = POINTERS.iter().zip(&pointers).filter(|&(_, &target)| target == address).map(|(pointer, _)| pointer).collect::<Vec<_>>();
+            if let Some((_, color)) = targets.first() {
+                canvas.fill(&cell, *color);
+            }
+            let names = targets.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(",");
+            let text = format!("{:5} {:6} {}", address, computer.read_memory(address as i16), names);
+            // the pointers themselves are in their colors
+            let color = POINTERS.get(address as usize).map_or(TEXT, |&(_, color)| brighten(color));
+            canvas.text(cell.x + 4, cell.y + 1, &text, color);
+        }
+    }
+
+    fn draw_rom(&mut self, canvas: &mut Canvas, debugger: &Debugger) {
+        let pc = debugger.computer().pc() as i32;
+        if self.follow_pc && !(self.rom_top .. self.rom_top + ROM_ROWS as i32).contains(&pc) {
+            self.rom_top = self.clamp_rom_top(pc - ROM_ROWS as i32 / 3);
+        }
+        canvas.text(ROM.x + 4, ROM.y + 2, "ROM", TEXT);
+        canvas.text(ROM.x + 4 + 6 * CHAR_WIDTH, ROM.y + 2, "(click to toggle a breakpoint, wheel to scroll)", DIM);
+        for i in 0 .. ROM_ROWS {
+            let address = self.rom_top + i as i32;
+            let line = match self.rom.get(address as usize) {
+                Some(line) => line,
+                None => break
+            };
+            let row = Rect{ x: ROM.x, y: ROM.y + HEADER + i * LINE_HEIGHT, width: ROM.width, height: LINE_HEIGHT };
+            if address == pc {
+                canvas.fill(&row, PC_ROW);
+            }
+            if debugger.is_breakpoint(address as i16) {
+                canvas.fill(&Rect{ x: row.x + 4, y: row.y + 2, width: 5, height: 5 }, BREAKPOINT);
+            }
+            let marker = if address == pc { ">" } else { " " };
+            canvas.text(row.x + 12, row.y + 1, &format!("{}{:5}  {}", marker, address, line), TEXT);
+        }
+    }
+
+    fn clamp_rom_top(&self, top: i32) -> i32 {
+        top.min(self.rom.len() as i32 - ROM_ROWS as i32).max(0)
+    }
+
+    // a click at (x, y) on the canvas. returns the address of the breakpoint toggled, if any
+    pub fn click(&mut self, x: usize, y: usize, debugger: &mut Debugger) -> Option<i16> {
+        if !ROM.contains(x, y) || y < ROM.y + HEADER { return None; }
+        let address = self.rom_top + ((y - ROM.y - HEADER) / LINE_HEIGHT) as i32;
+        if address >= self.rom.len() as i32 { return None; }
+        let address = address as i16;
+        if !debugger.remove_breakpoint(address) {
+            debugger.add_breakpoint(address);
+        }
+        Some(address)
+    }
+
+    // the mouse wheel at (x, y). positive 'lines' scrolls up
+    pub fn scroll(&mut self, x: usize, y: usize, lines: i32) {
+        if RAM.contains(x, y) {
+            self.ram_top = (self.ram_top - lines * RAM_COLUMNS as i32).min(LAST_ADDRESS + 1 - (RAM_COLUMNS * RAM_ROWS) as i32).max(0);
+        } else if ROM.contains(x, y) {
+            self.follow_pc = false;
+            self.rom_top = self.clamp_rom_top(self.rom_top - lines * 3);
+        }
+    }
+}
+
+fn brighten(color: Color) -> Color {
+    let up = |c: u8| c.saturating_add(80);
+    [up(color[0]), up(color[1]), up(color[2]), color[3]]
+}
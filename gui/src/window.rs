@@ -2,6 +2,7 @@ extern crate gl;
 extern crate glutin;
 use glutin::dpi::*;
 use glutin::GlContext;
+use crate::panels;
 
 mod gl_util {
     fn compile_shader(shader_type: u32, source: &str) -> u32 {
@@ -41,6 +42,17 @@ mod gl_util {
             program
         }
     }
+
+    pub fn create_texture() -> u32 {
+        unsafe {
+            let mut tex: u32 = 0;
+            gl::GenTextures(1, &mut tex as *mut u32);
+            gl::BindTexture(gl::TEXTURE_2D, tex);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            tex
+        }
+    }
 }
 
 static SHADER_V: (u32, &str) = (gl::VERTEX_SHADER, "
@@ -72,13 +84,28 @@ void main ()
 }
 ");
 
+// the panels are an RGBA image, whose first row is the top
+static SHADER_PANELS: (u32, &str) = (gl::FRAGMENT_SHADER, "
+#version 150
+uniform sampler2D panels;
+in vec2 texcod;
+out vec4 color;
+void main ()
+{
+    color = texture(panels, vec2(texcod.x, 1 - texcod.y));
+}
+");
+
 static SCREEN_RECT: [(f32, f32); 4] = [
     (-1.0, -1.0), (1.0, -1.0),
     (-1.0,  1.0), (1.0,  1.0),
 ];
 
 pub struct Window {
-    window: glutin::GlWindow
+    window: glutin::GlWindow,
+    size: std::cell::Cell<LogicalSize>,
+    screen: (u32, u32),     // (program, texture) of the screen
+    panels: (u32, u32),     // (program, texture) of the panels
 }
 
 impl Window {
@@ -86,45 +113,76 @@ impl Window {
         let gl_window = {
             let window = glutin::WindowBuilder::new()
                 .with_title("nand2tetris in Rust")
-                .with_dimensions(LogicalSize::new(512.0, 256.0));
+                .with_dimensions(LogicalSize::new(panels::WIDTH as f64, panels::HEIGHT as f64));
             let context = glutin::ContextBuilder::new()
                 .with_vsync(true);
             glutin::GlWindow::new(window, context, events_loop).unwrap()
         };
 
-        unsafe {
+        let (screen, panels) = unsafe {
             gl_window.make_current().unwrap();
 
             gl::load_with(|symbol| gl_window.get_proc_address(symbol) as *const _);
             gl::ClearColor(0.0, 0.0, 0.0, 1.0);
 
-            let mut tex: u32 = 0;
-            gl::GenTextures(1, &mut tex as *mut u32);
-            gl::BindTexture(gl::TEXTURE_2D, tex);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
-
-            let program = gl_util::create_program(&[SHADER_V, SHADER_F]);
-            gl::UseProgram(program);
+            let screen = (gl_util::create_program(&[SHADER_V, SHADER_F]), gl_util::create_texture());
+            gl::UseProgram(screen.0);
             gl::Uniform1i(0, 0);
-        }
+            let panels = (gl_util::create_program(&[SHADER_V, SHADER_PANELS]), gl_util::create_texture());
+            (screen, panels)
+        };
 
-        Self{ window: gl_window }
+        let size = std::cell::Cell::new(LogicalSize::new(panels::WIDTH as f64, panels::HEIGHT as f64));
+        Self{ window: gl_window, size, screen, panels }
     }
 
     pub fn resize(&self, size: LogicalSize) {
-        let size = size.to_physical(self.window.get_hidpi_factor());
-        self.window.resize(size);
-        unsafe{ gl::Viewport(0, 0, size.width as i32, size.height as i32); }
+        self.size.set(size);
+        self.window.resize(size.to_physical(self.window.get_hidpi_factor()));
+    }
+
+    // position in the window to the pixel of the panels
+    pub fn to_canvas(&self, position: LogicalPosition) -> (usize, usize) {
+        let size = self.size.get();
+        let x = position.x * panels::WIDTH as f64 / size.width;
+        let y = position.y * panels::HEIGHT as f64 / size.height;
+        (x.max(0.0) as usize, y.max(0.0) as usize)
+    }
+
+    // set the viewport to a rectangle of the panels, which is scaled with the window
+    fn viewport(&self, rect: &panels::Rect) {
+        let size = self.size.get().to_physical(self.window.get_hidpi_factor());
+        let scale_x = size.width / panels::WIDTH as f64;
+        let scale_y = size.height / panels::HEIGHT as f64;
+        let bottom = panels::HEIGHT - rect.y - rect.height;  // the origin of GL is the bottom left
+        unsafe {
+            gl::Viewport((rect.x as f64 * scale_x) as i32, (bottom as f64 * scale_y) as i32,
+                (rect.width as f64 * scale_x) as i32, (rect.height as f64 * scale_y) as i32);
+        }
     }
 
-    pub fn draw (&self, screen_image: &[i16; 32 * 256]) {
+    pub fn draw (&self, screen_image: &[i16; 32 * 256], canvas: &panels::Canvas) {
+        self.viewport(&panels::Rect{ x: 0, y: 0, width: panels::WIDTH, height: panels::HEIGHT });
         unsafe {
+            gl::UseProgram(self.panels.0);
+            gl::BindTexture(gl::TEXTURE_2D, self.panels.1);
+            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA8 as i32, canvas.width() as i32, canvas.height() as i32, 0, gl::RGBA, gl::UNSIGNED_BYTE, canvas.pixels().as_ptr() as *const std::ffi::c_void);
+            draw_rect();
+        }
+        self.viewport(&panels::SCREEN);
+        unsafe {
+            gl::UseProgram(self.screen.0);
+            gl::BindTexture(gl::TEXTURE_2D, self.screen.1);
             gl::TexImage2D(gl::TEXTURE_2D, 0, gl::R16UI as i32, 32, 256, 0, gl::RED_INTEGER, gl::UNSIGNED_SHORT, screen_image.as_ptr() as *const std::ffi::c_void);
-            gl::EnableVertexAttribArray(0);
-            gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, 0, SCREEN_RECT.as_ptr() as *const std::ffi::c_void);
-            gl::DrawArrays(gl::TRIANGLE_STRIP, 0, SCREEN_RECT.len() as i32);
+            draw_rect();
         }
         self.window.swap_buffers().unwrap();
     }
+}
+
+// fill the viewport with the texture bound
+unsafe fn draw_rect() {
+    gl::EnableVertexAttribArray(0);
+    gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, 0, SCREEN_RECT.as_ptr() as *const std::ffi::c_void);
+    gl::DrawArrays(gl::TRIANGLE_STRIP, 0, SCREEN_RECT.len() as i32);
 }
\ No newline at end of file
@@ -0,0 +1,97 @@
+// Abstract Syntax Tree of the Jack language
+use crate::error::Span;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    Int,
+    Char,
+    Boolean,
+    Class(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClassVarKind { Static, Field }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubroutineKind { Constructor, Function, Method }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Class {
+    pub name: Ident,
+    pub vars: Vec<ClassVarDec>,
+    pub subroutines: Vec<Subroutine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassVarDec {
+    pub kind: ClassVarKind,
+    pub ty: Type,
+    pub names: Vec<Ident>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VarDec {
+    pub ty: Type,
+    pub names: Vec<Ident>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subroutine {
+    pub kind: SubroutineKind,
+    pub return_type: Option<Type>,  // None for 'void'
+    pub name: Ident,
+    pub params: Vec<(Type, Ident)>,
+    pub locals: Vec<VarDec>,
+    pub body: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    Let{ target: Ident, index: Option<Expression>, value: Expression },
+    If{ cond: Expression, then_branch: Vec<Statement>, else_branch: Option<Vec<Statement>> },
+    While{ cond: Expression, body: Vec<Statement> },
+    Do(SubroutineCall),
+    Return(Option<Expression>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp { Add, Sub, Mul, Div, And, Or, Lt, Gt, Eq }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp { Neg, Not }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeywordConstant { True, False, Null, This }
+
+// term (op term)*
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expression {
+    pub head: Term,
+    pub tail: Vec<(BinaryOp, Term)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term {
+    Integer(i16),
+    String(String),
+    Keyword(KeywordConstant, Span),
+    Var(Ident),
+    Index(Ident, Box<Expression>),
+    Call(SubroutineCall),
+    Paren(Box<Expression>),
+    Unary(UnaryOp, Box<Term>),
+}
+
+// subroutineName(args), or receiver.subroutineName(args) where receiver is a class name or a variable name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubroutineCall {
+    pub receiver: Option<Ident>,
+    pub name: Ident,
+    pub args: Vec<Expression>,
+}
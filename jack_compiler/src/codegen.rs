@@ -0,0 +1,264 @@
+use crate::ast::*;
+use crate::error::*;
+use crate::symbol_table::*;
+use crate::writer::*;
+
+pub struct CodeGenerator<'a> {
+    out: VmWriter<'a>,
+    class: &'a Class,
+    symbols: SymbolTable,
+    subroutine_kind: SubroutineKind,
+    label_id: usize,
+}
+
+impl<'a> CodeGenerator<'a> {
+    pub fn new(out: &'a mut dyn std::fmt::Write, class: &'a Class) -> Self {
+        Self{
+            out: VmWriter::new(out),
+            class,
+            symbols: SymbolTable::new(),
+            subroutine_kind: SubroutineKind::Function,
+            label_id: 0
+        }
+    }
+
+    // labels are unique in a class, since VM labels are not scoped by function
+    fn new_unique_label(&mut self, label: &str) -> String {
+        self.label_id += 1;
+        format!("{}.{}_{}", self.class.name.name, label, self.label_id)
+    }
+
+    pub fn compile_class(&mut self) -> Result<()> {
+        let class = self.class;
+        for var in &class.vars {
+            let kind = match var.kind {
+                ClassVarKind::Static => Kind::Static,
+                ClassVarKind::Field => Kind::Field,
+            };
+            for name in &var.names {
+                self.symbols.define(name, var.ty.clone(), kind)?;
+            }
+        }
+        for subroutine in &class.subroutines {
+            self.compile_subroutine(subroutine)?;
+        }
+        Ok(())
+    }
+
+    fn compile_subroutine(&mut self, subroutine: &Subroutine) -> Result<()> {
+        self.symbols.start_subroutine();
+        self.subroutine_kind = subroutine.kind;
+        if subroutine.kind == SubroutineKind::Method {
+            // argument 0 is the object on which the method is called
+            let this = Ident{ name: "this".to_string(), span: subroutine.name.span };
+            self.symbols.define(&this, Type::Class(self.class.name.name.clone()), Kind::Argument)?;
+        }
+        for (ty, name) in &subroutine.params {
+            self.symbols.define(name, ty.clone(), Kind::Argument)?;
+        }
+        for var in &subroutine.locals {
+            for name in &var.names {
+                self.symbols.define(name, var.ty.clone(), Kind::Local)?;
+            }
+        }
+
+        let funcname = format!("{}.{}", self.class.name.name, subroutine.name.name);
+        self.out.function(&funcname, self.symbols.var_count(Kind::Local));
+        match subroutine.kind {
+            SubroutineKind::Constructor => {
+                // this = Memory.alloc(number of fields)
+                self.out.push("constant", self.symbols.var_count(Kind::Field));
+                self.out.call("Memory.alloc", 1);
+                self.out.pop("pointer", 0);
+            },
+            SubroutineKind::Method => {
+                self.out.push("argument", 0);
+                self.out.pop("pointer", 0);
+            },
+            SubroutineKind::Function => ()
+        }
+        self.compile_statements(&subroutine.body)
+    }
+
+    fn compile_statements(&mut self, statements: &[Statement]) -> Result<()> {
+        for statement in statements {
+            self.compile_statement(statement)?;
+        }
+        Ok(())
+    }
+
+    fn compile_statement(&mut self, statement: &Statement) -> Result<()> {
+        match statement {
+            Statement::Let{ target, index: None, value } => {
+                let symbol = self.lookup(target)?;
+                self.compile_expression(value)?;
+                self.out.pop(symbol.kind.segment(), symbol.index);
+            },
+            Statement::Let{ target, index: Some(index), value } => {
+                let symbol = self.lookup(target)?;
+                self.out.push(symbol.kind.segment(), symbol.index);
+                self.compile_expression(index)?;
+                self.out.arithmetic(Arithmetic::Add);
+                self.compile_expression(value)?;
+                // the value is saved in temp 0, since evaluating it may change pointer 1
+                self.out.pop("temp", 0);
+                self.out.pop("pointer", 1);
+                self.out.push("temp", 0);
+                self.out.pop("that", 0);
+            },
+            Statement::If{ cond, then_branch, else_branch } => {
+                let if_false = self.new_unique_label("IF_FALSE");
+                let if_end = self.new_unique_label("IF_END");
+                self.compile_expression(cond)?;
+                self.out.arithmetic(Arithmetic::Not);
+                self.out.if_goto(&if_false);
+                self.compile_statements(then_branch)?;
+                match else_branch {
+                    Some(else_branch) => {
+                        self.out.goto(&if_end);
+                        self.out.label(&if_false);
+                        self.compile_statements(else_branch)?;
+                        self.out.label(&if_end);
+                    },
+                    None => self.out.label(&if_false)
+                }
+            },
+            Statement::While{ cond, body } => {
+                let while_exp = self.new_unique_label("WHILE_EXP");
+                let while_end = self.new_unique_label("WHILE_END");
+                self.out.label(&while_exp);
+                self.compile_expression(cond)?;
+                self.out.arithmetic(Arithmetic::Not);
+                self.out.if_goto(&while_end);
+                self.compile_statements(body)?;
+                self.out.goto(&while_exp);
+                self.out.label(&while_end);
+            },
+            Statement::Do(call) => {
+                self.compile_subroutine_call(call)?;
+                self.out.pop("temp", 0);    // discard the return value
+            },
+            Statement::Return(value) => {
+                match value {
+                    Some(value) => self.compile_expression(value)?,
+                    None => self.out.push("constant", 0)    // void function returns 0
+                }
+                self.out.ret();
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_expression(&mut self, expr: &Expression) -> Result<()> {
+        self.compile_term(&expr.head)?;
+        for (op, term) in &expr.tail {
+            self.compile_term(term)?;
+            match op {
+                BinaryOp::Add => self.out.arithmetic(Arithmetic::Add),
+                BinaryOp::Sub => self.out.arithmetic(Arithmetic::Sub),
+                BinaryOp::Mul => self.out.call("Math.multiply", 2),
+                BinaryOp::Div => self.out.call("Math.divide", 2),
+                BinaryOp::And => self.out.arithmetic(Arithmetic::And),
+                BinaryOp::Or  => self.out.arithmetic(Arithmetic::Or),
+                BinaryOp::Lt  => self.out.arithmetic(Arithmetic::Lt),
+                BinaryOp::Gt  => self.out.arithmetic(Arithmetic::Gt),
+                BinaryOp::Eq  => self.out.arithmetic(Arithmetic::Eq),
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_term(&mut self, term: &Term) -> Result<()> {
+        match term {
+            Term::Integer(n) => self.out.push("constant", *n),
+            Term::String(s) => {
+                // the tokenizer accepts only ASCII strings up to 32767 characters, so the casts are exact
+                self.out.push("constant", s.len() as i16);
+                self.out.call("String.new", 1);
+                for c in s.chars() {
+                    self.out.push("constant", c as i16);
+                    self.out.call("String.appendChar", 2);
+                }
+            },
+            Term::Keyword(KeywordConstant::True, _) => {
+                self.out.push("constant", 0);
+                self.out.arithmetic(Arithmetic::Not);
+            },
+            Term::Keyword(KeywordConstant::False, _) | Term::Keyword(KeywordConstant::Null, _) => {
+                self.out.push("constant", 0);
+            },
+            Term::Keyword(KeywordConstant::This, span) => {
+                if self.subroutine_kind == SubroutineKind::Function {
+                    return Err(CompileError::new(ErrorKind::ThisInFunction, *span));
+                }
+                self.out.push("pointer", 0);
+            },
+            Term::Var(name) => {
+                let symbol = self.lookup(name)?;
+                self.out.push(symbol.kind.segment(), symbol.index);
+            },
+            Term::Index(name, index) => {
+                let symbol = self.lookup(name)?;
+                self.out.push(symbol.kind.segment(), symbol.index);
+                self.compile_expression(index)?;
+                self.out.arithmetic(Arithmetic::Add);
+                self.out.pop("pointer", 1);
+                self.out.push("that", 0);
+            },
+            Term::Call(call) => self.compile_subroutine_call(call)?,
+            Term::Paren(expr) => self.compile_expression(expr)?,
+            Term::Unary(op, term) => {
+                self.compile_term(term)?;
+                match op {
+                    UnaryOp::Neg => self.out.arithmetic(Arithmetic::Neg),
+                    UnaryOp::Not => self.out.arithmetic(Arithmetic::Not),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_subroutine_call(&mut self, call: &SubroutineCall) -> Result<()> {
+        let (funcname, receiver) = match &call.receiver {
+            // method call on a variable: obj.f(args)
+            Some(receiver) if self.symbols.lookup(&receiver.name).is_some() => {
+                let symbol = self.lookup(receiver)?;
+                let class_name = match &symbol.ty {
+                    Type::Class(class_name) => class_name.clone(),
+                    _ => return Err(CompileError::new(ErrorKind::NotAnObject(receiver.name.clone()), receiver.span))
+                };
+                self.out.push(symbol.kind.segment(), symbol.index);
+                (format!("{}.{}", class_name, call.name.name), 1)
+            },
+            // function or constructor call: ClassName.f(args)
+            Some(class_name) => (format!("{}.{}", class_name.name, call.name.name), 0),
+            // subroutine of this class: f(args)
+            None => {
+                let is_function = self.class.subroutines.iter()
+                    .any(|s| s.name.name == call.name.name && s.kind != SubroutineKind::Method);
+                let receiver = if is_function { 0 } else {
+                    if self.subroutine_kind == SubroutineKind::Function {
+                        return Err(CompileError::new(ErrorKind::MethodCallFromFunction(call.name.name.clone()), call.name.span));
+                    }
+                    self.out.push("pointer", 0);
+                    1
+                };
+                (format!("{}.{}", self.class.name.name, call.name.name), receiver)
+            }
+        };
+        for arg in &call.args {
+            self.compile_expression(arg)?;
+        }
+        self.out.call(&funcname, call.args.len() + receiver);
+        Ok(())
+    }
+
+    fn lookup(&self, name: &Ident) -> Result<Symbol> {
+        let symbol = self.symbols.lookup(&name.name)
+            .ok_or_else(|| CompileError::new(ErrorKind::UndefinedVariable(name.name.clone()), name.span))?;
+        if symbol.kind == Kind::Field && self.subroutine_kind == SubroutineKind::Function {
+            return Err(CompileError::new(ErrorKind::FieldInFunction(name.name.clone()), name.span));
+        }
+        Ok(symbol.clone())
+    }
+}
@@ -0,0 +1,72 @@
+// position in a source file (both are 1-origin)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    // lexical errors
+    UnexpectedChar(char),
+    UnterminatedString,
+    NonAsciiChar(char),         // in a string constant
+    StringTooLong(usize),       // longer than 32767 characters
+    UnterminatedComment,
+    IntegerOverflow(String),
+
+    // syntax errors
+    UnexpectedToken{ expected: String, found: String },
+    UnexpectedEof{ expected: String },
+
+    // semantic errors
+    UndefinedVariable(String),
+    DuplicateVariable(String),
+    ThisInFunction,
+    FieldInFunction(String),
+    MethodCallFromFunction(String),
+    NotAnObject(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileError {
+    pub kind: ErrorKind,
+    pub span: Span,
+}
+
+pub type Result<T> = std::result::Result<T, CompileError>;
+
+impl CompileError {
+    pub fn new(kind: ErrorKind, span: Span) -> Self {
+        Self{ kind, span }
+    }
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ErrorKind::UnexpectedChar(c) => write!(f, "unexpected character '{}'", c),
+            ErrorKind::UnterminatedString => write!(f, "unterminated string constant"),
+            ErrorKind::NonAsciiChar(c) => write!(f, "non-ASCII character '{}' in string constant", c),
+            ErrorKind::StringTooLong(n) => write!(f, "string constant of {} characters is longer than 32767", n),
+            ErrorKind::UnterminatedComment => write!(f, "unterminated comment"),
+            ErrorKind::IntegerOverflow(s) => write!(f, "integer constant {} is out of range 0..32767", s),
+            ErrorKind::UnexpectedToken{ expected, found } => write!(f, "expected {}, found {}", expected, found),
+            ErrorKind::UnexpectedEof{ expected } => write!(f, "expected {}, found end of file", expected),
+            ErrorKind::UndefinedVariable(name) => write!(f, "undefined variable '{}'", name),
+            ErrorKind::DuplicateVariable(name) => write!(f, "variable '{}' is already defined", name),
+            ErrorKind::ThisInFunction => write!(f, "'this' cannot be used in a function"),
+            ErrorKind::FieldInFunction(name) => write!(f, "field '{}' cannot be used in a function", name),
+            ErrorKind::MethodCallFromFunction(name) => write!(f, "method '{}' cannot be called from a function without a receiver", name),
+            ErrorKind::NotAnObject(name) => write!(f, "'{}' is not an object", name),
+        }
+    }
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.span.line, self.span.column, self.kind)
+    }
+}
+
+impl std::error::Error for CompileError {}
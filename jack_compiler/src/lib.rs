@@ -0,0 +1,242 @@
+mod tokenizer;
+mod parser;
+mod symbol_table;
+mod writer;
+mod codegen;
+pub mod ast;
+pub mod error;
+
+pub use error::*;
+pub use parser::parse;
+
+// compile a Jack class into VM commands, which can be fed into vm_translator::compile
+pub fn compile(out: &mut dyn std::fmt::Write, source: &str) -> Result<()> {
+    compile_class(out, &parse(source)?)
+}
+
+// same as compile(), for a class already parsed (e.g. to name the VM file after the class)
+pub fn compile_class(out: &mut dyn std::fmt::Write, class: &ast::Class) -> Result<()> {
+    codegen::CodeGenerator::new(out, class).compile_class()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile_to_string(source: &str) -> Result<String> {
+        let mut vm = String::new();
+        compile(&mut vm, source)?;
+        Ok(vm)
+    }
+
+    fn assert_vm(source: &str, expected: &str) {
+        let vm = compile_to_string(source).unwrap();
+        let expected = expected.lines().map(|line| line.trim()).filter(|line| !line.is_empty()).collect::<Vec<_>>();
+        assert_eq!(vm.lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn function() {
+        assert_vm("
+        class Main {
+            function int add(int a, int b) {
+                var int c;
+                let c = a + b;
+                return c;
+            }
+        }", "
+        function Main.add 1
+        push argument 0
+        push argument 1
+        add
+        pop local 0
+        push local 0
+        return
+        ");
+    }
+
+    #[test]
+    fn constructor_and_method() {
+        assert_vm("
+        class Point {
+            field int x, y;
+            static int count;
+            constructor Point new(int ax, int ay) {
+                let x = ax;
+                let y = ay;
+                let count = count + 1;
+                return this;
+            }
+            method int sum() {
+                return x + y;
+            }
+            method void print() {
+                do Output.printInt(sum());
+                return;
+            }
+        }", "
+        function Point.new 0
+        push constant 2
+        call Memory.alloc 1
+        pop pointer 0
+        push argument 0
+        pop this 0
+        push argument 1
+        pop this 1
+        push static 0
+        push constant 1
+        add
+        pop static 0
+        push pointer 0
+        return
+        function Point.sum 0
+        push argument 0
+        pop pointer 0
+        push this 0
+        push this 1
+        add
+        return
+        function Point.print 0
+        push argument 0
+        pop pointer 0
+        push pointer 0
+        call Point.sum 1
+        call Output.printInt 1
+        pop temp 0
+        push constant 0
+        return
+        ");
+    }
+
+    #[test]
+    fn method_call_on_variable() {
+        assert_vm("
+        class Main {
+            function void main() {
+                var Point p;
+                let p = Point.new(1, 2);
+                do p.print();
+                return;
+            }
+        }", "
+        function Main.main 1
+        push constant 1
+        push constant 2
+        call Point.new 2
+        pop local 0
+        push local 0
+        call Point.print 1
+        pop temp 0
+        push constant 0
+        return
+        ");
+    }
+
+    #[test]
+    fn control_flow() {
+        assert_vm("
+        class Main {
+            function int loop(int n) {
+                var int i;
+                while (i < n) {
+                    if (i = 3) { let i = i + 2; } else { let i = i + 1; }
+                }
+                if (~true) { return 0; }
+                return i;
+            }
+        }", "
+        function Main.loop 1
+        label Main.WHILE_EXP_1
+        push local 0
+        push argument 0
+        lt
+        not
+        if-goto Main.WHILE_END_2
+        push local 0
+        push constant 3
+        eq
+        not
+        if-goto Main.IF_FALSE_3
+        push local 0
+        push constant 2
+        add
+        pop local 0
+        goto Main.IF_END_4
+        label Main.IF_FALSE_3
+        push local 0
+        push constant 1
+        add
+        pop local 0
+        label Main.IF_END_4
+        goto Main.WHILE_EXP_1
+        label Main.WHILE_END_2
+        push constant 0
+        not
+        not
+        not
+        if-goto Main.IF_FALSE_5
+        push constant 0
+        return
+        label Main.IF_FALSE_5
+        push local 0
+        return
+        ");
+    }
+
+    #[test]
+    fn array_and_string() {
+        assert_vm("
+        class Main {
+            function void main() {
+                var Array a;
+                let a[1] = a[2] * 3;
+                do Output.printString(\"Hi\");
+                return;
+            }
+        }", "
+        function Main.main 1
+        push local 0
+        push constant 1
+        add
+        push local 0
+        push constant 2
+        add
+        pop pointer 1
+        push that 0
+        push constant 3
+        call Math.multiply 2
+        pop temp 0
+        pop pointer 1
+        push temp 0
+        pop that 0
+        push constant 2
+        call String.new 1
+        push constant 72
+        call String.appendChar 2
+        push constant 105
+        call String.appendChar 2
+        call Output.printString 1
+        pop temp 0
+        push constant 0
+        return
+        ");
+    }
+
+    #[test]
+    fn semantic_errors() {
+        let err = compile_to_string("class Main {\n  function void main() {\n    let x = 1;\n  }\n}").unwrap_err();
+        assert_eq!(err, CompileError::new(ErrorKind::UndefinedVariable("x".to_string()), Span{ line: 3, column: 9 }));
+
+        let err = compile_to_string("class Main {\n  field int x;\n  function int f() { return x; }\n}").unwrap_err();
+        assert_eq!(err, CompileError::new(ErrorKind::FieldInFunction("x".to_string()), Span{ line: 3, column: 29 }));
+
+        let err = compile_to_string("class Main {\n  function Main f() { return this; }\n}").unwrap_err();
+        assert_eq!(err, CompileError::new(ErrorKind::ThisInFunction, Span{ line: 2, column: 30 }));
+
+        let err = compile_to_string("class Main {\n  method void m() { return; }\n  function void f() { do m(); return; }\n}").unwrap_err();
+        assert_eq!(err, CompileError::new(ErrorKind::MethodCallFromFunction("m".to_string()), Span{ line: 3, column: 26 }));
+
+        let err = compile_to_string("class Main {\n  function void f(int a, int a) { return; }\n}").unwrap_err();
+        assert_eq!(err.to_string(), "2:30: variable 'a' is already defined");
+    }
+}
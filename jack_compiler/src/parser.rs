@@ -0,0 +1,394 @@
+use crate::ast::*;
+use crate::error::*;
+use crate::tokenizer::*;
+
+struct Parser {
+    tokens: Vec<(Token, Span)>,
+    pos: usize,
+    eof: Span,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+    fn next(&mut self, expected: &str) -> Result<(Token, Span)> {
+        match self.tokens.get(self.pos) {
+            Some(token) => {
+                self.pos += 1;
+                Ok(token.clone())
+            },
+            None => Err(CompileError::new(ErrorKind::UnexpectedEof{ expected: expected.to_string() }, self.eof))
+        }
+    }
+    fn unexpected<T>(&self, expected: &str) -> Result<T> {
+        match self.tokens.get(self.pos) {
+            Some((token, span)) => Err(CompileError::new(
+                ErrorKind::UnexpectedToken{ expected: expected.to_string(), found: token.to_string() }, *span)),
+            None => Err(CompileError::new(ErrorKind::UnexpectedEof{ expected: expected.to_string() }, self.eof))
+        }
+    }
+    fn is_symbol(&self, c: char) -> bool {
+        self.peek() == Some(&Token::Symbol(c))
+    }
+    fn is_keyword(&self, k: Keyword) -> bool {
+        self.peek() == Some(&Token::Keyword(k))
+    }
+    fn expect_symbol(&mut self, c: char) -> Result<()> {
+        if self.is_symbol(c) { self.pos += 1; Ok(()) } else { self.unexpected(&format!("'{}'", c)) }
+    }
+    fn expect_keyword(&mut self, k: Keyword) -> Result<()> {
+        if self.is_keyword(k) { self.pos += 1; Ok(()) } else { self.unexpected(&format!("'{}'", k.as_str())) }
+    }
+    fn identifier(&mut self) -> Result<Ident> {
+        match self.next("identifier")? {
+            (Token::Identifier(name), span) => Ok(Ident{ name, span }),
+            _ => { self.pos -= 1; self.unexpected("identifier") }
+        }
+    }
+
+    // class: 'class' className '{' classVarDec* subroutineDec* '}'
+    fn class(&mut self) -> Result<Class> {
+        self.expect_keyword(Keyword::Class)?;
+        let name = self.identifier()?;
+        self.expect_symbol('{')?;
+        let mut vars = Vec::new();
+        while self.is_keyword(Keyword::Static) || self.is_keyword(Keyword::Field) {
+            vars.push(self.class_var_dec()?);
+        }
+        let mut subroutines = Vec::new();
+        while !self.is_symbol('}') {
+            subroutines.push(self.subroutine()?);
+        }
+        self.expect_symbol('}')?;
+        if self.peek().is_some() {
+            return self.unexpected("end of file");
+        }
+        Ok(Class{ name, vars, subroutines })
+    }
+
+    // classVarDec: ('static' | 'field') type varName (',' varName)* ';'
+    fn class_var_dec(&mut self) -> Result<ClassVarDec> {
+        let kind = match self.next("'static' or 'field'")? {
+            (Token::Keyword(Keyword::Static), _) => ClassVarKind::Static,
+            _ => ClassVarKind::Field
+        };
+        let ty = self.ty()?;
+        let names = self.var_names()?;
+        Ok(ClassVarDec{ kind, ty, names })
+    }
+
+    // varName (',' varName)* ';'
+    fn var_names(&mut self) -> Result<Vec<Ident>> {
+        let mut names = vec![self.identifier()?];
+        while self.is_symbol(',') {
+            self.pos += 1;
+            names.push(self.identifier()?);
+        }
+        self.expect_symbol(';')?;
+        Ok(names)
+    }
+
+    // type: 'int' | 'char' | 'boolean' | className
+    fn ty(&mut self) -> Result<Type> {
+        let ty = match self.peek() {
+            Some(Token::Keyword(Keyword::Int)) => Type::Int,
+            Some(Token::Keyword(Keyword::Char)) => Type::Char,
+            Some(Token::Keyword(Keyword::Boolean)) => Type::Boolean,
+            Some(Token::Identifier(name)) => Type::Class(name.clone()),
+            _ => return self.unexpected("type")
+        };
+        self.pos += 1;
+        Ok(ty)
+    }
+
+    // subroutineDec: ('constructor' | 'function' | 'method') ('void' | type) subroutineName
+    //                '(' parameterList ')' subroutineBody
+    fn subroutine(&mut self) -> Result<Subroutine> {
+        let kind = match self.peek() {
+            Some(Token::Keyword(Keyword::Constructor)) => SubroutineKind::Constructor,
+            Some(Token::Keyword(Keyword::Function)) => SubroutineKind::Function,
+            Some(Token::Keyword(Keyword::Method)) => SubroutineKind::Method,
+            _ => return self.unexpected("'constructor', 'function' or 'method'")
+        };
+        self.pos += 1;
+        let return_type = if self.is_keyword(Keyword::Void) {
+            self.pos += 1;
+            None
+        } else {
+            Some(self.ty()?)
+        };
+        let name = self.identifier()?;
+        self.expect_symbol('(')?;
+        let mut params = Vec::new();
+        if !self.is_symbol(')') {
+            loop {
+                let ty = self.ty()?;
+                params.push((ty, self.identifier()?));
+                if !self.is_symbol(',') { break; }
+                self.pos += 1;
+            }
+        }
+        self.expect_symbol(')')?;
+        self.expect_symbol('{')?;
+        let mut locals = Vec::new();
+        while self.is_keyword(Keyword::Var) {
+            self.pos += 1;
+            let ty = self.ty()?;
+            locals.push(VarDec{ ty, names: self.var_names()? });
+        }
+        let body = self.statements()?;
+        self.expect_symbol('}')?;
+        Ok(Subroutine{ kind, return_type, name, params, locals, body })
+    }
+
+    // statements: statement*
+    fn statements(&mut self) -> Result<Vec<Statement>> {
+        let mut statements = Vec::new();
+        while !self.is_symbol('}') {
+            statements.push(self.statement()?);
+        }
+        Ok(statements)
+    }
+
+    // '{' statements '}'
+    fn block(&mut self) -> Result<Vec<Statement>> {
+        self.expect_symbol('{')?;
+        let statements = self.statements()?;
+        self.expect_symbol('}')?;
+        Ok(statements)
+    }
+
+    fn statement(&mut self) -> Result<Statement> {
+        let keyword = match self.peek() {
+            Some(Token::Keyword(k)) => *k,
+            _ => return self.unexpected("statement")
+        };
+        match keyword {
+            Keyword::Let => {
+                self.pos += 1;
+                let target = self.identifier()?;
+                let index = if self.is_symbol('[') {
+                    self.pos += 1;
+                    let index = self.expression()?;
+                    self.expect_symbol(']')?;
+                    Some(index)
+                } else { None };
+                self.expect_symbol('=')?;
+                let value = self.expression()?;
+                self.expect_symbol(';')?;
+                Ok(Statement::Let{ target, index, value })
+            },
+            Keyword::If => {
+                self.pos += 1;
+                self.expect_symbol('(')?;
+                let cond = self.expression()?;
+                self.expect_symbol(')')?;
+                let then_branch = self.block()?;
+                let else_branch = if self.is_keyword(Keyword::Else) {
+                    self.pos += 1;
+                    Some(self.block()?)
+                } else { None };
+                Ok(Statement::If{ cond, then_branch, else_branch })
+            },
+            Keyword::While => {
+                self.pos += 1;
+                self.expect_symbol('(')?;
+                let cond = self.expression()?;
+                self.expect_symbol(')')?;
+                let body = self.block()?;
+                Ok(Statement::While{ cond, body })
+            },
+            Keyword::Do => {
+                self.pos += 1;
+                let name = self.identifier()?;
+                let call = self.subroutine_call(name)?;
+                self.expect_symbol(';')?;
+                Ok(Statement::Do(call))
+            },
+            Keyword::Return => {
+                self.pos += 1;
+                let value = if self.is_symbol(';') { None } else { Some(self.expression()?) };
+                self.expect_symbol(';')?;
+                Ok(Statement::Return(value))
+            },
+            _ => self.unexpected("statement")
+        }
+    }
+
+    // expression: term (op term)*
+    fn expression(&mut self) -> Result<Expression> {
+        let head = self.term()?;
+        let mut tail = Vec::new();
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol('+')) => BinaryOp::Add,
+                Some(Token::Symbol('-')) => BinaryOp::Sub,
+                Some(Token::Symbol('*')) => BinaryOp::Mul,
+                Some(Token::Symbol('/')) => BinaryOp::Div,
+                Some(Token::Symbol('&')) => BinaryOp::And,
+                Some(Token::Symbol('|')) => BinaryOp::Or,
+                Some(Token::Symbol('<')) => BinaryOp::Lt,
+                Some(Token::Symbol('>')) => BinaryOp::Gt,
+                Some(Token::Symbol('=')) => BinaryOp::Eq,
+                _ => return Ok(Expression{ head, tail })
+            };
+            self.pos += 1;
+            tail.push((op, self.term()?));
+        }
+    }
+
+    fn term(&mut self) -> Result<Term> {
+        let (token, span) = self.next("term")?;
+        let term = match token {
+            Token::IntegerConstant(n) => Term::Integer(n),
+            Token::StringConstant(s) => Term::String(s),
+            Token::Keyword(Keyword::True) => Term::Keyword(KeywordConstant::True, span),
+            Token::Keyword(Keyword::False) => Term::Keyword(KeywordConstant::False, span),
+            Token::Keyword(Keyword::Null) => Term::Keyword(KeywordConstant::Null, span),
+            Token::Keyword(Keyword::This) => Term::Keyword(KeywordConstant::This, span),
+            Token::Symbol('(') => {
+                let expr = self.expression()?;
+                self.expect_symbol(')')?;
+                Term::Paren(Box::new(expr))
+            },
+            Token::Symbol('-') => Term::Unary(UnaryOp::Neg, Box::new(self.term()?)),
+            Token::Symbol('~') => Term::Unary(UnaryOp::Not, Box::new(self.term()?)),
+            Token::Identifier(name) => {
+                let ident = Ident{ name, span };
+                match self.peek() {
+                    Some(Token::Symbol('[')) => {
+                        self.pos += 1;
+                        let index = self.expression()?;
+                        self.expect_symbol(']')?;
+                        Term::Index(ident, Box::new(index))
+                    },
+                    Some(Token::Symbol('(')) | Some(Token::Symbol('.')) => Term::Call(self.subroutine_call(ident)?),
+                    _ => Term::Var(ident)
+                }
+            },
+            _ => { self.pos -= 1; return self.unexpected("term") }
+        };
+        Ok(term)
+    }
+
+    // subroutineCall: subroutineName '(' expressionList ')'
+    //               | (className | varName) '.' subroutineName '(' expressionList ')'
+    // the first identifier has already been consumed.
+    fn subroutine_call(&mut self, first: Ident) -> Result<SubroutineCall> {
+        let (receiver, name) = if self.is_symbol('.') {
+            self.pos += 1;
+            (Some(first), self.identifier()?)
+        } else {
+            (None, first)
+        };
+        self.expect_symbol('(')?;
+        let mut args = Vec::new();
+        if !self.is_symbol(')') {
+            loop {
+                args.push(self.expression()?);
+                if !self.is_symbol(',') { break; }
+                self.pos += 1;
+            }
+        }
+        self.expect_symbol(')')?;
+        Ok(SubroutineCall{ receiver, name, args })
+    }
+}
+
+// parse a Jack source file which contains a single class
+pub fn parse(source: &str) -> Result<Class> {
+    let tokens = tokenize(source)?;
+    let eof = {
+        let line = source.lines().count().max(1);
+        let column = source.lines().last().map(|l| l.chars().count()).unwrap_or(0) + 1;
+        Span{ line, column }
+    };
+    Parser{ tokens, pos: 0, eof }.class()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ident(name: &str, line: usize, column: usize) -> Ident {
+        Ident{ name: name.to_string(), span: Span{ line, column } }
+    }
+
+    #[test]
+    fn test_class() {
+        let class = parse("
+class Point {
+    field int x, y;
+    static Point origin;
+    constructor Point new(int ax, int ay) {
+        let x = ax;
+        let y = ay;
+        return this;
+    }
+    method int getX() { return x; }
+}").unwrap();
+        assert_eq!(class.name, ident("Point", 2, 7));
+        assert_eq!(class.vars, vec![
+            ClassVarDec{ kind: ClassVarKind::Field, ty: Type::Int, names: vec![ident("x", 3, 15), ident("y", 3, 18)] },
+            ClassVarDec{ kind: ClassVarKind::Static, ty: Type::Class("Point".to_string()), names: vec![ident("origin", 4, 18)] },
+        ]);
+        assert_eq!(class.subroutines.len(), 2);
+        let new = &class.subroutines[0];
+        assert_eq!(new.kind, SubroutineKind::Constructor);
+        assert_eq!(new.return_type, Some(Type::Class("Point".to_string())));
+        assert_eq!(new.params, vec![(Type::Int, ident("ax", 5, 31)), (Type::Int, ident("ay", 5, 39))]);
+        assert_eq!(new.body.len(), 3);
+        assert_eq!(new.body[2], Statement::Return(Some(Expression{
+            head: Term::Keyword(KeywordConstant::This, Span{ line: 8, column: 16 }),
+            tail: vec![]
+        })));
+        assert_eq!(class.subroutines[1].kind, SubroutineKind::Method);
+    }
+
+    fn parse_statement(source: &str) -> Statement {
+        let class = parse(&format!("class Main {{ function void main() {{ {} }} }}", source)).unwrap();
+        class.subroutines[0].body[0].clone()
+    }
+
+    #[test]
+    fn test_expression() {
+        // no operator precedence in Jack: 1 + 2 * 3 is (1 + 2) * 3
+        assert_eq!(parse_statement("return 1 + 2 * -x;"), Statement::Return(Some(Expression{
+            head: Term::Integer(1),
+            tail: vec![
+                (BinaryOp::Add, Term::Integer(2)),
+                (BinaryOp::Mul, Term::Unary(UnaryOp::Neg, Box::new(Term::Var(ident("x", 1, 53))))),
+            ]
+        })));
+    }
+
+    #[test]
+    fn test_subroutine_call() {
+        let call = match parse_statement("do Output.printInt(a[1], f());") {
+            Statement::Do(call) => call,
+            _ => panic!()
+        };
+        assert_eq!(call.receiver, Some(ident("Output", 1, 40)));
+        assert_eq!(call.name.name, "printInt");
+        assert_eq!(call.args.len(), 2);
+        match &call.args[1].head {
+            Term::Call(f) => {
+                assert_eq!(f.receiver, None);
+                assert_eq!(f.name.name, "f");
+            },
+            _ => panic!()
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = parse("class Main {\n  function void main() {\n    let x = ;\n  }\n}").unwrap_err();
+        assert_eq!(err.span, Span{ line: 3, column: 13 });
+        assert_eq!(err.kind, ErrorKind::UnexpectedToken{ expected: "term".to_string(), found: "';'".to_string() });
+
+        let err = parse("class Main {\n  function void main() {\n").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::UnexpectedEof{ expected: "statement".to_string() });
+        assert_eq!(err.span.line, 2);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::ast::*;
+use crate::error::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind { Static, Field, Argument, Local }
+
+impl Kind {
+    // VM memory segment which holds variables of this kind
+    pub fn segment(self) -> &'static str {
+        match self {
+            Kind::Static    => "static",
+            Kind::Field     => "this",
+            Kind::Argument  => "argument",
+            Kind::Local     => "local",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub ty: Type,
+    pub kind: Kind,
+    pub index: i16,
+}
+
+// class-level scope (static, field) and subroutine-level scope (argument, local)
+#[derive(Default)]
+pub struct SymbolTable {
+    class_scope: HashMap<String, Symbol>,
+    subroutine_scope: HashMap<String, Symbol>,
+    counts: HashMap<Kind, i16>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // clear the subroutine-level scope
+    pub fn start_subroutine(&mut self) {
+        self.subroutine_scope.clear();
+        self.counts.remove(&Kind::Argument);
+        self.counts.remove(&Kind::Local);
+    }
+    pub fn define(&mut self, name: &Ident, ty: Type, kind: Kind) -> Result<()> {
+        let scope = match kind {
+            Kind::Static | Kind::Field => &mut self.class_scope,
+            Kind::Argument | Kind::Local => &mut self.subroutine_scope,
+        };
+        if scope.contains_key(&name.name) {
+            return Err(CompileError::new(ErrorKind::DuplicateVariable(name.name.clone()), name.span));
+        }
+        let count = self.counts.entry(kind).or_insert(0);
+        scope.insert(name.name.clone(), Symbol{ ty, kind, index: *count });
+        *count += 1;
+        Ok(())
+    }
+    pub fn var_count(&self, kind: Kind) -> i16 {
+        self.counts.get(&kind).cloned().unwrap_or(0)
+    }
+    // subroutine-level symbols shadow class-level ones
+    pub fn lookup(&self, name: &str) -> Option<&Symbol> {
+        self.subroutine_scope.get(name).or_else(|| self.class_scope.get(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ident(name: &str) -> Ident {
+        Ident{ name: name.to_string(), span: Span{ line: 1, column: 1 } }
+    }
+
+    #[test]
+    fn test_define_and_lookup() {
+        let mut table = SymbolTable::new();
+        table.define(&ident("x"), Type::Int, Kind::Field).unwrap();
+        table.define(&ident("y"), Type::Int, Kind::Field).unwrap();
+        table.define(&ident("count"), Type::Int, Kind::Static).unwrap();
+        assert_eq!(table.var_count(Kind::Field), 2);
+        assert_eq!(table.lookup("y"), Some(&Symbol{ ty: Type::Int, kind: Kind::Field, index: 1 }));
+        assert_eq!(table.lookup("count"), Some(&Symbol{ ty: Type::Int, kind: Kind::Static, index: 0 }));
+
+        table.start_subroutine();
+        table.define(&ident("this"), Type::Class("Point".to_string()), Kind::Argument).unwrap();
+        table.define(&ident("x"), Type::Boolean, Kind::Local).unwrap();
+        assert_eq!(table.lookup("x"), Some(&Symbol{ ty: Type::Boolean, kind: Kind::Local, index: 0 }));
+
+        table.start_subroutine();
+        assert_eq!(table.var_count(Kind::Local), 0);
+        assert_eq!(table.lookup("x"), Some(&Symbol{ ty: Type::Int, kind: Kind::Field, index: 0 }));
+        assert_eq!(table.lookup("z"), None);
+    }
+
+    #[test]
+    fn test_duplicate() {
+        let mut table = SymbolTable::new();
+        table.define(&ident("a"), Type::Int, Kind::Argument).unwrap();
+        assert_eq!(
+            table.define(&ident("a"), Type::Int, Kind::Local).map_err(|e| e.kind),
+            Err(ErrorKind::DuplicateVariable("a".to_string())));
+    }
+}
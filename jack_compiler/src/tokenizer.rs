@@ -0,0 +1,241 @@
+use crate::error::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keyword {
+    Class, Constructor, Function, Method, Field, Static, Var,
+    Int, Char, Boolean, Void, True, False, Null, This,
+    Let, Do, If, Else, While, Return,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    Keyword(Keyword),
+    Symbol(char),
+    IntegerConstant(i16),
+    StringConstant(String),
+    Identifier(String),
+}
+
+impl Keyword {
+    fn from_word(s: &str) -> Option<Self> {
+        use Keyword::*;
+        let keyword = match s {
+            "class" => Class, "constructor" => Constructor, "function" => Function, "method" => Method,
+            "field" => Field, "static" => Static, "var" => Var,
+            "int" => Int, "char" => Char, "boolean" => Boolean, "void" => Void,
+            "true" => True, "false" => False, "null" => Null, "this" => This,
+            "let" => Let, "do" => Do, "if" => If, "else" => Else, "while" => While, "return" => Return,
+            _ => return None
+        };
+        Some(keyword)
+    }
+    pub fn as_str(&self) -> &'static str {
+        use Keyword::*;
+        match self {
+            Class => "class", Constructor => "constructor", Function => "function", Method => "method",
+            Field => "field", Static => "static", Var => "var",
+            Int => "int", Char => "char", Boolean => "boolean", Void => "void",
+            True => "true", False => "false", Null => "null", This => "this",
+            Let => "let", Do => "do", If => "if", Else => "else", While => "while", Return => "return",
+        }
+    }
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Token::Keyword(k) => write!(f, "keyword '{}'", k.as_str()),
+            Token::Symbol(c) => write!(f, "'{}'", c),
+            Token::IntegerConstant(n) => write!(f, "integer {}", n),
+            Token::StringConstant(s) => write!(f, "string \"{}\"", s),
+            Token::Identifier(s) => write!(f, "identifier '{}'", s),
+        }
+    }
+}
+
+const SYMBOLS: &str = "{}()[].,;+-*/&|<>=~";
+
+struct Cursor<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    span: Span,
+}
+
+impl<'a> Cursor<'a> {
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().cloned()
+    }
+    fn peek2(&self) -> Option<char> {
+        let mut chars = self.chars.clone();
+        chars.next();
+        chars.next()
+    }
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.span.line += 1;
+            self.span.column = 1;
+        } else {
+            self.span.column += 1;
+        }
+        Some(c)
+    }
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> String {
+        let mut s = String::new();
+        while let Some(c) = self.peek() {
+            if !f(c) { break; }
+            s.push(c);
+            self.next();
+        }
+        s
+    }
+    // skip white spaces and comments
+    fn skip_trivia(&mut self) -> Result<()> {
+        loop {
+            match (self.peek(), self.peek2()) {
+                (Some(c), _) if c.is_whitespace() => { self.next(); },
+                (Some('/'), Some('/')) => { self.take_while(|c| c != '\n'); },
+                (Some('/'), Some('*')) => {
+                    let start = self.span;
+                    self.next();
+                    self.next();
+                    loop {
+                        match self.next() {
+                            Some('*') if self.peek() == Some('/') => { self.next(); break; },
+                            Some(_) => (),
+                            None => return Err(CompileError::new(ErrorKind::UnterminatedComment, start))
+                        }
+                    }
+                },
+                _ => return Ok(())
+            }
+        }
+    }
+}
+
+pub fn tokenize(source: &str) -> Result<Vec<(Token, Span)>> {
+    let mut cursor = Cursor{ chars: source.chars().peekable(), span: Span{ line: 1, column: 1 } };
+    let mut tokens = Vec::new();
+    loop {
+        cursor.skip_trivia()?;
+        let span = cursor.span;
+        let c = match cursor.peek() {
+            Some(c) => c,
+            None => return Ok(tokens)
+        };
+        let token = if SYMBOLS.contains(c) {
+            cursor.next();
+            Token::Symbol(c)
+        }
+        else if c.is_ascii_digit() {
+            let digits = cursor.take_while(|c| c.is_ascii_digit());
+            match digits.parse::<i16>() {
+                Ok(n) => Token::IntegerConstant(n),
+                Err(_) => return Err(CompileError::new(ErrorKind::IntegerOverflow(digits), span))
+            }
+        }
+        else if c == '"' {
+            cursor.next();
+            let mut s = String::new();
+            while let Some(c) = cursor.peek() {
+                if c == '"' || c == '\n' { break; }
+                // each character is pushed as an integer constant
+                if !c.is_ascii() {
+                    return Err(CompileError::new(ErrorKind::NonAsciiChar(c), cursor.span));
+                }
+                s.push(c);
+                cursor.next();
+            }
+            if cursor.next() != Some('"') {
+                return Err(CompileError::new(ErrorKind::UnterminatedString, span));
+            }
+            // the length is the argument of String.new
+            if s.len() > i16::MAX as usize {
+                return Err(CompileError::new(ErrorKind::StringTooLong(s.len()), span));
+            }
+            Token::StringConstant(s)
+        }
+        else if c.is_ascii_alphabetic() || c == '_' {
+            let word = cursor.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+            match Keyword::from_word(&word) {
+                Some(keyword) => Token::Keyword(keyword),
+                None => Token::Identifier(word)
+            }
+        }
+        else {
+            return Err(CompileError::new(ErrorKind::UnexpectedChar(c), span));
+        };
+        tokens.push((token, span));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(source: &str) -> Vec<Token> {
+        tokenize(source).unwrap().into_iter().map(|(token, _)| token).collect()
+    }
+
+    #[test]
+    fn test_tokens() {
+        assert_eq!(tokens("let x = x + 1;"), vec![
+            Token::Keyword(Keyword::Let),
+            Token::Identifier("x".to_string()),
+            Token::Symbol('='),
+            Token::Identifier("x".to_string()),
+            Token::Symbol('+'),
+            Token::IntegerConstant(1),
+            Token::Symbol(';'),
+        ]);
+        assert_eq!(tokens("do Output.printString(\"Hello, world\");"), vec![
+            Token::Keyword(Keyword::Do),
+            Token::Identifier("Output".to_string()),
+            Token::Symbol('.'),
+            Token::Identifier("printString".to_string()),
+            Token::Symbol('('),
+            Token::StringConstant("Hello, world".to_string()),
+            Token::Symbol(')'),
+            Token::Symbol(';'),
+        ]);
+    }
+
+    #[test]
+    fn test_comments() {
+        let source = "
+        /** API comment */
+        var int i; // line comment
+        /* block
+           comment */ return;
+        ";
+        assert_eq!(tokens(source), vec![
+            Token::Keyword(Keyword::Var),
+            Token::Keyword(Keyword::Int),
+            Token::Identifier("i".to_string()),
+            Token::Symbol(';'),
+            Token::Keyword(Keyword::Return),
+            Token::Symbol(';'),
+        ]);
+    }
+
+    #[test]
+    fn test_span() {
+        let tokens = tokenize("class Main {\n  field int x;\n}").unwrap();
+        assert_eq!(tokens[0].1, Span{ line: 1, column: 1 });
+        assert_eq!(tokens[1].1, Span{ line: 1, column: 7 });
+        assert_eq!(tokens[3].1, Span{ line: 2, column: 3 });
+        assert_eq!(tokens[7].1, Span{ line: 3, column: 1 });
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(tokenize("let x = 32768;"), Err(CompileError::new(ErrorKind::IntegerOverflow("32768".to_string()), Span{ line: 1, column: 9 })));
+        assert_eq!(tokenize("let s = \"abc;\n"), Err(CompileError::new(ErrorKind::UnterminatedString, Span{ line: 1, column: 9 })));
+        assert_eq!(tokenize("let s = \"caf\u{e9}\";"), Err(CompileError::new(ErrorKind::NonAsciiChar('\u{e9}'), Span{ line: 1, column: 13 })));
+        assert_eq!(tokenize("let s = \"\u{1f600}\";"), Err(CompileError::new(ErrorKind::NonAsciiChar('\u{1f600}'), Span{ line: 1, column: 10 })));
+        let long = format!("let s = \"{}\";", "a".repeat(32768));
+        assert_eq!(tokenize(&long), Err(CompileError::new(ErrorKind::StringTooLong(32768), Span{ line: 1, column: 9 })));
+        assert!(tokenize(&format!("let s = \"{}\";", "a".repeat(32767))).is_ok());
+        assert_eq!(tokenize("x /* comment"), Err(CompileError::new(ErrorKind::UnterminatedComment, Span{ line: 1, column: 3 })));
+        assert_eq!(tokenize("\n  x # y"), Err(CompileError::new(ErrorKind::UnexpectedChar('#'), Span{ line: 2, column: 5 })));
+    }
+}
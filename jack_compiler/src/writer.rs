@@ -0,0 +1,50 @@
+#[derive(Debug, Clone, Copy)]
+pub enum Arithmetic { Add, Sub, Neg, Eq, Gt, Lt, And, Or, Not }
+
+pub struct VmWriter<'a> {
+    out: &'a mut dyn std::fmt::Write,
+}
+
+impl<'a> VmWriter<'a> {
+    pub fn new(out: &'a mut dyn std::fmt::Write) -> Self {
+        Self{ out }
+    }
+    pub fn push(&mut self, segment: &str, index: i16) {
+        writeln!(self.out, "push {} {}", segment, index).unwrap();
+    }
+    pub fn pop(&mut self, segment: &str, index: i16) {
+        writeln!(self.out, "pop {} {}", segment, index).unwrap();
+    }
+    pub fn arithmetic(&mut self, command: Arithmetic) {
+        let command = match command {
+            Arithmetic::Add => "add",
+            Arithmetic::Sub => "sub",
+            Arithmetic::Neg => "neg",
+            Arithmetic::Eq  => "eq",
+            Arithmetic::Gt  => "gt",
+            Arithmetic::Lt  => "lt",
+            Arithmetic::And => "and",
+            Arithmetic::Or  => "or",
+            Arithmetic::Not => "not",
+        };
+        writeln!(self.out, "{}", command).unwrap();
+    }
+    pub fn label(&mut self, label: &str) {
+        writeln!(self.out, "label {}", label).unwrap();
+    }
+    pub fn goto(&mut self, label: &str) {
+        writeln!(self.out, "goto {}", label).unwrap();
+    }
+    pub fn if_goto(&mut self, label: &str) {
+        writeln!(self.out, "if-goto {}", label).unwrap();
+    }
+    pub fn call(&mut self, funcname: &str, nargs: usize) {
+        writeln!(self.out, "call {} {}", funcname, nargs).unwrap();
+    }
+    pub fn function(&mut self, funcname: &str, nlocals: i16) {
+        writeln!(self.out, "function {} {}", funcname, nlocals).unwrap();
+    }
+    pub fn ret(&mut self) {
+        writeln!(self.out, "return").unwrap();
+    }
+}
@@ -1,17 +1,17 @@
 use crate::given::*;
 use crate::gate::*;
 
-pub fn half_adder(a: bool, b: bool) -> [bool; 2] {
+pub fn half_adder<S: Signal>(a: S, b: S) -> [S; 2] {
     [xor(a, b), and(a, b)]
 }
 
-pub fn full_adder(a: bool, b: bool, carry: bool) -> [bool; 2] {
+pub fn full_adder<S: Signal>(a: S, b: S, carry: S) -> [S; 2] {
     let [sum1, carry1] = half_adder(a, b);
     let [sum2, carry2] = half_adder(sum1, carry);
     [sum2, or(carry1, carry2)]
 }
 
-pub fn add16(a: Word, b: Word) -> Word {
+pub fn add16<S: Signal>(a: Word<S>, b: Word<S>) -> Word<S> {
     let [sum00, carry] = half_adder(a[ 0], b[ 0]);
     let [sum01, carry] = full_adder(a[ 1], b[ 1], carry);
     let [sum02, carry] = full_adder(a[ 2], b[ 2], carry);
@@ -36,8 +36,8 @@ pub fn add16(a: Word, b: Word) -> Word {
     ]
 }
 
-pub fn inc16(a: Word) -> Word {
-    let [sum00, carry] = half_adder(a[ 0], true);
+pub fn inc16<S: Signal>(a: Word<S>) -> Word<S> {
+    let [sum00, carry] = half_adder(a[ 0], S::constant(true));
     let [sum01, carry] = half_adder(a[ 1], carry);
     let [sum02, carry] = half_adder(a[ 2], carry);
     let [sum03, carry] = half_adder(a[ 3], carry);
@@ -61,6 +61,192 @@ pub fn inc16(a: Word) -> Word {
     ]
 }
 
+// 16-bit adder designs. the ALU can be built with any of them (see alu::alu_with)
+pub trait Adder {
+    fn add16<S: Signal>(a: Word<S>, b: Word<S>) -> Word<S>;
+}
+
+pub struct RippleCarry;
+pub struct CarryLookahead;
+pub struct CarrySelect;
+pub struct KoggeStone;
+
+impl Adder for RippleCarry {
+    fn add16<S: Signal>(a: Word<S>, b: Word<S>) -> Word<S> {
+        add16(a, b)
+    }
+}
+
+impl Adder for CarryLookahead {
+    fn add16<S: Signal>(a: Word<S>, b: Word<S>) -> Word<S> {
+        add16_lookahead(a, b)
+    }
+}
+
+impl Adder for CarrySelect {
+    fn add16<S: Signal>(a: Word<S>, b: Word<S>) -> Word<S> {
+        add16_select(a, b)
+    }
+}
+
+impl Adder for KoggeStone {
+    fn add16<S: Signal>(a: Word<S>, b: Word<S>) -> Word<S> {
+        add16_kogge_stone(a, b)
+    }
+}
+
+// carries into each bit of a 4-bit block from the generate bits g, the propagate bits p and the carry c
+fn carries4<S: Signal>(g: [S; 4], p: [S; 4], c: S) -> [S; 4] {
+    [
+        c,
+        or(g[0], and(p[0], c)),
+        or(or(g[1], and(p[1], g[0])), and(and(p[1], p[0]), c)),
+        or(or(g[2], and(p[2], g[1])), or(and(and(p[2], p[1]), g[0]), and(and(p[2], p[1]), and(p[0], c)))),
+    ]
+}
+
+// generate and propagate bits of a 4-bit block
+fn group4<S: Signal>(g: [S; 4], p: [S; 4]) -> [S; 2] {
+    [
+        or(or(g[3], and(p[3], g[2])), or(and(and(p[3], p[2]), g[1]), and(and(p[3], p[2]), and(p[1], g[0])))),
+        and(and(p[3], p[2]), and(p[1], p[0])),
+    ]
+}
+
+// two-level carry-lookahead adder of 4-bit blocks
+pub fn add16_lookahead<S: Signal>(a: Word<S>, b: Word<S>) -> Word<S> {
+    let [p00, g00] = half_adder(a[ 0], b[ 0]);
+    let [p01, g01] = half_adder(a[ 1], b[ 1]);
+    let [p02, g02] = half_adder(a[ 2], b[ 2]);
+    let [p03, g03] = half_adder(a[ 3], b[ 3]);
+    let [p04, g04] = half_adder(a[ 4], b[ 4]);
+    let [p05, g05] = half_adder(a[ 5], b[ 5]);
+    let [p06, g06] = half_adder(a[ 6], b[ 6]);
+    let [p07, g07] = half_adder(a[ 7], b[ 7]);
+    let [p08, g08] = half_adder(a[ 8], b[ 8]);
+    let [p09, g09] = half_adder(a[ 9], b[ 9]);
+    let [p10, g10] = half_adder(a[10], b[10]);
+    let [p11, g11] = half_adder(a[11], b[11]);
+    let [p12, g12] = half_adder(a[12], b[12]);
+    let [p13, g13] = half_adder(a[13], b[13]);
+    let [p14, g14] = half_adder(a[14], b[14]);
+    let [p15, g15] = half_adder(a[15], b[15]);
+    let (g0, p0) = ([g00, g01, g02, g03], [p00, p01, p02, p03]);
+    let (g1, p1) = ([g04, g05, g06, g07], [p04, p05, p06, p07]);
+    let (g2, p2) = ([g08, g09, g10, g11], [p08, p09, p10, p11]);
+    let (g3, p3) = ([g12, g13, g14, g15], [p12, p13, p14, p15]);
+    let [gg0, gp0] = group4(g0, p0);
+    let [gg1, gp1] = group4(g1, p1);
+    let [gg2, gp2] = group4(g2, p2);
+    let [gg3, gp3] = group4(g3, p3);
+    let c = carries4([gg0, gg1, gg2, gg3], [gp0, gp1, gp2, gp3], S::constant(false));
+    let [c00, c01, c02, c03] = carries4(g0, p0, c[0]);
+    let [c04, c05, c06, c07] = carries4(g1, p1, c[1]);
+    let [c08, c09, c10, c11] = carries4(g2, p2, c[2]);
+    let [c12, c13, c14, c15] = carries4(g3, p3, c[3]);
+    [
+        xor(p00, c00), xor(p01, c01), xor(p02, c02), xor(p03, c03),
+        xor(p04, c04), xor(p05, c05), xor(p06, c06), xor(p07, c07),
+        xor(p08, c08), xor(p09, c09), xor(p10, c10), xor(p11, c11),
+        xor(p12, c12), xor(p13, c13), xor(p14, c14), xor(p15, c15),
+    ]
+}
+
+// 4-bit ripple-carry adder, which returns the sum and the carry out
+fn add4<S: Signal>(a: [S; 4], b: [S; 4], carry: S) -> ([S; 4], S) {
+    let [sum0, carry] = full_adder(a[0], b[0], carry);
+    let [sum1, carry] = full_adder(a[1], b[1], carry);
+    let [sum2, carry] = full_adder(a[2], b[2], carry);
+    let [sum3, carry] = full_adder(a[3], b[3], carry);
+    ([sum0, sum1, sum2, sum3], carry)
+}
+
+// a 4-bit block computed for both carries, and selected by the actual carry
+fn select4<S: Signal>(a: [S; 4], b: [S; 4], carry: S) -> ([S; 4], S) {
+    let (sum0, carry0) = add4(a, b, S::constant(false));
+    let (sum1, carry1) = add4(a, b, S::constant(true));
+    (
+        [mux(sum0[0], sum1[0], carry), mux(sum0[1], sum1[1], carry), mux(sum0[2], sum1[2], carry), mux(sum0[3], sum1[3], carry)],
+        mux(carry0, carry1, carry)
+    )
+}
+
+// carry-select adder of 4-bit ripple-carry blocks
+pub fn add16_select<S: Signal>(a: Word<S>, b: Word<S>) -> Word<S> {
+    let ([sum00, sum01, sum02, sum03], carry) = add4([a[ 0], a[ 1], a[ 2], a[ 3]], [b[ 0], b[ 1], b[ 2], b[ 3]], S::constant(false));
+    let ([sum04, sum05, sum06, sum07], carry) = select4([a[ 4], a[ 5], a[ 6], a[ 7]], [b[ 4], b[ 5], b[ 6], b[ 7]], carry);
+    let ([sum08, sum09, sum10, sum11], carry) = select4([a[ 8], a[ 9], a[10], a[11]], [b[ 8], b[ 9], b[10], b[11]], carry);
+    let ([sum12, sum13, sum14, sum15], _    ) = select4([a[12], a[13], a[14], a[15]], [b[12], b[13], b[14], b[15]], carry);
+    [
+        sum00, sum01, sum02, sum03,
+        sum04, sum05, sum06, sum07,
+        sum08, sum09, sum10, sum11,
+        sum12, sum13, sum14, sum15,
+    ]
+}
+
+// prefix operator of the generate and propagate pairs: (g, p) of the bits hi..lo
+fn combine<S: Signal>(hi: [S; 2], lo: [S; 2]) -> [S; 2] {
+    [or(hi[0], and(hi[1], lo[0])), and(hi[1], lo[1])]
+}
+
+// Kogge-Stone parallel prefix adder: the carries are computed in log2(16) = 4 levels
+pub fn add16_kogge_stone<S: Signal>(a: Word<S>, b: Word<S>) -> Word<S> {
+    let [p00, g00] = half_adder(a[ 0], b[ 0]);
+    let [p01, g01] = half_adder(a[ 1], b[ 1]);
+    let [p02, g02] = half_adder(a[ 2], b[ 2]);
+    let [p03, g03] = half_adder(a[ 3], b[ 3]);
+    let [p04, g04] = half_adder(a[ 4], b[ 4]);
+    let [p05, g05] = half_adder(a[ 5], b[ 5]);
+    let [p06, g06] = half_adder(a[ 6], b[ 6]);
+    let [p07, g07] = half_adder(a[ 7], b[ 7]);
+    let [p08, g08] = half_adder(a[ 8], b[ 8]);
+    let [p09, g09] = half_adder(a[ 9], b[ 9]);
+    let [p10, g10] = half_adder(a[10], b[10]);
+    let [p11, g11] = half_adder(a[11], b[11]);
+    let [p12, g12] = half_adder(a[12], b[12]);
+    let [p13, g13] = half_adder(a[13], b[13]);
+    let [p14, g14] = half_adder(a[14], b[14]);
+    let [p15, g15] = half_adder(a[15], b[15]);
+    let x = [
+        [g00, p00], [g01, p01], [g02, p02], [g03, p03],
+        [g04, p04], [g05, p05], [g06, p06], [g07, p07],
+        [g08, p08], [g09, p09], [g10, p10], [g11, p11],
+        [g12, p12], [g13, p13], [g14, p14], [g15, p15],
+    ];
+    let x = [
+        x[ 0],              combine(x[ 1], x[ 0]), combine(x[ 2], x[ 1]), combine(x[ 3], x[ 2]),
+        combine(x[ 4], x[ 3]), combine(x[ 5], x[ 4]), combine(x[ 6], x[ 5]), combine(x[ 7], x[ 6]),
+        combine(x[ 8], x[ 7]), combine(x[ 9], x[ 8]), combine(x[10], x[ 9]), combine(x[11], x[10]),
+        combine(x[12], x[11]), combine(x[13], x[12]), combine(x[14], x[13]), combine(x[15], x[14]),
+    ];
+    let x = [
+        x[ 0],              x[ 1],              combine(x[ 2], x[ 0]), combine(x[ 3], x[ 1]),
+        combine(x[ 4], x[ 2]), combine(x[ 5], x[ 3]), combine(x[ 6], x[ 4]), combine(x[ 7], x[ 5]),
+        combine(x[ 8], x[ 6]), combine(x[ 9], x[ 7]), combine(x[10], x[ 8]), combine(x[11], x[ 9]),
+        combine(x[12], x[10]), combine(x[13], x[11]), combine(x[14], x[12]), combine(x[15], x[13]),
+    ];
+    let x = [
+        x[ 0],              x[ 1],              x[ 2],              x[ 3],
+        combine(x[ 4], x[ 0]), combine(x[ 5], x[ 1]), combine(x[ 6], x[ 2]), combine(x[ 7], x[ 3]),
+        combine(x[ 8], x[ 4]), combine(x[ 9], x[ 5]), combine(x[10], x[ 6]), combine(x[11], x[ 7]),
+        combine(x[12], x[ 8]), combine(x[13], x[ 9]), combine(x[14], x[10]), combine(x[15], x[11]),
+    ];
+    // x[i][0] is the carry out of the bit i
+    let x = [
+        x[ 0],              x[ 1],              x[ 2],              x[ 3],
+        x[ 4],              x[ 5],              x[ 6],              x[ 7],
+        combine(x[ 8], x[ 0]), combine(x[ 9], x[ 1]), combine(x[10], x[ 2]), combine(x[11], x[ 3]),
+        combine(x[12], x[ 4]), combine(x[13], x[ 5]), combine(x[14], x[ 6]), combine(x[15], x[ 7]),
+    ];
+    [
+        p00,                xor(p01, x[ 0][0]), xor(p02, x[ 1][0]), xor(p03, x[ 2][0]),
+        xor(p04, x[ 3][0]), xor(p05, x[ 4][0]), xor(p06, x[ 5][0]), xor(p07, x[ 6][0]),
+        xor(p08, x[ 7][0]), xor(p09, x[ 8][0]), xor(p10, x[ 9][0]), xor(p11, x[10][0]),
+        xor(p12, x[11][0]), xor(p13, x[12][0]), xor(p14, x[13][0]), xor(p15, x[14][0]),
+    ]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -107,4 +293,53 @@ mod tests {
             assert_eq!(word2int(inc16(int2word(n))), n+1);
         }
     }
-}
\ No newline at end of file
+
+    fn assert_adders(a: i16, b: i16) {
+        let expected = add16(int2word(a), int2word(b));
+        assert_eq!(add16_lookahead(int2word(a), int2word(b)), expected, "{} + {}", a, b);
+        assert_eq!(add16_select(int2word(a), int2word(b)), expected, "{} + {}", a, b);
+        assert_eq!(add16_kogge_stone(int2word(a), int2word(b)), expected, "{} + {}", a, b);
+    }
+
+    #[test]
+    fn test_adders_blocks() {
+        // every pair of 4-bit blocks at each position, after every combination of the lower blocks
+        // which kill, propagate or generate a carry. each block of the adders depends on the lower
+        // blocks only through its carry in, so this covers every block with both carries in
+        // and the composition of the block carries, i.e. all the 2^32 pairs of words.
+        let states = [(0x0, 0x0), (0xf, 0x0), (0xf, 0x1)];  // kill, propagate and generate
+        for position in 0 .. 4 {
+            for lower in 0 .. 3usize.pow(position) {
+                let (mut a, mut b) = (0u16, 0u16);
+                for i in 0 .. position {
+                    let (x, y) = states[lower / 3usize.pow(i) % 3];
+                    a |= x << (4 * i);
+                    b |= y << (4 * i);
+                }
+                for x in 0 .. 16u16 {
+                for y in 0 .. 16u16 {
+                    assert_adders((a | x << (4 * position)) as i16, (b | y << (4 * position)) as i16);
+                } }
+            }
+        }
+    }
+
+    #[test]
+    fn test_adders_bytes() {
+        // samples: every pair of bytes, with and without the carries through the upper byte
+        for x in 0 .. 256 {
+        for y in 0 .. 256 {
+            assert_adders(x, y);
+            assert_adders(x | 0xff00u16 as i16, y);
+        } }
+    }
+
+    #[test]
+    fn test_adders_words() {
+        // samples: every word against the patterns which carry through many bits
+        let patterns = [1, -1, 0x5555];
+        for a in i16::MIN ..= i16::MAX {
+            for &b in &patterns { assert_adders(a, b); }
+        }
+    }
+}
@@ -1,28 +1,35 @@
 use crate::given::*;
 use crate::gate::*;
-use crate::adder;
+use crate::adder::{self, Adder};
 
-pub struct AluOutput {
-    pub out: Word,  // 16-bit output
-    pub zr: bool,   // True iff out = 0
-    pub ng: bool,   // True iff out < 0
+pub struct AluOutput<S: Signal = bool> {
+    pub out: Word<S>,   // 16-bit output
+    pub zr: S,          // True iff out = 0
+    pub ng: S,          // True iff out < 0
 }
 
-pub fn alu(
-    x: Word, y: Word,   // Two 16-bit data inputs
-    zx: bool,   // Zero the x input
-    nx: bool,   // Negate the x input
-    zy: bool,   // Zero the y input
-    ny: bool,   // Negate the y input
-    f: bool,    // Function code: true for Add, false for And
-    no: bool    // Negate the out output
-) -> AluOutput {
-    let x = mux16(x, [false; 16], zx);
+// ALU built with the given adder design
+#[allow(clippy::too_many_arguments)]
+pub fn alu_with<A: Adder, S: Signal>(
+    x: Word<S>, y: Word<S>,   // Two 16-bit data inputs
+    zx: S,  // Zero the x input
+    nx: S,  // Negate the x input
+    zy: S,  // Zero the y input
+    ny: S,  // Negate the y input
+    f: S,   // Function code: true for Add, false for And
+    no: S   // Negate the out output
+) -> AluOutput<S> {
+    let x = mux16(x, [S::constant(false); 16], zx);
     let x = mux16(x, not16(x), nx);
-    let y = mux16(y, [false; 16], zy);
+    let y = mux16(y, [S::constant(false); 16], zy);
     let y = mux16(y, not16(y), ny);
-    let out = mux16(and16(x, y), adder::add16(x, y), f);
+    let out = mux16(and16(x, y), A::add16(x, y), f);
     let out = mux16(out, not16(out), no);
+    flags(out)
+}
+
+// the output with its zr and ng flags
+pub fn flags<S: Signal>(out: Word<S>) -> AluOutput<S> {
     let zr = not(or(
         or8way([out[0], out[1], out[2], out[3], out[4], out[5], out[6], out[7]]),
         or8way([out[8], out[9], out[10], out[11], out[12], out[13], out[14], out[15]])));
@@ -30,6 +37,11 @@ pub fn alu(
     AluOutput{ out: out, zr: zr, ng: ng }
 }
 
+// the ALU of the computer, built with the ripple-carry adder
+pub fn alu<S: Signal>(x: Word<S>, y: Word<S>, zx: S, nx: S, zy: S, ny: S, f: S, no: S) -> AluOutput<S> {
+    alu_with::<adder::RippleCarry, S>(x, y, zx, nx, zy, ny, f, no)
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -71,4 +83,23 @@ mod tests {
         } }
 
     }
+
+    #[test]
+    fn test_alu_with_adders() {
+        fn assert_same<A: adder::Adder>() {
+            let data = [73, -61, 973, 0x7fff, -0x8000];
+            for &x in &data {
+            for &y in &data {
+                for &(zx, nx, zy, ny, no) in &[(0, 0, 0, 0, 0), (0, 1, 0, 0, 1), (0, 0, 1, 1, 0), (1, 1, 0, 1, 1)] {
+                    let (zx, nx, zy, ny, no) = (zx != 0, nx != 0, zy != 0, ny != 0, no != 0);
+                    let expected = alu(int2word(x), int2word(y), zx, nx, zy, ny, true, no);
+                    let actual = alu_with::<A, bool>(int2word(x), int2word(y), zx, nx, zy, ny, true, no);
+                    assert_eq!((actual.out, actual.zr, actual.ng), (expected.out, expected.zr, expected.ng));
+                }
+            } }
+        }
+        assert_same::<adder::CarryLookahead>();
+        assert_same::<adder::CarrySelect>();
+        assert_same::<adder::KoggeStone>();
+    }
 }
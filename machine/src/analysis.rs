@@ -0,0 +1,268 @@
+// instrumented evaluation of the chips: counts NAND evaluations and flip-flops,
+// and measures the critical path in NAND delays
+use std::cell::Cell;
+use crate::given::*;
+use crate::gate::*;
+use crate::adder::*;
+use crate::alu::*;
+use crate::ram::*;
+use crate::cpu::*;
+
+thread_local! {
+    static NANDS: Cell<u64> = const { Cell::new(0) };
+    static FLIPFLOPS: Cell<u64> = const { Cell::new(0) };
+    static LATCH_DEPTH: Cell<u32> = const { Cell::new(0) };   // deepest signal stored to the flip-flops
+}
+
+// a signal which knows how many NAND delays it takes to arrive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Probe {
+    pub value: bool,
+    pub depth: u32,
+}
+
+impl Signal for Probe {
+    fn constant(value: bool) -> Self {
+        Probe{ value, depth: 0 }
+    }
+    fn nand(a: Self, b: Self) -> Self {
+        NANDS.with(|n| n.set(n.get() + 1));
+        Probe{ value: !(a.value && b.value), depth: a.depth.max(b.depth) + 1 }
+    }
+    fn latch(self) -> Self {
+        LATCH_DEPTH.with(|d| d.set(d.get().max(self.depth)));
+        Probe{ value: self.value, depth: 0 }
+    }
+    fn flipflop_created() {
+        FLIPFLOPS.with(|n| n.set(n.get() + 1));
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Cost {
+    pub nands: u64,     // NAND evaluations for an evaluation (and a clock for sequential chips)
+    pub flipflops: u64,
+    pub depth: u32,     // critical path from the inputs or flip-flops to the outputs or flip-flops
+}
+
+// inputs which arrive at time 0
+pub fn input<const N: usize>() -> [Probe; N] {
+    [Probe::constant(false); N]
+}
+
+// evaluate a chip built with Probe, and count the costs
+pub fn measure(f: impl FnOnce() -> Vec<Probe>) -> Cost {
+    NANDS.with(|n| n.set(0));
+    FLIPFLOPS.with(|n| n.set(0));
+    LATCH_DEPTH.with(|d| d.set(0));
+    let outputs = f();
+    let depth = outputs.iter().map(|output| output.depth).max().unwrap_or(0);
+    Cost{
+        nands: NANDS.with(|n| n.get()),
+        flipflops: FLIPFLOPS.with(|n| n.get()),
+        depth: depth.max(LATCH_DEPTH.with(|d| d.get())),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub name: &'static str,
+    pub cost: Cost,
+    pub parts: Vec<Report>,
+}
+
+impl Report {
+    fn new(name: &'static str, cost: Cost, parts: Vec<Report>) -> Self {
+        Self{ name, cost, parts }
+    }
+    fn write(&self, f: &mut std::fmt::Formatter, indent: usize) -> std::fmt::Result {
+        let name = format!("{:indent$}{}", "", self.name, indent = indent * 2);
+        writeln!(f, "{:<24} {:>10} {:>10} {:>6}", name, self.cost.nands, self.cost.flipflops, self.cost.depth)?;
+        for part in &self.parts { part.write(f, indent + 1)?; }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "{:<24} {:>10} {:>10} {:>6}", "chip", "nands", "flipflops", "depth")?;
+        self.write(f, 0)
+    }
+}
+
+fn gate(name: &'static str, f: impl FnOnce() -> Vec<Probe>) -> Report {
+    Report::new(name, measure(f), Vec::new())
+}
+
+fn xor_report() -> Report {
+    Report::new("xor", measure(|| vec![xor(Probe::constant(false), Probe::constant(false))]), vec![
+        gate("not", || vec![not(Probe::constant(false))]),
+        gate("and", || vec![and(Probe::constant(false), Probe::constant(false))]),
+        gate("or", || vec![or(Probe::constant(false), Probe::constant(false))]),
+    ])
+}
+
+fn half_adder_report() -> Report {
+    Report::new("half_adder", measure(|| half_adder(Probe::constant(false), Probe::constant(false)).to_vec()), vec![xor_report()])
+}
+
+pub fn add16_report() -> Report {
+    Report::new("add16", measure(|| add16(input(), input()).to_vec()), vec![
+        half_adder_report(),
+        Report::new("full_adder", measure(|| full_adder(input::<1>()[0], input::<1>()[0], input::<1>()[0]).to_vec()), vec![half_adder_report()]),
+    ])
+}
+
+fn alu_report() -> Report {
+    let zero = || Probe::constant(false);
+    Report::new("alu", measure(|| {
+        let out = alu(input(), input(), zero(), zero(), zero(), zero(), zero(), zero());
+        let mut outputs = out.out.to_vec();
+        outputs.extend(&[out.zr, out.ng]);
+        outputs
+    }), vec![
+        gate("mux16", || mux16(input(), input(), zero()).to_vec()),
+        gate("not16", || not16(input()).to_vec()),
+        gate("and16", || and16(input(), input()).to_vec()),
+        add16_report(),
+        gate("or8way", || vec![or8way(input())]),
+    ])
+}
+
+fn register_report() -> Report {
+    Report::new("Register", measure(|| {
+        let mut register = Register::<Probe>::new();
+        let out = register.out().to_vec();
+        register.clock(input(), Probe::constant(false));
+        out
+    }), vec![gate("BitRegister", || {
+        let mut bit = BitRegister::<Probe>::new();
+        let out = vec![bit.out()];
+        bit.clock(Probe::constant(false), Probe::constant(false));
+        out
+    })])
+}
+
+macro_rules! ram_report {
+    ($name:expr, $ram:ident, $parts:expr) => {
+        Report::new($name, measure(|| {
+            let mut ram = $ram::<Probe>::new();
+            let out = ram.out(input()).to_vec();
+            ram.clock(input(), input(), Probe::constant(false));
+            out
+        }), $parts)
+    };
+}
+
+fn ram8_report() -> Report {
+    ram_report!("RAM8", RAM8, vec![
+        register_report(),
+        gate("mux8way16", || { let w = input; mux8way16(w(), w(), w(), w(), w(), w(), w(), w(), input()).to_vec() }),
+        gate("dmux8way", || dmux8way(Probe::constant(false), input()).to_vec()),
+    ])
+}
+
+fn ram16k_report() -> Report {
+    ram_report!("RAM16K", RAM16K, vec![
+        ram_report!("RAM4K", RAM4K, vec![
+            ram_report!("RAM512", RAM512, vec![
+                ram_report!("RAM64", RAM64, vec![ram8_report()])])])])
+}
+
+pub fn cpu_report() -> Report {
+    Report::new("Cpu", measure(|| {
+        let mut cpu = Cpu::<Probe>::new();
+        let input = CpuInput{ inM: input(), instruction: input(), reset: Probe::constant(false) };
+        let out = cpu.out(input);
+        cpu.clock(input);
+        let mut outputs = out.outM.to_vec();
+        outputs.push(out.writeM);
+        outputs.extend(&cpu.addressM());
+        outputs.extend(&cpu.pc());
+        outputs
+    }), vec![
+        alu_report(),
+        register_report(),
+        Report::new("Counter", measure(|| {
+            let mut counter = Counter::<Probe>::new();
+            let out = counter.out().to_vec();
+            counter.clock(input(), Probe::constant(false), Probe::constant(false), Probe::constant(false));
+            out
+        }), vec![gate("inc16", || inc16(input()).to_vec()), register_report()]),
+    ])
+}
+
+// costs of the main chips: the CPU and the data memory
+pub fn report() -> Vec<Report> {
+    vec![cpu_report(), ram16k_report()]
+}
+
+fn adder_report<A: Adder>(name: &'static str) -> Report {
+    gate(name, || A::add16(input(), input()).to_vec())
+}
+
+// costs of the 16-bit adder designs, to compare with the ripple-carry add16
+pub fn adder_reports() -> Vec<Report> {
+    vec![
+        adder_report::<RippleCarry>("ripple carry"),
+        adder_report::<CarryLookahead>("carry lookahead"),
+        adder_report::<CarrySelect>("carry select"),
+        adder_report::<KoggeStone>("Kogge-Stone"),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find<'a>(report: &'a Report, name: &str) -> Option<&'a Report> {
+        if report.name == name { return Some(report); }
+        report.parts.iter().find_map(|part| find(part, name))
+    }
+
+    #[test]
+    fn test_gates() {
+        let xor = xor_report();
+        assert_eq!(xor.cost, Cost{ nands: 9, flipflops: 0, depth: 5 });
+        assert_eq!(find(&xor, "not").unwrap().cost, Cost{ nands: 1, flipflops: 0, depth: 1 });
+        assert_eq!(find(&xor, "or").unwrap().cost, Cost{ nands: 3, flipflops: 0, depth: 2 });
+
+        // ripple carry: 4 NAND delays per bit after the first full adder
+        let add16 = add16_report();
+        assert_eq!(add16.cost.nands, 11 + 15 * 25);
+        assert_eq!(add16.cost.depth, 9 + 13 * 4 + 5);
+    }
+
+    #[test]
+    fn test_sequential() {
+        let cpu = cpu_report();
+        assert_eq!(cpu.cost.flipflops, 16 * 3);
+        assert!(cpu.cost.depth > find(&cpu, "alu").unwrap().cost.depth);
+        let ram = ram16k_report();
+        assert_eq!(ram.cost.flipflops, 16 * 1024 * 16);
+        assert_eq!(find(&ram, "RAM8").unwrap().cost.flipflops, 8 * 16);
+        assert_eq!(find(&ram, "BitRegister").unwrap().cost, Cost{ nands: 8, flipflops: 1, depth: 5 });
+    }
+
+    #[test]
+    fn test_adders() {
+        let adders = adder_reports();
+        let depth = |name: &str| adders.iter().find(|report| report.name == name).unwrap().cost.depth;
+        assert_eq!(depth("ripple carry"), add16_report().cost.depth);
+        assert!(depth("carry select") < depth("ripple carry"));
+        assert!(depth("carry lookahead") < depth("carry select"));
+        assert!(depth("Kogge-Stone") < depth("carry lookahead"));
+    }
+
+    #[test]
+    fn test_display() {
+        let text = xor_report().to_string();
+        assert_eq!(text.lines().collect::<Vec<_>>(), vec![
+            "chip                          nands  flipflops  depth",
+            "xor                               9          0      5",
+            "  not                             1          0      1",
+            "  and                             2          0      2",
+            "  or                              3          0      2",
+        ]);
+    }
+}
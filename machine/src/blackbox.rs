@@ -18,6 +18,10 @@ impl ROM32K {
     pub fn is_terminated(&self, address: Word) -> bool {
         word2int(address) as usize >= self.len
     }
+    // word-level access for the emulator (address is 15 bit)
+    pub fn read(&self, address: i16) -> i16 {
+        self.data[address as usize & 0x7fff]
+    }
 }
 
 pub struct Screen {
@@ -49,6 +53,13 @@ impl Screen {
     pub fn raw_image(&self) -> &[i16; 32 * 256] {
         &*self.data
     }
+    // word-level access for the emulator (address is 13 bit)
+    pub fn read(&self, address: i16) -> i16 {
+        self.data[address as usize & 0x1fff]
+    }
+    pub fn write(&mut self, address: i16, value: i16) {
+        self.data[address as usize & 0x1fff] = value;
+    }
 }
 
 pub struct Keyboard {
@@ -65,4 +76,7 @@ impl Keyboard {
     pub fn input(&mut self, key: i16) {
         self.key = key;
     }
+    pub fn key(&self) -> i16 {
+        self.key
+    }
 }
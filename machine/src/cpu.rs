@@ -4,40 +4,58 @@ use crate::gate::*;
 use crate::ram::*;
 use crate::alu::*;
 
-pub struct Cpu {
-    A: Register,    // A register ('A' stands for address)
-    D: Register,    // D register ('D' stands for data)
-    PC: Counter,    // Program counter
+pub struct Cpu<S: Signal = bool> {
+    A: Register<S>, // A register ('A' stands for address)
+    D: Register<S>, // D register ('D' stands for data)
+    PC: Counter<S>, // Program counter
 }
 
 #[derive(Clone, Copy)]
-pub struct CpuInput {
-    pub inM: Word,
-    pub instruction: Word,
-    pub reset: bool
+pub struct CpuInput<S: Signal = bool> {
+    pub inM: Word<S>,
+    pub instruction: Word<S>,
+    pub reset: S
 }
 
-pub struct CpuOutput {
-    pub outM: Word,
-    pub writeM: bool,
+pub struct CpuOutput<S: Signal = bool> {
+    pub outM: Word<S>,
+    pub writeM: S,
 }
 
-struct ControlBits {
-    in_a: Word,     // input to A-register
-    in_d: Word,     // input to D-register
-    in_pc: Word,    // input to program counter
-    load_a: bool,   // load bit for A-register
-    load_d: bool,   // load bit for D-register
-    jump: bool      // load bit for program counter
+// signals inside the CPU during a clock, for tracers
+pub struct CpuSignals<S: Signal = bool> {
+    pub alu_x: Word<S>,     // D
+    pub alu_y: Word<S>,     // A or M
+    pub alu_out: Word<S>,
+    pub zr: S,
+    pub ng: S,
+    pub load_a: S,
+    pub load_d: S,
+    pub jump: S,
+    pub writeM: S,
 }
 
-impl Cpu {
+struct ControlBits<S: Signal> {
+    in_a: Word<S>,  // input to A-register
+    in_d: Word<S>,  // input to D-register
+    in_pc: Word<S>, // input to program counter
+    load_a: S,      // load bit for A-register
+    load_d: S,      // load bit for D-register
+    jump: S         // load bit for program counter
+}
+
+impl<S: Signal> Cpu<S> {
     pub fn new() -> Self {
         Self { A: Register::new(), D: Register::new(), PC: Counter::new() }
     }
-    fn alu(&self, inM: Word, instruction: Word) -> AluOutput {
+    fn alu_inputs(&self, inM: Word<S>, instruction: Word<S>) -> [Word<S>; 2] {
         let x = self.D.out();   // x = D
         let y = mux16(self.A.out(), inM, instruction[12]);  // y = A or M
+        [x, y]
+    }
+    #[cfg(not(feature = "extended"))]
+    fn alu(&self, inM: Word<S>, instruction: Word<S>) -> AluOutput<S> {
+        let [x, y] = self.alu_inputs(inM, instruction);
         alu(x, y,
             instruction[11],    // zx
             instruction[10],    // nx
@@ -46,7 +64,20 @@ impl Cpu {
             instruction[ 7],    // f
             instruction[ 6])    // no
     }
-    fn decode(&self, inM: Word, instruction: Word) -> ControlBits {
+    // the C-instructions prefixed with 101 are the shifts, which bypass the ALU
+    #[cfg(feature = "extended")]
+    fn alu(&self, inM: Word<S>, instruction: Word<S>) -> AluOutput<S> {
+        use crate::shifter::*;
+        let [x, y] = self.alu_inputs(inM, instruction);
+        let alu_out = alu(x, y,
+            instruction[11], instruction[10], instruction[9], instruction[8], instruction[7], instruction[6]);
+        let is_shift = and(instruction[15], and(not(instruction[14]), instruction[13]));
+        let shifted = shifter(
+            mux16(y, x, instruction[10]),   // D or A/M
+            instruction[11]);               // left or right
+        flags(mux16(alu_out.out, shifted, is_shift))
+    }
+    fn decode(&self, inM: Word<S>, instruction: Word<S>) -> ControlBits<S> {
         let is_c_instruction = instruction[15];
         let alu_out = self.alu(inM, instruction);
         ControlBits {
@@ -63,20 +94,44 @@ impl Cpu {
                     and(instruction[2], alu_out.ng)))
         }
     }
-    pub fn addressM(&self) -> Word {
+    pub fn addressM(&self) -> Word<S> {
         self.A.out()
     }
-    pub fn pc(&self) -> Word {
+    pub fn pc(&self) -> Word<S> {
         self.PC.out()
     }
-    pub fn out(&self, input: CpuInput) -> CpuOutput {
+    pub fn d(&self) -> Word<S> {
+        self.D.out()
+    }
+    // overwrite the registers directly, like a debug port of the hardware
+    pub fn set_a(&mut self, a: Word<S>) {
+        self.A.clock(a, S::constant(true));
+    }
+    pub fn set_d(&mut self, d: Word<S>) {
+        self.D.clock(d, S::constant(true));
+    }
+    pub fn set_pc(&mut self, pc: Word<S>) {
+        self.PC.clock(pc, S::constant(false), S::constant(true), S::constant(false));
+    }
+    pub fn out(&self, input: CpuInput<S>) -> CpuOutput<S> {
         let is_c_instruction = input.instruction[15];
         CpuOutput{
             outM: self.alu(input.inM, input.instruction).out,
             writeM: and(is_c_instruction, input.instruction[3]),
         }
     }
-    pub fn clock(&mut self, input: CpuInput) {
+    pub fn signals(&self, input: CpuInput<S>) -> CpuSignals<S> {
+        let [alu_x, alu_y] = self.alu_inputs(input.inM, input.instruction);
+        let alu_out = self.alu(input.inM, input.instruction);
+        let c = self.decode(input.inM, input.instruction);
+        CpuSignals{
+            alu_x, alu_y,
+            alu_out: alu_out.out, zr: alu_out.zr, ng: alu_out.ng,
+            load_a: c.load_a, load_d: c.load_d, jump: c.jump,
+            writeM: self.out(input).writeM,
+        }
+    }
+    pub fn clock(&mut self, input: CpuInput<S>) {
         let c = self.decode(input.inM, input.instruction);
         self.A.clock(c.in_a, c.load_a);
         self.D.clock(c.in_d, c.load_d);
@@ -0,0 +1,296 @@
+// Step debugger which drives any Computer.
+// Breakpoints stop before the instruction at the address is executed,
+// and watchpoints stop right after an instruction writes to the address.
+use crate::{Computer, MachineState};
+use crate::inst::*;
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    Step,
+    Breakpoint(i16),
+    Watchpoint{ address: i16, old: i16, new: i16 },
+    Terminated,
+    CycleLimit,
+}
+
+impl std::fmt::Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            StopReason::Step => write!(f, "step"),
+            StopReason::Breakpoint(address) => write!(f, "breakpoint at {}", address),
+            StopReason::Watchpoint{ address, old, new } => write!(f, "watchpoint RAM[{}]: {} -> {}", address, old, new),
+            StopReason::Terminated => write!(f, "terminated"),
+            StopReason::CycleLimit => write!(f, "cycle limit reached"),
+        }
+    }
+}
+
+pub struct Debugger {
+    computer: Box<dyn Computer>,
+    rom: Vec<i16>,
+    labels: Vec<(String, i16)>,
+    locations: Vec<Option<String>>,
+    breakpoints: BTreeSet<i16>,
+    watchpoints: BTreeSet<i16>,
+}
+
+impl Debugger {
+    pub fn new(computer: Box<dyn Computer>, rom: &[i16]) -> Self {
+        Self{
+            computer,
+            rom: rom.to_vec(),
+            labels: Vec::new(),
+            locations: Vec::new(),
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new()
+        }
+    }
+
+    // labels of the program (e.g. from asm::labels) for breakpoints by name and disassembly
+    pub fn set_labels(&mut self, labels: Vec<(String, i16)>) {
+        self.labels = labels;
+    }
+    pub fn label_address(&self, label: &str) -> Option<i16> {
+        self.labels.iter().find(|(name, _)| name == label).map(|(_, address)| *address)
+    }
+    pub fn labels_at(&self, address: i16) -> impl Iterator<Item = &str> {
+        self.labels.iter().filter(move |(_, a)| *a == address).map(|(name, _)| name.as_str())
+    }
+
+    // source location of each ROM address (e.g. the asm line or the VM command)
+    pub fn set_locations(&mut self, locations: Vec<Option<String>>) {
+        self.locations = locations;
+    }
+    pub fn location(&self, address: i16) -> Option<&str> {
+        self.locations.get(address as u16 as usize).and_then(|location| location.as_deref())
+    }
+
+    pub fn computer(&self) -> &dyn Computer {
+        self.computer.as_ref()
+    }
+    pub fn computer_mut(&mut self) -> &mut dyn Computer {
+        self.computer.as_mut()
+    }
+    pub fn state(&self) -> MachineState {
+        self.computer.state()
+    }
+
+    pub fn add_breakpoint(&mut self, address: i16) {
+        self.breakpoints.insert(address);
+    }
+    pub fn remove_breakpoint(&mut self, address: i16) -> bool {
+        self.breakpoints.remove(&address)
+    }
+    pub fn breakpoints(&self) -> impl Iterator<Item = i16> + '_ {
+        self.breakpoints.iter().cloned()
+    }
+    pub fn is_breakpoint(&self, address: i16) -> bool {
+        self.breakpoints.contains(&address)
+    }
+    pub fn add_watchpoint(&mut self, address: i16) {
+        self.watchpoints.insert(address);
+    }
+    pub fn remove_watchpoint(&mut self, address: i16) -> bool {
+        self.watchpoints.remove(&address)
+    }
+    pub fn watchpoints(&self) -> impl Iterator<Item = i16> + '_ {
+        self.watchpoints.iter().cloned()
+    }
+
+    fn rom(&self, address: i16) -> Option<i16> {
+        self.rom.get(address as u16 as usize).cloned()
+    }
+
+    // execute one instruction
+    pub fn step(&mut self) -> StopReason {
+        if self.computer.is_terminated() { return StopReason::Terminated; }
+        // a C-instruction with 'M' in its destination writes to RAM[A]
+        let state = self.computer.state();
+        let written = match state.instruction {
+            Some(CInstruction(_, dest, _)) if dest & dest::M != 0 && self.watchpoints.contains(&state.a) => Some(state.a),
+            _ => None
+        };
+        let old = written.map(|address| self.computer.read_memory(address));
+        self.computer.clock(false);
+        match (written, old) {
+            (Some(address), Some(old)) => StopReason::Watchpoint{ address, old, new: self.computer.read_memory(address) },
+            _ => StopReason::Step
+        }
+    }
+
+    // run until a breakpoint, a watchpoint or termination. the instruction at the current PC is
+    // always executed, so that 'continue' at a breakpoint proceeds.
+    pub fn cont(&mut self, max_cycles: u64) -> StopReason {
+        self.run_until(max_cycles, |_| false)
+    }
+
+    // if PC is at a function call emitted by vm_translator, run until the function returns.
+    // otherwise same as step().
+    pub fn step_over(&mut self, max_cycles: u64) -> StopReason {
+        let return_address = match self.call_at(self.computer.pc()) {
+            Some(return_address) => return_address,
+            None => return self.step()
+        };
+        // the callee and its recursive calls return to the same address with a larger SP
+        let sp = self.computer.read_memory(0);
+        self.run_until(max_cycles, |computer| computer.pc() == return_address && computer.read_memory(0) <= sp)
+    }
+
+    fn run_until(&mut self, max_cycles: u64, stop: impl Fn(&dyn Computer) -> bool) -> StopReason {
+        for i in 0 .. max_cycles {
+            let pc = self.computer.pc();
+            if i > 0 && self.is_breakpoint(pc) { return StopReason::Breakpoint(pc); }
+            match self.step() {
+                StopReason::Step => (),
+                reason => return reason
+            }
+            if stop(self.computer.as_ref()) { return StopReason::Step; }
+            if self.computer.is_terminated() { return StopReason::Terminated; }
+        }
+        StopReason::CycleLimit
+    }
+
+    // a function call of vm_translator ends with
+    //      @LCL, M=D   (LCL = SP)
+    //      @function
+    //      0;JMP
+    //   (RETURN_n)
+    // returns the return address if PC is at '@function' or '0;JMP'
+    fn call_at(&self, pc: i16) -> Option<i16> {
+        let is_call = |at: i16| {
+            let inst = |offset: i16| self.rom(at.wrapping_add(offset)).and_then(Instruction::try_decode);
+            inst(-2) == Some(AInstruction(1)) &&
+            inst(-1) == Some(CInstruction(Computation::D, dest::M, Jump::Null)) &&
+            matches!(inst(0), Some(AInstruction(_))) &&
+            inst(1) == Some(CInstruction(Computation::Zero, 0, Jump::JMP))
+        };
+        if is_call(pc) { Some(pc + 2) }
+        else if pc > 0 && is_call(pc - 1) { Some(pc + 1) }
+        else { None }
+    }
+
+    // disassembly of ROM[from ..= to] with labels. the line at PC is marked with '=>' and breakpoints with '*'.
+    pub fn disassemble(&self, from: i16, to: i16) -> Vec<String> {
+        let pc = self.computer.pc();
+        let mut lines = Vec::new();
+        for address in from.max(0) ..= to.min(self.rom.len() as i16 - 1) {
+            for label in self.labels_at(address) {
+                lines.push(format!("        ({})", label));
+            }
+            let marker = if address == pc { "=>" } else { "  " };
+            let breakpoint = if self.breakpoints.contains(&address) { '*' } else { ' ' };
+            let word = self.rom[address as usize];
+            lines.push(format!("{}{}{:5}  {}", marker, breakpoint, address, disassemble(word)));
+        }
+        lines
+    }
+
+    // disassembly of some instructions before and after PC
+    pub fn disassemble_around_pc(&self, radius: i16) -> Vec<String> {
+        let pc = self.computer.pc();
+        self.disassemble(pc.saturating_sub(radius), pc.saturating_add(radius))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Emulator;
+
+    fn encode(program: &[Instruction]) -> Vec<i16> {
+        program.iter().map(|inst| inst.encode()).collect()
+    }
+
+    fn debugger(program: &[Instruction]) -> Debugger {
+        let bin = encode(program);
+        Debugger::new(Box::new(Emulator::new(&bin)), &bin)
+    }
+
+    #[test]
+    fn test_breakpoint_and_watchpoint() {
+        use Computation::*;
+        let mut debugger = debugger(&[
+            /* @16      */  AInstruction(16),
+            /* M=M+1    */  CInstruction(XPlusOne(true), dest::M, Jump::Null),
+            /* @17      */  AInstruction(17),
+            /* M=M-1    */  CInstruction(XMinusOne(true), dest::M, Jump::Null),
+            /* @0       */  AInstruction(0),
+            /* 0;JMP    */  CInstruction(Zero, 0, Jump::JMP),
+        ]);
+        debugger.add_breakpoint(2);
+        assert_eq!(debugger.cont(100), StopReason::Breakpoint(2));
+        assert_eq!(debugger.state().pc, 2);
+        assert_eq!(debugger.cont(100), StopReason::Breakpoint(2));
+        assert_eq!(debugger.computer().read_memory(16), 2);
+
+        assert!(debugger.is_breakpoint(2));
+        debugger.remove_breakpoint(2);
+        assert!(!debugger.is_breakpoint(2));
+        debugger.add_watchpoint(17);
+        assert_eq!(debugger.cont(100), StopReason::Watchpoint{ address: 17, old: -1, new: -2 });
+        assert_eq!(debugger.state().pc, 4);
+        debugger.remove_watchpoint(17);
+        assert_eq!(debugger.cont(10), StopReason::CycleLimit);
+        assert_eq!(debugger.step(), StopReason::Step);
+    }
+
+    #[test]
+    fn test_step_over() {
+        use Computation::*;
+        let mut debugger = debugger(&[
+            /* @300     */  AInstruction(300),
+            /* D=A      */  CInstruction(X(false), dest::D, Jump::Null),
+            /* @SP      */  AInstruction(0),
+            /* M=D      */  CInstruction(D, dest::M, Jump::Null),
+            /* @LCL     */  AInstruction(1),
+            /* M=D      */  CInstruction(D, dest::M, Jump::Null),
+            /* @FUNC    */  AInstruction(10),
+            /* 0;JMP    */  CInstruction(Zero, 0, Jump::JMP),
+            /* (RETURN) */  // address is 8
+            /* @END     */  AInstruction(8),
+            /* 0;JMP    */  CInstruction(Zero, 0, Jump::JMP),
+            /* (FUNC)   */  // address is 10
+            /* @SP      */  AInstruction(0),
+            /* M=M-1    */  CInstruction(XMinusOne(true), dest::M, Jump::Null),
+            /* @8       */  AInstruction(8),
+            /* 0;JMP    */  CInstruction(Zero, 0, Jump::JMP),
+        ]);
+        debugger.set_labels(vec![("RETURN".to_string(), 8), ("FUNC".to_string(), 10)]);
+        for _ in 0 .. 6 {
+            assert_eq!(debugger.step_over(100), StopReason::Step);
+        }
+        assert_eq!(debugger.state().pc, 6);
+        assert_eq!(debugger.step_over(100), StopReason::Step);
+        assert_eq!(debugger.state().pc, 8);
+        assert_eq!(debugger.computer().read_memory(0), 299);
+
+        // a breakpoint in the callee stops step-over
+        debugger.computer_mut().set_register(crate::CpuRegister::PC, 6);
+        debugger.add_breakpoint(debugger.label_address("FUNC").unwrap());
+        assert_eq!(debugger.step_over(100), StopReason::Breakpoint(10));
+    }
+
+    #[test]
+    fn test_disassemble() {
+        use Computation::*;
+        let mut debugger = debugger(&[
+            /* @16      */  AInstruction(16),
+            /* M=M+1    */  CInstruction(XPlusOne(true), dest::M, Jump::Null),
+            /* 0;JMP    */  CInstruction(Zero, 0, Jump::JMP),
+        ]);
+        debugger.set_labels(vec![("LOOP".to_string(), 0)]);
+        debugger.set_locations(vec![Some("Prog.asm:2".to_string()), None]);
+        assert_eq!(debugger.location(0), Some("Prog.asm:2"));
+        assert_eq!(debugger.location(1), None);
+        assert_eq!(debugger.location(2), None);
+        debugger.add_breakpoint(2);
+        debugger.step();
+        assert_eq!(debugger.disassemble_around_pc(5), vec![
+            "        (LOOP)",
+            "       0  @16",
+            "=>     1  M=M+1",
+            "  *    2  0;JMP",
+        ]);
+    }
+}
@@ -0,0 +1,242 @@
+// Lock-step differential testing.
+// A target (typically the gate-level Machine) and a small reference interpreter run the same program
+// side by side, and their registers and memory writes are compared after every clock.
+// The reference interpreter is written directly from the instruction set specification
+// and shares no code with Cpu, Memory nor Emulator.
+use crate::inst::Instruction;
+
+// the state observed after a clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Snapshot {
+    pub pc: i16,
+    pub a: i16,
+    pub d: i16,
+    pub write: Option<(i16, i16)>,  // (address, value) written to the data memory during the clock
+}
+
+pub trait Target {
+    fn step(&mut self) -> Snapshot;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub cycle: usize,               // 0-origin
+    pub pc: i16,                    // address of the instruction executed in the divergent cycle
+    pub instruction: i16,
+    pub expected: Option<Snapshot>, // None if the reference cannot execute the instruction
+    pub actual: Snapshot,
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "diverged at cycle {}: ROM[{}] = {:016b}", self.cycle, self.pc, self.instruction as u16)?;
+        if let Some(instruction) = Instruction::try_decode(self.instruction) {
+            write!(f, " ({})", instruction)?;
+        }
+        writeln!(f)?;
+        match &self.expected {
+            Some(expected) => writeln!(f, "  expected: {}", expected)?,
+            None => writeln!(f, "  expected: (invalid instruction)")?,
+        }
+        write!(f, "  actual:   {}", self.actual)
+    }
+}
+
+impl std::fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "PC={} A={} D={}", self.pc, self.a, self.d)?;
+        match self.write {
+            Some((address, value)) => write!(f, " RAM[{}]<-{}", address, value),
+            None => write!(f, " (no write)")
+        }
+    }
+}
+
+// word-level reference interpreter
+pub struct Reference {
+    rom: Vec<i16>,
+    memory: Vec<i16>,   // 32K words; the keyboard is never pressed
+    a: i16,
+    d: i16,
+    pc: i16,
+}
+
+impl Reference {
+    pub fn new(instructions: &[i16]) -> Self {
+        Self{ rom: instructions.to_vec(), memory: vec![0; 0x8000], a: 0, d: 0, pc: 0 }
+    }
+
+    pub fn instruction(&self) -> i16 {
+        // the ROM is filled with zeros (= @0) beyond the program
+        self.rom.get(self.pc as u16 as usize).cloned().unwrap_or(0)
+    }
+
+    // comp field (a-bit and c1..c6) as the closure of (D, A or M), or None for an undefined code
+    fn computation(instruction: i16) -> Option<fn(i16, i16) -> i16> {
+        #[cfg(feature = "extended")]
+        if instruction & 0x6000 == 0x2000 {
+            // shifts of the extended instruction set
+            let f: fn(i16, i16) -> i16 = match ((instruction >> 6) & 0b111111, instruction & (1 << 12) != 0) {
+                (0b110000, false) => |d, _| d << 1,
+                (0b100000, _) => |_, x| x << 1,
+                (0b010000, false) => |d, _| d >> 1,
+                (0b000000, _) => |_, x| x >> 1,
+                _ => return None
+            };
+            return Some(f);
+        }
+        let f: fn(i16, i16) -> i16 = match (instruction >> 6) & 0b111111 {
+            0b101010 => |_, _| 0,
+            0b111111 => |_, _| 1,
+            0b111010 => |_, _| -1,
+            0b001100 => |d, _| d,
+            0b110000 => |_, x| x,
+            0b001101 => |d, _| !d,
+            0b110001 => |_, x| !x,
+            0b001111 => |d, _| d.wrapping_neg(),
+            0b110011 => |_, x| x.wrapping_neg(),
+            0b011111 => |d, _| d.wrapping_add(1),
+            0b110111 => |_, x| x.wrapping_add(1),
+            0b001110 => |d, _| d.wrapping_sub(1),
+            0b110010 => |_, x| x.wrapping_sub(1),
+            0b000010 => |d, x| d.wrapping_add(x),
+            0b010011 => |d, x| d.wrapping_sub(x),
+            0b000111 => |d, x| x.wrapping_sub(d),
+            0b000000 => |d, x| d & x,
+            0b010101 => |d, x| d | x,
+            _ => return None
+        };
+        Some(f)
+    }
+
+    // execute an instruction. None if the instruction is undefined.
+    pub fn step(&mut self) -> Option<Snapshot> {
+        let instruction = self.instruction();
+        let mut write = None;
+        if instruction >= 0 {
+            self.a = instruction;
+            self.pc = self.pc.wrapping_add(1);
+        } else {
+            let comp = Self::computation(instruction)?;
+            let address = self.a;
+            let x = if instruction & (1 << 12) != 0 { self.memory[address as usize & 0x7fff] } else { self.a };
+            let out = comp(self.d, x);
+            if instruction & 0b001000 != 0 {
+                write = Some((address, out));
+                if address & 0x7fff < 0x6000 {
+                    self.memory[address as usize & 0x7fff] = out;
+                }
+            }
+            if instruction & 0b100000 != 0 { self.a = out; }
+            if instruction & 0b010000 != 0 { self.d = out; }
+            let jump = match instruction & 0b111 {
+                0b000 => false,
+                0b001 => out >  0,
+                0b010 => out == 0,
+                0b011 => out >= 0,
+                0b100 => out <  0,
+                0b101 => out != 0,
+                0b110 => out <= 0,
+                _     => true
+            };
+            self.pc = if jump { address } else { self.pc.wrapping_add(1) };
+        }
+        Some(Snapshot{ pc: self.pc, a: self.a, d: self.d, write })
+    }
+}
+
+// run the target and the reference in lock-step for max_cycles,
+// and return the number of cycles executed or the first divergence.
+pub fn diff(target: &mut dyn Target, instructions: &[i16], max_cycles: usize) -> Result<usize, Divergence> {
+    let mut reference = Reference::new(instructions);
+    for cycle in 0 .. max_cycles {
+        let pc = reference.pc;
+        let instruction = reference.instruction();
+        let expected = reference.step();
+        let actual = target.step();
+        if expected != Some(actual) {
+            return Err(Divergence{ cycle, pc, instruction, expected, actual });
+        }
+    }
+    Ok(max_cycles)
+}
+
+// check the gate-level Machine against the reference
+pub fn diff_machine(instructions: &[i16], max_cycles: usize) -> Result<usize, Divergence> {
+    diff(&mut crate::Machine::new(instructions), instructions, max_cycles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inst::*;
+
+    fn encode(program: &[Instruction]) -> Vec<i16> {
+        program.iter().map(|inst| inst.encode()).collect()
+    }
+
+    #[test]
+    fn test_machine() {
+        use Computation::*;
+        let bin = encode(&[
+            /* @100     */  AInstruction(100),
+            /* D=A      */  CInstruction(X(false), dest::D, Jump::Null),
+            /* @0       */  AInstruction(0),
+            /* M=D      */  CInstruction(D, dest::M, Jump::Null),
+            /* (LOOP)   */  // address is 4
+            /* @0       */  AInstruction(0),
+            /* MD=M-1   */  CInstruction(XMinusOne(true), dest::M | dest::D, Jump::Null),
+            /* @SCREEN  */  AInstruction(0x4000),
+            /* AM=D|A   */  CInstruction(DOrX(false), dest::A | dest::M, Jump::Null),
+            /* M=!M     */  CInstruction(NotX(true), dest::M, Jump::Null),
+            /* @KBD     */  AInstruction(0x6000),
+            /* D=D-M    */  CInstruction(DMinusX(true), dest::D, Jump::Null),
+            /* @LOOP    */  AInstruction(4),
+            /* D;JNE    */  CInstruction(D, 0, Jump::JNE),
+        ]);
+        assert_eq!(diff_machine(&bin, 300), Ok(300));
+    }
+
+    // a machine whose D register is stuck at zero after some cycles
+    struct Faulty {
+        machine: crate::Machine,
+        cycle: usize,
+    }
+
+    impl Target for Faulty {
+        fn step(&mut self) -> Snapshot {
+            self.cycle += 1;
+            let mut snapshot = self.machine.step();
+            if self.cycle > 3 { snapshot.d = 0; }
+            snapshot
+        }
+    }
+
+    #[test]
+    fn test_divergence() {
+        use Computation::*;
+        let bin = encode(&[
+            /* @7       */  AInstruction(7),
+            /* D=A      */  CInstruction(X(false), dest::D, Jump::Null),
+            /* @1       */  AInstruction(1),
+            /* D=D+A    */  CInstruction(DPlusX(false), dest::D, Jump::Null),
+            /* @2       */  AInstruction(2),
+        ]);
+        let mut target = Faulty{ machine: crate::Machine::new(&bin), cycle: 0 };
+        let divergence = diff(&mut target, &bin, 10).unwrap_err();
+        assert_eq!(divergence.cycle, 3);
+        assert_eq!(divergence.pc, 3);
+        assert_eq!(divergence.expected.unwrap().d, 8);
+        assert_eq!(divergence.actual.d, 0);
+        assert!(divergence.to_string().contains("(D=D+A)"));
+    }
+
+    #[test]
+    fn test_invalid_instruction() {
+        // comp bits 0b100000 are not defined in the specification
+        let bin = [0b1110_1000_0001_0000u16 as i16];
+        let divergence = diff_machine(&bin, 10).unwrap_err();
+        assert_eq!(divergence.cycle, 0);
+        assert_eq!(divergence.expected, None);
+    }
+}
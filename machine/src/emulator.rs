@@ -0,0 +1,242 @@
+// Word-level emulator of the Hack computer.
+// Unlike Machine, this executes instructions directly on i16 registers and a flat memory array,
+// so it is much faster but tells nothing about how the hardware works.
+use crate::given::debug::int2word;
+use crate::blackbox::*;
+use crate::{Computer, CpuRegister, MachineState};
+use crate::inst::Instruction;
+
+pub struct Emulator {
+    rom: Box<ROM32K>,
+    ram: Box<[i16; 16 * 1024]>,
+    screen: Screen,
+    keyboard: Keyboard,
+    a: i16,
+    d: i16,
+    pc: i16,
+    cycle: u64,
+}
+
+impl Emulator {
+    pub fn new(instructions: &[i16]) -> Self {
+        Self{
+            rom: Box::new(ROM32K::new(instructions)),
+            ram: Box::new([0; 16 * 1024]),
+            screen: Screen::new(),
+            keyboard: Keyboard::new(),
+            a: 0,
+            d: 0,
+            pc: 0,
+            cycle: 0
+        }
+    }
+
+    // the memory map is decoded in the same way as Memory does: bit 14 selects RAM or I/O,
+    // and bit 13 selects the screen or the keyboard. bit 15 is ignored.
+    fn read(&self, address: i16) -> i16 {
+        if address & 0x4000 == 0 { self.ram[address as usize & 0x3fff] }
+        else if address & 0x2000 == 0 { self.screen.read(address) }
+        else { self.keyboard.key() }
+    }
+    fn write(&mut self, address: i16, value: i16) {
+        if address & 0x4000 == 0 { self.ram[address as usize & 0x3fff] = value; }
+        else if address & 0x2000 == 0 { self.screen.write(address, value); }
+    }
+
+    // same function as alu::alu, computed on i16
+    fn alu(x: i16, y: i16, instruction: i16) -> i16 {
+        let bit = |i: i16| instruction & (1 << i) != 0;
+        let x = if bit(11) { 0 } else { x };    // zx
+        let x = if bit(10) { !x } else { x };   // nx
+        let y = if bit( 9) { 0 } else { y };    // zy
+        let y = if bit( 8) { !y } else { y };   // ny
+        let out = if bit(7) { x.wrapping_add(y) } else { x & y };   // f
+        if bit(6) { !out } else { out }         // no
+    }
+
+    // the ALU, or the shifter for the C-instructions prefixed with 101
+    #[cfg(feature = "extended")]
+    fn compute(x: i16, y: i16, instruction: i16) -> i16 {
+        if instruction & 0x6000 != 0x2000 { return Self::alu(x, y, instruction); }
+        let a = if instruction & (1 << 10) != 0 { x } else { y };
+        if instruction & (1 << 11) != 0 { a << 1 } else { a >> 1 }
+    }
+    #[cfg(not(feature = "extended"))]
+    fn compute(x: i16, y: i16, instruction: i16) -> i16 {
+        Self::alu(x, y, instruction)
+    }
+}
+
+impl Computer for Emulator {
+    fn clock(&mut self, reset: bool) {
+        let instruction = self.rom.read(self.pc);
+        if instruction >= 0 {
+            // A-instruction
+            self.a = instruction;
+            self.pc = self.pc.wrapping_add(1);
+        } else {
+            // C-instruction
+            let y = if instruction & (1 << 12) != 0 { self.read(self.a) } else { self.a };
+            let out = Self::compute(self.d, y, instruction);
+            let jump =
+                (instruction & 0b001 != 0 && out > 0) ||
+                (instruction & 0b010 != 0 && out == 0) ||
+                (instruction & 0b100 != 0 && out < 0);
+            let address = self.a;
+            if instruction & 0b001000 != 0 { self.write(address, out); }
+            if instruction & 0b100000 != 0 { self.a = out; }
+            if instruction & 0b010000 != 0 { self.d = out; }
+            self.pc = if jump { address } else { self.pc.wrapping_add(1) };
+        }
+        if reset {
+            self.pc = 0;
+        }
+        self.cycle += 1;
+    }
+    fn pc(&self) -> i16 {
+        self.pc
+    }
+    fn next_instruction(&self) -> i16 {
+        self.rom.read(self.pc)
+    }
+    fn is_terminated(&self) -> bool {
+        self.rom.is_terminated(int2word(self.pc))
+    }
+    fn read_memory(&self, address: i16) -> i16 {
+        self.read(address)
+    }
+    fn write_memory(&mut self, address: i16, value: i16) {
+        self.write(address, value);
+    }
+    fn set_register(&mut self, register: CpuRegister, value: i16) {
+        match register {
+            CpuRegister::A => self.a = value,
+            CpuRegister::D => self.d = value,
+            CpuRegister::PC => self.pc = value,
+        }
+    }
+    fn state(&self) -> MachineState {
+        MachineState{
+            a: self.a,
+            d: self.d,
+            pc: self.pc,
+            instruction: Instruction::try_decode(self.next_instruction()),
+            cycle: self.cycle
+        }
+    }
+    fn screen_image(&self) -> &[i16; 32 * 256] {
+        self.screen.raw_image()
+    }
+    fn keyboard_input(&mut self, key: i16) {
+        self.keyboard.input(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inst::*;
+    use crate::Machine;
+
+    fn sum_1_to_10() -> Vec<i16> {
+        use Computation::*;
+        let i   = 0b10000;  // address of symbol 'i'
+        let sum = 0b10001;  // address of symbol 'sum'
+        [
+            /* @i       */  AInstruction(i),
+            /* M=1      */  CInstruction(One, dest::M, Jump::Null),
+            /* @sum     */  AInstruction(sum),
+            /* M=0      */  CInstruction(Zero, dest::M, Jump::Null),
+            /* (LOOP)   */  // address is 4
+            /* @i       */  AInstruction(i),
+            /* D=M      */  CInstruction(X(true), dest::D, Jump::Null),
+            /* @10      */  AInstruction(10),
+            /* D=D-A    */  CInstruction(DMinusX(false), dest::D, Jump::Null),
+            /* @END     */  AInstruction(18),
+            /* D;JGT    */  CInstruction(D, 0, Jump::JGT),
+            /* @i       */  AInstruction(i),
+            /* D=M      */  CInstruction(X(true), dest::D, Jump::Null),
+            /* @sum     */  AInstruction(sum),
+            /* M=D+M    */  CInstruction(DPlusX(true), dest::M, Jump::Null),
+            /* @i       */  AInstruction(i),
+            /* M=M+1    */  CInstruction(XPlusOne(true), dest::M, Jump::Null),
+            /* @LOOP    */  AInstruction(4),
+            /* 0;JMP    */  CInstruction(Zero, 0, Jump::JMP),
+            /* (END)    */  // address = 18
+            /* @END     */  AInstruction(18),
+            /* 0;JMP    */  CInstruction(Zero, 0, Jump::JMP),
+        ].iter().map(|inst| inst.encode()).collect()
+    }
+
+    #[test]
+    fn test_sum_1_to_10() {
+        let mut emulator = Emulator::new(&sum_1_to_10());
+        for _ in 0 .. 200 {
+            emulator.clock(false);
+        }
+        assert_eq!(emulator.read_memory(0b10001), 10 * (10 + 1) / 2);
+        assert_eq!(emulator.pc(), 18);
+    }
+
+    #[test]
+    fn test_screen_and_keyboard() {
+        use Computation::*;
+        let bin = [
+            /* @SCREEN  */  AInstruction(0x4000),
+            /* M=-1     */  CInstruction(MinusOne, dest::M, Jump::Null),
+            /* @KBD     */  AInstruction(0x6000),
+            /* D=M      */  CInstruction(X(true), dest::D, Jump::Null),
+            /* @0       */  AInstruction(0),
+            /* M=D      */  CInstruction(D, dest::M, Jump::Null),
+        ].iter().map(|inst| inst.encode()).collect::<Vec<_>>();
+        let mut emulator = Emulator::new(&bin);
+        emulator.keyboard_input(65);
+        while !emulator.is_terminated() {
+            emulator.clock(false);
+        }
+        assert_eq!(emulator.screen_image()[0], -1);
+        assert_eq!(emulator.read_memory(0), 65);
+    }
+
+    #[test]
+    fn test_same_as_machine() {
+        // both implementations must be interchangeable through Computer
+        let bin = sum_1_to_10();
+        let mut computers: [Box<dyn Computer>; 2] = [Box::new(Machine::new(&bin)), Box::new(Emulator::new(&bin))];
+        for _ in 0 .. 60 {
+            for computer in computers.iter_mut() {
+                computer.clock(false);
+            }
+            assert_eq!(computers[0].state(), computers[1].state());
+            assert_eq!(computers[0].read_memory(0b10000), computers[1].read_memory(0b10000));
+            assert_eq!(computers[0].read_memory(0b10001), computers[1].read_memory(0b10001));
+        }
+    }
+
+    #[cfg(feature = "extended")]
+    #[test]
+    fn test_shifts() {
+        use Computation::*;
+        let bin = [
+            /* @0       */  AInstruction(0),
+            /* D=M      */  CInstruction(X(true), dest::D, Jump::Null),
+            /* D=D<<    */  CInstruction(ShiftLeftD, dest::D, Jump::Null),
+            /* M=M>>    */  CInstruction(ShiftRightX(true), dest::M, Jump::Null),
+            /* A=D>>    */  CInstruction(ShiftRightD, dest::A, Jump::Null),
+            /* D=A<<    */  CInstruction(ShiftLeftX(false), dest::D, Jump::Null),
+            /* @1       */  AInstruction(1),
+            /* M=D      */  CInstruction(D, dest::M, Jump::Null),
+        ].iter().map(|inst| inst.encode()).collect::<Vec<_>>();
+        for &x in &[0, 1, -1, 0x4321, -12345] {
+            let mut computers: [Box<dyn Computer>; 2] = [Box::new(Machine::new(&bin)), Box::new(Emulator::new(&bin))];
+            for computer in computers.iter_mut() {
+                computer.write_memory(0, x);
+                while !computer.is_terminated() { computer.clock(false); }
+            }
+            for computer in computers.iter() {
+                assert_eq!(computer.read_memory(0), x >> 1);
+                assert_eq!(computer.read_memory(1), x << 1);
+            }
+        }
+    }
+}
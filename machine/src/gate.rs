@@ -1,32 +1,32 @@
 use crate::given::*;
 
-pub fn not(a: bool) -> bool {
+pub fn not<S: Signal>(a: S) -> S {
     nand(a, a)
 }
 
-pub fn and(a: bool, b: bool) -> bool {
+pub fn and<S: Signal>(a: S, b: S) -> S {
     not(nand(a, b))
 }
 
-pub fn or(a: bool, b: bool) -> bool {
+pub fn or<S: Signal>(a: S, b: S) -> S {
     nand(not(a), not(b))
 }
 
-pub fn xor(a: bool, b: bool) -> bool {
+pub fn xor<S: Signal>(a: S, b: S) -> S {
     or(and(a, not(b)), and(not(a), b))
 }
 
 // multiplexor
-pub fn mux(a: bool, b: bool, sel: bool) -> bool {
+pub fn mux<S: Signal>(a: S, b: S, sel: S) -> S {
     or(and(a, not(sel)), and(b, sel))
 }
 
 // demultiplexor
-pub fn dmux(input: bool, sel: bool) -> [bool; 2] {
+pub fn dmux<S: Signal>(input: S, sel: S) -> [S; 2] {
     [and(input, not(sel)), and(input, sel)]
 }
 
-pub fn not16(a: Word) -> Word {
+pub fn not16<S: Signal>(a: Word<S>) -> Word<S> {
     [
         not(a[ 0]), not(a[ 1]), not(a[ 2]), not(a[ 3]),
         not(a[ 4]), not(a[ 5]), not(a[ 6]), not(a[ 7]),
@@ -35,7 +35,7 @@ pub fn not16(a: Word) -> Word {
     ]
 }
 
-pub fn and16(a: Word, b: Word) -> Word {
+pub fn and16<S: Signal>(a: Word<S>, b: Word<S>) -> Word<S> {
     [
         and(a[ 0], b[ 0]), and(a[ 1], b[ 1]), and(a[ 2], b[ 2]), and(a[ 3], b[ 3]),
         and(a[ 4], b[ 4]), and(a[ 5], b[ 5]), and(a[ 6], b[ 6]), and(a[ 7], b[ 7]),
@@ -44,7 +44,7 @@ pub fn and16(a: Word, b: Word) -> Word {
     ]
 }
 
-pub fn mux16(a: Word, b: Word, sel: bool) -> Word {
+pub fn mux16<S: Signal>(a: Word<S>, b: Word<S>, sel: S) -> Word<S> {
     [
         mux(a[ 0], b[ 0], sel), mux(a[ 1], b[ 1], sel), mux(a[ 2], b[ 2], sel), mux(a[ 3], b[ 3], sel),
         mux(a[ 4], b[ 4], sel), mux(a[ 5], b[ 5], sel), mux(a[ 6], b[ 6], sel), mux(a[ 7], b[ 7], sel),
@@ -53,26 +53,26 @@ pub fn mux16(a: Word, b: Word, sel: bool) -> Word {
     ]
 }
 
-pub fn or8way(a: [bool; 8]) -> bool {
+pub fn or8way<S: Signal>(a: [S; 8]) -> S {
     or(or(or(a[0], a[1]), or(a[2], a[3])), or(or(a[4], a[5]), or(a[6], a[7])))
 }
 
-pub fn mux4way16(a: Word, b: Word, c: Word, d: Word, sel: [bool; 2]) -> Word {
+pub fn mux4way16<S: Signal>(a: Word<S>, b: Word<S>, c: Word<S>, d: Word<S>, sel: [S; 2]) -> Word<S> {
     mux16(mux16(a, b, sel[0]), mux16(c, d, sel[0]), sel[1])
 }
 
-pub fn mux8way16(a: Word, b: Word, c: Word, d: Word, e: Word, f: Word, g: Word, h: Word, sel: [bool; 3]) -> Word {
+pub fn mux8way16<S: Signal>(a: Word<S>, b: Word<S>, c: Word<S>, d: Word<S>, e: Word<S>, f: Word<S>, g: Word<S>, h: Word<S>, sel: [S; 3]) -> Word<S> {
     mux16(mux4way16(a, b, c, d, [sel[0], sel[1]]), mux4way16(e, f, g, h, [sel[0], sel[1]]), sel[2])
 }
 
-pub fn dmux4way(input: bool, sel: [bool; 2]) -> [bool; 4] {
+pub fn dmux4way<S: Signal>(input: S, sel: [S; 2]) -> [S; 4] {
     let [ab, cd] = dmux(input, sel[1]);
     let [a, b] = dmux(ab, sel[0]);
     let [c, d] = dmux(cd, sel[0]);
     [a, b, c, d]
 }
 
-pub fn dmux8way(input: bool, sel: [bool; 3]) -> [bool; 8] {
+pub fn dmux8way<S: Signal>(input: S, sel: [S; 3]) -> [S; 8] {
     let [abcd, efgh] = dmux(input, sel[2]);
     let [a, b, c, d] = dmux4way(abcd, [sel[0], sel[1]]);
     let [e, f, g, h] = dmux4way(efgh, [sel[0], sel[1]]);
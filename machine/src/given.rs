@@ -1,25 +1,43 @@
+// a wire which carries a bit. bool is the real wire, and the other implementations
+// observe the circuit while it is evaluated (see analysis.rs)
+pub trait Signal: Copy {
+    fn constant(value: bool) -> Self;
+    fn nand(a: Self, b: Self) -> Self;
+    fn latch(self) -> Self { self }     // called when a flip-flop stores the signal
+    fn flipflop_created() {}
+}
+
+impl Signal for bool {
+    fn constant(value: bool) -> Self {
+        value
+    }
+    fn nand(a: bool, b: bool) -> bool {
+        !(a && b)
+    }
+}
 
-pub fn nand(a: bool, b: bool) -> bool {
-    !(a && b)
+pub fn nand<S: Signal>(a: S, b: S) -> S {
+    S::nand(a, b)
 }
 
-pub struct Flipflop { bit: bool }
+pub struct Flipflop<S: Signal = bool> { bit: S }
 
-impl Flipflop {
+impl<S: Signal> Flipflop<S> {
     pub fn new() -> Self {
-        Flipflop{ bit: false }
+        S::flipflop_created();
+        Flipflop{ bit: S::constant(false) }
     }
-    pub fn out(&self) -> bool {
+    pub fn out(&self) -> S {
         self.bit
     }
-    pub fn clock(&mut self, a: bool) {
-        self.bit = a;
+    pub fn clock(&mut self, a: S) {
+        self.bit = a.latch();
     }
 }
 
 
 // 16bit word
-pub type Word = [bool; 16];
+pub type Word<S = bool> = [S; 16];
 
 pub mod debug {
     use super::Word;
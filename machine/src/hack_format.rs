@@ -0,0 +1,146 @@
+// file formats of the machine code.
+// the text format is the official one: a line of 16 '0'/'1' characters per instruction, MSB first.
+// the binary format is 2 bytes per instruction in little endian.
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Binary,
+}
+
+impl Format {
+    // '.hack' files are the text format, and '.bin' files are the binary format
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension().and_then(|s| s.to_str()) {
+            Some("hack") => Some(Format::Text),
+            Some("bin") => Some(Format::Binary),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatError {
+    pub line: usize,    // 1-based. 0 for the binary format
+    pub message: String,
+}
+
+impl std::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.line == 0 { write!(f, "{}", self.message) } else { write!(f, "line {}: {}", self.line, self.message) }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+// blank lines are ignored. a line may end with "\r\n"
+pub fn parse_text(text: &str) -> Result<Vec<i16>, FormatError> {
+    let mut instructions = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() { continue; }
+        let error = |message: String| Err(FormatError{ line: i + 1, message });
+        if let Some(c) = line.chars().find(|&c| c != '0' && c != '1') {
+            return error(format!("unexpected character {:?} (expected '0' or '1')", c));
+        }
+        if line.len() != 16 {
+            return error(format!("expected 16 bits, found {}", line.len()));
+        }
+        instructions.push(u16::from_str_radix(line, 2).unwrap() as i16);
+    }
+    Ok(instructions)
+}
+
+pub fn to_text(instructions: &[i16]) -> String {
+    instructions.iter().map(|&inst| format!("{:016b}\n", inst as u16)).collect()
+}
+
+pub fn parse_binary(bytes: &[u8]) -> Result<Vec<i16>, FormatError> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(FormatError{ line: 0, message: format!("odd number of bytes ({}) for 16-bit instructions", bytes.len()) });
+    }
+    Ok(bytes.chunks(2).map(|a| i16::from_le_bytes([a[0], a[1]])).collect())
+}
+
+pub fn to_binary(instructions: &[i16]) -> Vec<u8> {
+    instructions.iter().flat_map(|inst| inst.to_le_bytes().to_vec()).collect()
+}
+
+pub fn parse(bytes: &[u8], format: Format) -> Result<Vec<i16>, FormatError> {
+    match format {
+        Format::Text => match std::str::from_utf8(bytes) {
+            Ok(text) => parse_text(text),
+            Err(e) => {
+                let line = bytes[.. e.valid_up_to()].iter().filter(|&&b| b == b'\n').count() + 1;
+                Err(FormatError{ line, message: "not a text file (use the .bin extension for the binary format)".to_string() })
+            }
+        },
+        Format::Binary => parse_binary(bytes)
+    }
+}
+
+pub fn to_bytes(instructions: &[i16], format: Format) -> Vec<u8> {
+    match format {
+        Format::Text => to_text(instructions).into_bytes(),
+        Format::Binary => to_binary(instructions)
+    }
+}
+
+// read a .hack (text) or .bin (binary) file. the error is a message to be shown to the user.
+pub fn read_file(path: &Path) -> Result<Vec<i16>, String> {
+    let format = Format::from_path(path).ok_or_else(|| format!("{}: unknown machine code format (expected .hack or .bin)", path.display()))?;
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    parse(&bytes, format).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// write a .hack (text) or .bin (binary) file
+pub fn write_file(path: &Path, instructions: &[i16]) -> Result<(), String> {
+    let format = Format::from_path(path).ok_or_else(|| format!("{}: unknown machine code format (expected .hack or .bin)", path.display()))?;
+    std::fs::write(path, to_bytes(instructions, format)).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text() {
+        let instructions = [2, -5448, 0x7fff, -0x8000];
+        let text = to_text(&instructions);
+        assert_eq!(text, "0000000000000010\n1110101010111000\n0111111111111111\n1000000000000000\n");
+        assert_eq!(parse_text(&text), Ok(instructions.to_vec()));
+        assert_eq!(parse_text("0000000000000010\r\n\r\n1110101010111000"), Ok(vec![2, -5448]));
+    }
+
+    #[test]
+    fn test_text_errors() {
+        let error = |line, message: &str| Err(FormatError{ line, message: message.to_string() });
+        assert_eq!(parse_text("0000000000000010\n000000000000001\n"), error(2, "expected 16 bits, found 15"));
+        assert_eq!(parse_text("\n\n00000000 00000010\n"), error(3, "unexpected character ' ' (expected '0' or '1')"));
+        assert_eq!(parse(&[b'0', b'\n', 0xff], Format::Text).unwrap_err().line, 2);
+        assert_eq!(FormatError{ line: 2, message: "expected 16 bits, found 15".to_string() }.to_string(), "line 2: expected 16 bits, found 15");
+    }
+
+    #[test]
+    fn test_binary() {
+        let instructions = [2, -5448, 0x7fff];
+        let bytes = to_binary(&instructions);
+        assert_eq!(bytes, vec![2, 0, 0xb8, 0xea, 0xff, 0x7f]);
+        assert_eq!(parse(&bytes, Format::Binary), Ok(instructions.to_vec()));
+        assert_eq!(parse_binary(&[1, 2, 3]).unwrap_err().to_string(), "odd number of bytes (3) for 16-bit instructions");
+    }
+
+    #[test]
+    fn test_files() {
+        let dir = std::env::temp_dir().join(format!("machine_test_hack_format_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in &["Prog.hack", "Prog.bin"] {
+            write_file(&dir.join(name), &[1, 2, -3]).unwrap();
+            assert_eq!(read_file(&dir.join(name)), Ok(vec![1, 2, -3]));
+        }
+        assert_eq!(std::fs::read_to_string(dir.join("Prog.hack")).unwrap().lines().count(), 3);
+        assert!(read_file(&dir.join("Prog.txt")).unwrap_err().contains("expected .hack or .bin"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,220 @@
+// built-in chips of the HDL simulator, whose behavior is given by the Rust implementations
+use crate::given::*;
+use crate::given::debug::{int2word, word2int};
+use crate::gate::*;
+use crate::adder::*;
+use crate::alu::*;
+use crate::ram::*;
+use crate::cpu::*;
+
+// a leaf of the netlist. the value of each pin is packed into an i16 (bit i = pin[i])
+pub trait Chip {
+    fn eval(&self, inputs: &[i16]) -> Vec<i16>;
+    fn clock(&mut self, _inputs: &[i16]) {}
+}
+
+pub struct Builtin {
+    pub inputs: &'static [(&'static str, usize)],
+    pub outputs: &'static [(&'static str, usize)],
+    pub clocked: bool,
+    pub new: fn() -> Box<dyn Chip>,
+}
+
+fn bit(value: i16, i: usize) -> bool {
+    value & (1 << i) != 0
+}
+
+fn bits<const N: usize>(value: i16) -> [bool; N] {
+    let mut bits = [false; N];
+    for (i, b) in bits.iter_mut().enumerate() { *b = bit(value, i); }
+    bits
+}
+
+fn pack(bits: &[bool]) -> i16 {
+    bits.iter().enumerate().fold(0, |n, (i, &b)| if b { n | (1 << i) } else { n })
+}
+
+struct Combinational(fn(&[i16]) -> Vec<i16>);
+
+impl Chip for Combinational {
+    fn eval(&self, inputs: &[i16]) -> Vec<i16> {
+        (self.0)(inputs)
+    }
+}
+
+struct Dff(Flipflop);
+
+impl Chip for Dff {
+    fn eval(&self, _: &[i16]) -> Vec<i16> {
+        vec![self.0.out() as i16]
+    }
+    fn clock(&mut self, inputs: &[i16]) {
+        self.0.clock(bit(inputs[0], 0));
+    }
+}
+
+struct Bit(BitRegister);
+
+impl Chip for Bit {
+    fn eval(&self, _: &[i16]) -> Vec<i16> {
+        vec![self.0.out() as i16]
+    }
+    fn clock(&mut self, inputs: &[i16]) {
+        self.0.clock(bit(inputs[0], 0), bit(inputs[1], 0));
+    }
+}
+
+struct Reg(Register);
+
+impl Chip for Reg {
+    fn eval(&self, _: &[i16]) -> Vec<i16> {
+        vec![word2int(self.0.out())]
+    }
+    fn clock(&mut self, inputs: &[i16]) {
+        self.0.clock(int2word(inputs[0]), bit(inputs[1], 0));
+    }
+}
+
+struct Pc(Counter);
+
+impl Chip for Pc {
+    fn eval(&self, _: &[i16]) -> Vec<i16> {
+        vec![word2int(self.0.out())]
+    }
+    fn clock(&mut self, inputs: &[i16]) {
+        // in, load, inc, reset
+        self.0.clock(int2word(inputs[0]), bit(inputs[2], 0), bit(inputs[1], 0), bit(inputs[3], 0));
+    }
+}
+
+macro_rules! ram_chip {
+    ($chip:ident, $ram:ident, $address:expr) => {
+        struct $chip(Box<$ram>);
+
+        impl Chip for $chip {
+            fn eval(&self, inputs: &[i16]) -> Vec<i16> {
+                vec![word2int(self.0.out(bits::<$address>(inputs[2])))]
+            }
+            fn clock(&mut self, inputs: &[i16]) {
+                self.0.clock(bits::<$address>(inputs[2]), int2word(inputs[0]), bit(inputs[1], 0));
+            }
+        }
+    };
+}
+
+ram_chip!(Ram8, RAM8, 3);
+ram_chip!(Ram64, RAM64, 6);
+ram_chip!(Ram512, RAM512, 9);
+ram_chip!(Ram4K, RAM4K, 12);
+ram_chip!(Ram16K, RAM16K, 14);
+
+struct CpuChip(Cpu);
+
+impl CpuChip {
+    fn input(inputs: &[i16]) -> CpuInput {
+        CpuInput{ inM: int2word(inputs[0]), instruction: int2word(inputs[1]), reset: bit(inputs[2], 0) }
+    }
+}
+
+impl Chip for CpuChip {
+    fn eval(&self, inputs: &[i16]) -> Vec<i16> {
+        let out = self.0.out(Self::input(inputs));
+        vec![word2int(out.outM), out.writeM as i16, word2int(self.0.addressM()) & 0x7fff, word2int(self.0.pc()) & 0x7fff]
+    }
+    fn clock(&mut self, inputs: &[i16]) {
+        self.0.clock(Self::input(inputs));
+    }
+}
+
+const AB: &[(&str, usize)] = &[("a", 1), ("b", 1)];
+const AB16: &[(&str, usize)] = &[("a", 16), ("b", 16)];
+const IN: &[(&str, usize)] = &[("in", 1)];
+const IN16: &[(&str, usize)] = &[("in", 16)];
+const OUT: &[(&str, usize)] = &[("out", 1)];
+const OUT16: &[(&str, usize)] = &[("out", 16)];
+
+fn combinational(inputs: &'static [(&'static str, usize)], outputs: &'static [(&'static str, usize)], new: fn() -> Box<dyn Chip>) -> Builtin {
+    Builtin{ inputs, outputs, clocked: false, new }
+}
+
+fn sequential(inputs: &'static [(&'static str, usize)], outputs: &'static [(&'static str, usize)], new: fn() -> Box<dyn Chip>) -> Builtin {
+    Builtin{ inputs, outputs, clocked: true, new }
+}
+
+fn ram(address: usize, new: fn() -> Box<dyn Chip>) -> Builtin {
+    const RAM_INPUTS: [&[(&str, usize)]; 5] = [
+        &[("in", 16), ("load", 1), ("address", 3)],
+        &[("in", 16), ("load", 1), ("address", 6)],
+        &[("in", 16), ("load", 1), ("address", 9)],
+        &[("in", 16), ("load", 1), ("address", 12)],
+        &[("in", 16), ("load", 1), ("address", 14)],
+    ];
+    sequential(RAM_INPUTS[address / 3 - 1], OUT16, new)
+}
+
+pub fn builtin(name: &str) -> Option<Builtin> {
+    Some(match name {
+        "Nand" => combinational(AB, OUT, || Box::new(Combinational(|x| vec![nand(bit(x[0], 0), bit(x[1], 0)) as i16]))),
+        "Not" => combinational(IN, OUT, || Box::new(Combinational(|x| vec![not(bit(x[0], 0)) as i16]))),
+        "And" => combinational(AB, OUT, || Box::new(Combinational(|x| vec![and(bit(x[0], 0), bit(x[1], 0)) as i16]))),
+        "Or" => combinational(AB, OUT, || Box::new(Combinational(|x| vec![or(bit(x[0], 0), bit(x[1], 0)) as i16]))),
+        "Xor" => combinational(AB, OUT, || Box::new(Combinational(|x| vec![xor(bit(x[0], 0), bit(x[1], 0)) as i16]))),
+        "Mux" => combinational(&[("a", 1), ("b", 1), ("sel", 1)], OUT,
+            || Box::new(Combinational(|x| vec![mux(bit(x[0], 0), bit(x[1], 0), bit(x[2], 0)) as i16]))),
+        "DMux" => combinational(&[("in", 1), ("sel", 1)], AB,
+            || Box::new(Combinational(|x| dmux(bit(x[0], 0), bit(x[1], 0)).iter().map(|&b| b as i16).collect()))),
+        "Not16" => combinational(IN16, OUT16, || Box::new(Combinational(|x| vec![word2int(not16(int2word(x[0])))]))),
+        "And16" => combinational(AB16, OUT16, || Box::new(Combinational(|x| vec![word2int(and16(int2word(x[0]), int2word(x[1])))]))),
+        "Or16" => combinational(AB16, OUT16, || Box::new(Combinational(|x| {
+            let (a, b) = (int2word(x[0]), int2word(x[1]));
+            vec![pack(&(0 .. 16).map(|i| or(a[i], b[i])).collect::<Vec<_>>())]
+        }))),
+        "Mux16" => combinational(&[("a", 16), ("b", 16), ("sel", 1)], OUT16,
+            || Box::new(Combinational(|x| vec![word2int(mux16(int2word(x[0]), int2word(x[1]), bit(x[2], 0)))]))),
+        "Or8Way" => combinational(&[("in", 8)], OUT, || Box::new(Combinational(|x| vec![or8way(bits(x[0])) as i16]))),
+        "Mux4Way16" => combinational(&[("a", 16), ("b", 16), ("c", 16), ("d", 16), ("sel", 2)], OUT16,
+            || Box::new(Combinational(|x| {
+                let w = |i: usize| int2word(x[i]);
+                vec![word2int(mux4way16(w(0), w(1), w(2), w(3), bits(x[4])))]
+            }))),
+        "Mux8Way16" => combinational(
+            &[("a", 16), ("b", 16), ("c", 16), ("d", 16), ("e", 16), ("f", 16), ("g", 16), ("h", 16), ("sel", 3)], OUT16,
+            || Box::new(Combinational(|x| {
+                let w = |i: usize| int2word(x[i]);
+                vec![word2int(mux8way16(w(0), w(1), w(2), w(3), w(4), w(5), w(6), w(7), bits(x[8])))]
+            }))),
+        "DMux4Way" => combinational(&[("in", 1), ("sel", 2)], &[("a", 1), ("b", 1), ("c", 1), ("d", 1)],
+            || Box::new(Combinational(|x| dmux4way(bit(x[0], 0), bits(x[1])).iter().map(|&b| b as i16).collect()))),
+        "DMux8Way" => combinational(&[("in", 1), ("sel", 3)],
+            &[("a", 1), ("b", 1), ("c", 1), ("d", 1), ("e", 1), ("f", 1), ("g", 1), ("h", 1)],
+            || Box::new(Combinational(|x| dmux8way(bit(x[0], 0), bits(x[1])).iter().map(|&b| b as i16).collect()))),
+        "HalfAdder" => combinational(AB, &[("sum", 1), ("carry", 1)],
+            || Box::new(Combinational(|x| half_adder(bit(x[0], 0), bit(x[1], 0)).iter().map(|&b| b as i16).collect()))),
+        "FullAdder" => combinational(&[("a", 1), ("b", 1), ("c", 1)], &[("sum", 1), ("carry", 1)],
+            || Box::new(Combinational(|x| full_adder(bit(x[0], 0), bit(x[1], 0), bit(x[2], 0)).iter().map(|&b| b as i16).collect()))),
+        "Add16" => combinational(AB16, OUT16, || Box::new(Combinational(|x| vec![word2int(add16(int2word(x[0]), int2word(x[1])))]))),
+        "Inc16" => combinational(IN16, OUT16, || Box::new(Combinational(|x| vec![word2int(inc16(int2word(x[0])))]))),
+        "ALU" => combinational(
+            &[("x", 16), ("y", 16), ("zx", 1), ("nx", 1), ("zy", 1), ("ny", 1), ("f", 1), ("no", 1)],
+            &[("out", 16), ("zr", 1), ("ng", 1)],
+            || Box::new(Combinational(|x| {
+                let b = |i: usize| bit(x[i], 0);
+                let out = alu(int2word(x[0]), int2word(x[1]), b(2), b(3), b(4), b(5), b(6), b(7));
+                vec![word2int(out.out), out.zr as i16, out.ng as i16]
+            }))),
+        "DFF" => sequential(IN, OUT, || Box::new(Dff(Flipflop::new()))),
+        "Bit" => sequential(&[("in", 1), ("load", 1)], OUT, || Box::new(Bit(BitRegister::new()))),
+        "Register" => sequential(&[("in", 16), ("load", 1)], OUT16, || Box::new(Reg(Register::new()))),
+        "PC" => sequential(&[("in", 16), ("load", 1), ("inc", 1), ("reset", 1)], OUT16, || Box::new(Pc(Counter::new()))),
+        "RAM8" => ram(3, || Box::new(Ram8(Box::new(RAM8::new())))),
+        "RAM64" => ram(6, || Box::new(Ram64(Box::new(RAM64::new())))),
+        "RAM512" => ram(9, || Box::new(Ram512(Box::new(RAM512::new())))),
+        "RAM4K" => ram(12, || Box::new(Ram4K(Box::new(RAM4K::new())))),
+        "RAM16K" => ram(14, || Box::new(Ram16K(Box::new(RAM16K::new())))),
+        "CPU" => sequential(
+            &[("inM", 16), ("instruction", 16), ("reset", 1)],
+            &[("outM", 16), ("writeM", 1), ("addressM", 15), ("pc", 15)],
+            || Box::new(CpuChip(Cpu::new()))),
+        _ => return None
+    })
+}
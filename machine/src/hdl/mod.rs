@@ -0,0 +1,86 @@
+// simulator of chips written in the nand2tetris HDL
+mod parser;
+mod builtin;
+mod simulator;
+
+pub use parser::*;
+pub use builtin::{Builtin, Chip, builtin};
+pub use simulator::*;
+
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HdlError {
+    pub chip: String,       // name of the chip whose definition is wrong
+    pub line: usize,        // 1-origin; 0 if the error has no location
+    pub column: usize,
+    pub message: String,
+}
+
+impl HdlError {
+    pub fn new(message: impl Into<String>, line: usize, column: usize) -> Self {
+        Self{ chip: String::new(), line, column, message: message.into() }
+    }
+    fn in_chip(self, chip: &str) -> Self {
+        Self{ chip: chip.to_string(), ..self }
+    }
+}
+
+impl std::fmt::Display for HdlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}.hdl: {}", self.chip, self.message)
+        } else {
+            write!(f, "{}.hdl:{}:{}: {}", self.chip, self.line, self.column, self.message)
+        }
+    }
+}
+
+impl std::error::Error for HdlError {}
+
+// chips defined in HDL. chips which are not defined here are resolved to the built-in chips
+#[derive(Debug, Clone, Default)]
+pub struct HdlLibrary {
+    chips: HashMap<String, ChipDef>,
+}
+
+impl HdlLibrary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn add(&mut self, source: &str) -> Result<&ChipDef, HdlError> {
+        let chip = parse(source)?;
+        let name = chip.name.clone();
+        self.chips.insert(name.clone(), chip);
+        Ok(&self.chips[&name])
+    }
+    pub fn add_file(&mut self, path: &Path) -> Result<&ChipDef, HdlError> {
+        let chip = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+        let source = std::fs::read_to_string(path).map_err(|e| HdlError::new(e.to_string(), 0, 0).in_chip(&chip))?;
+        let def = parse(&source).map_err(|e| e.in_chip(&chip))?;
+        if def.name != chip {
+            return Err(HdlError::new(format!("chip '{}' is defined in {}", def.name, path.display()), 0, 0).in_chip(&chip));
+        }
+        self.chips.insert(chip.clone(), def);
+        Ok(&self.chips[&chip])
+    }
+    // add all the .hdl files in the directory
+    pub fn load_dir(&mut self, dir: &Path) -> Result<(), HdlError> {
+        let entries = std::fs::read_dir(dir).map_err(|e| HdlError::new(format!("{}: {}", dir.display(), e), 0, 0))?;
+        let mut paths = entries.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().map(|ext| ext == "hdl").unwrap_or(false))
+            .collect::<Vec<_>>();
+        paths.sort();
+        for path in paths {
+            self.add_file(&path)?;
+        }
+        Ok(())
+    }
+    pub fn get(&self, name: &str) -> Option<&ChipDef> {
+        self.chips.get(name)
+    }
+    pub fn build(&self, name: &str) -> Result<Simulator, HdlError> {
+        Simulator::build(self, name)
+    }
+}
@@ -0,0 +1,316 @@
+// parser of nand2tetris HDL, e.g.
+//
+//   CHIP Xor {
+//       IN a, b;
+//       OUT out;
+//       PARTS:
+//       Not(in=a, out=nota);
+//       ...
+//   }
+use super::HdlError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinDecl {
+    pub name: String,
+    pub width: usize,
+}
+
+// a pin or a sub-bus of it ('a', 'a[3]' or 'a[0..7]')
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinRef {
+    pub name: String,
+    pub range: Option<(usize, usize)>,  // inclusive
+    pub line: usize,
+    pub column: usize,
+}
+
+// right hand side of a connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Wire {
+    Const(bool),
+    Pin(PinRef),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Connection {
+    pub pin: PinRef,    // pin of the part
+    pub wire: Wire,     // pin of the chip, internal pin or constant
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Part {
+    pub chip: String,
+    pub connections: Vec<Connection>,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Body {
+    Parts(Vec<Part>),
+    Builtin(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChipDef {
+    pub name: String,
+    pub inputs: Vec<PinDecl>,
+    pub outputs: Vec<PinDecl>,
+    pub body: Body,
+    pub clocked: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Number(usize),
+    Symbol(&'static str),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Token::Ident(s) => write!(f, "'{}'", s),
+            Token::Number(n) => write!(f, "'{}'", n),
+            Token::Symbol(s) => write!(f, "'{}'", s),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize, usize)>, HdlError> {
+    const SYMBOLS: [&str; 10] = ["..", "{", "}", "(", ")", "[", "]", ",", ";", "="];
+    let mut tokens = Vec::new();
+    let (mut line, mut column) = (1, 1);
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_whitespace() {
+            c.len_utf8()
+        } else if rest.starts_with("//") {
+            rest.find('\n').unwrap_or(rest.len())
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            match comment.find("*/") {
+                Some(i) => i + 4,
+                None => return Err(HdlError::new("unterminated comment", line, column))
+            }
+        } else if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)) {
+            tokens.push((Token::Symbol(symbol), line, column));
+            symbol.len()
+        } else if c.is_ascii_digit() {
+            let len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            let n = rest[..len].parse::<usize>().map_err(|_| HdlError::new("too large number", line, column))?;
+            tokens.push((Token::Number(n), line, column));
+            len
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.')).unwrap_or(rest.len());
+            // 'a[0..7]' must not be read as an identifier with dots
+            let len = rest[..len].find("..").unwrap_or(len);
+            tokens.push((Token::Ident(rest[..len].to_string()), line, column));
+            len
+        } else if c == ':' {
+            tokens.push((Token::Symbol(":"), line, column));
+            1
+        } else {
+            return Err(HdlError::new(format!("unexpected character '{}'", c), line, column));
+        };
+        for c in rest[..len].chars() {
+            if c == '\n' { line += 1; column = 1; } else { column += 1; }
+        }
+        rest = &rest[len..];
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize, usize)>,
+    pos: usize,
+    end: (usize, usize),    // position of the end of file
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _, _)| token)
+    }
+    fn position(&self) -> (usize, usize) {
+        self.tokens.get(self.pos).map(|&(_, line, column)| (line, column)).unwrap_or(self.end)
+    }
+    fn error<T>(&self, expected: &str) -> Result<T, HdlError> {
+        let (line, column) = self.position();
+        let found = match self.peek() {
+            Some(token) => token.to_string(),
+            None => "end of file".to_string()
+        };
+        Err(HdlError::new(format!("expected {}, found {}", expected, found), line, column))
+    }
+    fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol)
+    }
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(s)) if s == keyword)
+    }
+    fn symbol(&mut self, symbol: &str) -> Result<(), HdlError> {
+        if self.is_symbol(symbol) { self.pos += 1; Ok(()) } else { self.error(&format!("'{}'", symbol)) }
+    }
+    fn keyword(&mut self, keyword: &str) -> Result<(), HdlError> {
+        if self.is_keyword(keyword) { self.pos += 1; Ok(()) } else { self.error(&format!("'{}'", keyword)) }
+    }
+    fn ident(&mut self) -> Result<String, HdlError> {
+        match self.peek() {
+            Some(Token::Ident(s)) => { let s = s.clone(); self.pos += 1; Ok(s) },
+            _ => self.error("an identifier")
+        }
+    }
+    fn number(&mut self) -> Result<usize, HdlError> {
+        match self.peek() {
+            Some(&Token::Number(n)) => { self.pos += 1; Ok(n) },
+            _ => self.error("a number")
+        }
+    }
+
+    // 'IN a, b[16];'
+    fn pin_decls(&mut self, keyword: &str) -> Result<Vec<PinDecl>, HdlError> {
+        let mut pins = Vec::new();
+        if !self.is_keyword(keyword) { return Ok(pins); }
+        self.pos += 1;
+        loop {
+            let name = self.ident()?;
+            let width = if self.is_symbol("[") {
+                self.pos += 1;
+                let width = self.number()?;
+                self.symbol("]")?;
+                width
+            } else { 1 };
+            if width == 0 || width > 16 {
+                return self.error("a width of 1 to 16");
+            }
+            pins.push(PinDecl{ name, width });
+            if self.is_symbol(";") { self.pos += 1; return Ok(pins); }
+            self.symbol(",")?;
+        }
+    }
+
+    // 'a', 'a[3]' or 'a[0..7]'
+    fn pin_ref(&mut self) -> Result<PinRef, HdlError> {
+        let (line, column) = self.position();
+        let name = self.ident()?;
+        let range = if self.is_symbol("[") {
+            self.pos += 1;
+            let lo = self.number()?;
+            let hi = if self.is_symbol("..") { self.pos += 1; self.number()? } else { lo };
+            self.symbol("]")?;
+            if lo > hi { return Err(HdlError::new(format!("invalid range {}..{}", lo, hi), line, column)); }
+            Some((lo, hi))
+        } else { None };
+        Ok(PinRef{ name, range, line, column })
+    }
+
+    fn part(&mut self) -> Result<Part, HdlError> {
+        let (line, column) = self.position();
+        let chip = self.ident()?;
+        self.symbol("(")?;
+        let mut connections = Vec::new();
+        loop {
+            let pin = self.pin_ref()?;
+            self.symbol("=")?;
+            let wire = if self.is_keyword("true") {
+                self.pos += 1;
+                Wire::Const(true)
+            } else if self.is_keyword("false") {
+                self.pos += 1;
+                Wire::Const(false)
+            } else {
+                Wire::Pin(self.pin_ref()?)
+            };
+            connections.push(Connection{ pin, wire });
+            if self.is_symbol(")") { self.pos += 1; break; }
+            self.symbol(",")?;
+        }
+        self.symbol(";")?;
+        Ok(Part{ chip, connections, line, column })
+    }
+
+    fn chip(&mut self) -> Result<ChipDef, HdlError> {
+        self.keyword("CHIP")?;
+        let name = self.ident()?;
+        self.symbol("{")?;
+        let inputs = self.pin_decls("IN")?;
+        let outputs = self.pin_decls("OUT")?;
+        let body = if self.is_keyword("BUILTIN") {
+            self.pos += 1;
+            let builtin = self.ident()?;
+            self.symbol(";")?;
+            Body::Builtin(builtin)
+        } else {
+            self.keyword("PARTS")?;
+            self.symbol(":")?;
+            let mut parts = Vec::new();
+            while !self.is_symbol("}") && !self.is_keyword("CLOCKED") && self.peek().is_some() {
+                parts.push(self.part()?);
+            }
+            Body::Parts(parts)
+        };
+        let mut clocked = Vec::new();
+        if self.is_keyword("CLOCKED") {
+            self.pos += 1;
+            loop {
+                clocked.push(self.ident()?);
+                if self.is_symbol(";") { self.pos += 1; break; }
+                self.symbol(",")?;
+            }
+        }
+        self.symbol("}")?;
+        if self.peek().is_some() { return self.error("end of file"); }
+        Ok(ChipDef{ name, inputs, outputs, body, clocked })
+    }
+}
+
+pub fn parse(source: &str) -> Result<ChipDef, HdlError> {
+    let tokens = tokenize(source)?;
+    let lines = source.split('\n').collect::<Vec<_>>();
+    let end = (lines.len(), lines.last().map_or(0, |line| line.chars().count()) + 1);
+    Parser{ tokens, pos: 0, end }.chip()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let chip = parse("
+        /** Exclusive-or gate */
+        CHIP Xor {
+            IN a, b[16];
+            OUT out;    // comment
+            PARTS:
+            Nand(a=a[0..7], b[3]=true, out=x);
+        }").unwrap();
+        assert_eq!(chip.name, "Xor");
+        assert_eq!(chip.inputs, vec![PinDecl{ name: "a".to_string(), width: 1 }, PinDecl{ name: "b".to_string(), width: 16 }]);
+        assert_eq!(chip.outputs, vec![PinDecl{ name: "out".to_string(), width: 1 }]);
+        let parts = match chip.body { Body::Parts(parts) => parts, _ => panic!() };
+        assert_eq!(parts.len(), 1);
+        assert_eq!((parts[0].chip.as_str(), parts[0].line, parts[0].column), ("Nand", 7, 13));
+        let pin = |name: &str, range, line, column| PinRef{ name: name.to_string(), range, line, column };
+        assert_eq!(parts[0].connections, vec![
+            Connection{ pin: pin("a", None, 7, 18), wire: Wire::Pin(pin("a", Some((0, 7)), 7, 20)) },
+            Connection{ pin: pin("b", Some((3, 3)), 7, 29), wire: Wire::Const(true) },
+            Connection{ pin: pin("out", None, 7, 40), wire: Wire::Pin(pin("x", None, 7, 44)) },
+        ]);
+    }
+
+    #[test]
+    fn test_builtin() {
+        let chip = parse("CHIP DFF { IN in; OUT out; BUILTIN DFF; CLOCKED in; }").unwrap();
+        assert_eq!(chip.body, Body::Builtin("DFF".to_string()));
+        assert_eq!(chip.clocked, vec!["in".to_string()]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(parse("CHIP Foo {\n  IN a\n  OUT b;").unwrap_err(), HdlError::new("expected ',', found 'OUT'", 3, 3));
+        assert_eq!(parse("CHIP Foo { PARTS: Not(in=a, out=b) }").unwrap_err(), HdlError::new("expected ';', found '}'", 1, 36));
+        assert_eq!(parse("CHIP Foo { IN a[17]; }").unwrap_err().message, "expected a width of 1 to 16, found ';'");
+        assert_eq!(parse("CHIP Foo {\n  PARTS: @").unwrap_err(), HdlError::new("unexpected character '@'", 2, 10));
+        assert_eq!(parse("CHIP Foo {").unwrap_err(), HdlError::new("expected 'PARTS', found end of file", 1, 11));
+    }
+}
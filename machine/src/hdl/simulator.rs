@@ -0,0 +1,428 @@
+// flattens a chip into a netlist of built-in chips, and simulates it
+use std::collections::HashMap;
+use super::*;
+
+// nesting limit of chips, to detect recursive definitions
+const MAX_DEPTH: usize = 64;
+
+// wire 0 is always false, and wire 1 is always true
+const FALSE: usize = 0;
+const TRUE: usize = 1;
+
+type Bus = Vec<usize>;  // wire index of each bit
+
+enum Kind {
+    Builtin(Box<dyn Chip>),
+    Copy,   // connection from an output of a part to an output pin of the chip
+}
+
+struct Component {
+    kind: Kind,
+    clocked: bool,
+    inputs: Vec<Bus>,
+    outputs: Vec<Bus>,
+}
+
+pub struct Simulator {
+    name: String,
+    wires: Vec<bool>,
+    components: Vec<Component>,
+    inputs: Vec<(String, Bus)>,
+    outputs: Vec<(String, Bus)>,
+}
+
+// pin list of a chip, which is either defined in HDL or built-in
+struct Interface {
+    inputs: Vec<(String, usize)>,
+    outputs: Vec<(String, usize)>,
+}
+
+impl Interface {
+    fn width(&self, name: &str) -> Option<(bool, usize)> {
+        if let Some((_, width)) = self.inputs.iter().find(|(pin, _)| pin == name) {
+            Some((true, *width))
+        } else {
+            self.outputs.iter().find(|(pin, _)| pin == name).map(|(_, width)| (false, *width))
+        }
+    }
+}
+
+fn slice(bus: &[usize], range: Option<(usize, usize)>, pin: &PinRef) -> Result<Bus, HdlError> {
+    match range {
+        None => Ok(bus.to_vec()),
+        Some((lo, hi)) if hi < bus.len() => Ok(bus[lo ..= hi].to_vec()),
+        Some((_, hi)) => Err(HdlError::new(
+            format!("sub bus index {} of '{}' is out of the width {}", hi, pin.name, bus.len()), pin.line, pin.column)),
+    }
+}
+
+fn range_width(range: Option<(usize, usize)>, width: usize) -> usize {
+    range.map(|(lo, hi)| hi - lo + 1).unwrap_or(width)
+}
+
+struct Builder<'a> {
+    library: &'a HdlLibrary,
+    wires: usize,
+    components: Vec<Component>,
+}
+
+impl<'a> Builder<'a> {
+    fn new_bus(&mut self, width: usize) -> Bus {
+        let bus = (self.wires .. self.wires + width).collect();
+        self.wires += width;
+        bus
+    }
+
+    fn interface(&self, name: &str) -> Option<Interface> {
+        let pins = |decls: &[PinDecl]| decls.iter().map(|pin| (pin.name.clone(), pin.width)).collect();
+        let pins_of = |decls: &[(&str, usize)]| decls.iter().map(|&(name, width)| (name.to_string(), width)).collect();
+        match self.library.get(name) {
+            Some(def) => Some(Interface{ inputs: pins(&def.inputs), outputs: pins(&def.outputs) }),
+            None => builtin(name).map(|chip| Interface{ inputs: pins_of(chip.inputs), outputs: pins_of(chip.outputs) })
+        }
+    }
+
+    // instantiate the chip, whose pins are connected to the given buses
+    fn instantiate(&mut self, name: &str, inputs: Vec<Bus>, outputs: Vec<Bus>, depth: usize) -> Result<(), HdlError> {
+        if depth > MAX_DEPTH {
+            return Err(HdlError::new("too deep nesting of chips; the definition may be recursive", 0, 0).in_chip(name));
+        }
+        match self.library.get(name) {
+            Some(def) => match &def.body {
+                Body::Parts(parts) => self.flatten(def, parts, inputs, outputs, depth),
+                Body::Builtin(builtin_name) => {
+                    let chip = builtin(builtin_name)
+                        .ok_or_else(|| HdlError::new(format!("unknown built-in chip '{}'", builtin_name), 0, 0).in_chip(name))?;
+                    self.push_builtin(chip, inputs, outputs);
+                    Ok(())
+                }
+            },
+            None => match builtin(name) {
+                Some(chip) => { self.push_builtin(chip, inputs, outputs); Ok(()) },
+                None => Err(HdlError::new(format!("unknown chip '{}'", name), 0, 0)),
+            }
+        }
+    }
+
+    fn push_builtin(&mut self, chip: Builtin, inputs: Vec<Bus>, outputs: Vec<Bus>) {
+        self.components.push(Component{ kind: Kind::Builtin((chip.new)()), clocked: chip.clocked, inputs, outputs });
+    }
+
+    fn flatten(&mut self, def: &ChipDef, parts: &[Part], inputs: Vec<Bus>, outputs: Vec<Bus>, depth: usize) -> Result<(), HdlError> {
+        let error = |message: String, pin: &PinRef| HdlError::new(message, pin.line, pin.column).in_chip(&def.name);
+        let chip_inputs = def.inputs.iter().map(|pin| pin.name.as_str()).zip(inputs).collect::<HashMap<_, _>>();
+        let chip_outputs = def.outputs.iter().map(|pin| pin.name.as_str()).zip(outputs).collect::<HashMap<_, _>>();
+
+        // first, allocate the outputs of the parts, and define the internal pins by them
+        let mut internals = HashMap::<&str, Bus>::new();
+        let mut part_outputs = Vec::new();
+        for part in parts {
+            let interface = self.interface(&part.chip)
+                .ok_or_else(|| HdlError::new(format!("unknown chip '{}'", part.chip), part.line, part.column).in_chip(&def.name))?;
+            let buses = interface.outputs.iter().map(|&(_, width)| self.new_bus(width)).collect::<Vec<_>>();
+            for connection in &part.connections {
+                let (is_input, width) = interface.width(&connection.pin.name)
+                    .ok_or_else(|| error(format!("'{}' has no pin '{}'", part.chip, connection.pin.name), &connection.pin))?;
+                if is_input { continue; }
+                let index = interface.outputs.iter().position(|(name, _)| *name == connection.pin.name).unwrap();
+                let source = slice(&buses[index], connection.pin.range, &connection.pin)?;
+                let target = match &connection.wire {
+                    Wire::Pin(target) => target,
+                    Wire::Const(_) => return Err(error("an output pin cannot be connected to a constant".to_string(), &connection.pin)),
+                };
+                if chip_inputs.contains_key(target.name.as_str()) {
+                    return Err(error(format!("cannot write to the input pin '{}'", target.name), target));
+                } else if let Some(bus) = chip_outputs.get(target.name.as_str()) {
+                    let bus = slice(bus, target.range, target)?;
+                    if bus.len() != source.len() {
+                        return Err(error(format!("width mismatch: {} bits to {} bits", source.len(), bus.len()), target));
+                    }
+                    self.components.push(Component{ kind: Kind::Copy, clocked: false, inputs: vec![source], outputs: vec![bus] });
+                } else if target.range.is_some() {
+                    return Err(error(format!("sub bus of the internal pin '{}' cannot be used", target.name), target));
+                } else if internals.contains_key(target.name.as_str()) {
+                    return Err(error(format!("internal pin '{}' has multiple outputs", target.name), target));
+                } else {
+                    debug_assert!(range_width(connection.pin.range, width) == source.len());
+                    internals.insert(&target.name, source);
+                }
+            }
+            part_outputs.push((interface, buses));
+        }
+
+        // next, connect the inputs of the parts, and instantiate them
+        for (part, (interface, outputs)) in parts.iter().zip(part_outputs) {
+            let mut inputs = interface.inputs.iter().map(|&(_, width)| vec![FALSE; width]).collect::<Vec<_>>();
+            for connection in &part.connections {
+                let index = match interface.inputs.iter().position(|(name, _)| *name == connection.pin.name) {
+                    Some(index) => index,
+                    None => continue,
+                };
+                let width = range_width(connection.pin.range, interface.inputs[index].1);
+                let source = match &connection.wire {
+                    Wire::Const(value) => vec![if *value { TRUE } else { FALSE }; width],
+                    Wire::Pin(source) => {
+                        let bus = if let Some(bus) = chip_inputs.get(source.name.as_str()) {
+                            bus
+                        } else if let Some(bus) = internals.get(source.name.as_str()) {
+                            bus
+                        } else if chip_outputs.contains_key(source.name.as_str()) {
+                            return Err(error(format!("cannot read the output pin '{}'", source.name), source));
+                        } else {
+                            return Err(error(format!("undefined pin '{}'", source.name), source));
+                        };
+                        slice(bus, source.range, source)?
+                    }
+                };
+                if source.len() != width {
+                    return Err(error(format!("width mismatch: {} bits to {} bits", source.len(), width), &connection.pin));
+                }
+                let lo = connection.pin.range.map(|(lo, _)| lo).unwrap_or(0);
+                slice(&inputs[index], connection.pin.range, &connection.pin)?;
+                inputs[index][lo .. lo + width].copy_from_slice(&source);
+            }
+            self.instantiate(&part.chip, inputs, outputs, depth + 1)
+                .map_err(|e| if e.chip.is_empty() { HdlError{ line: part.line, column: part.column, ..e }.in_chip(&def.name) } else { e })?;
+        }
+        Ok(())
+    }
+}
+
+impl Simulator {
+    pub fn build(library: &HdlLibrary, name: &str) -> Result<Self, HdlError> {
+        let mut builder = Builder{ library, wires: 2, components: Vec::new() };
+        let interface = builder.interface(name).ok_or_else(|| HdlError::new(format!("unknown chip '{}'", name), 0, 0).in_chip(name))?;
+        let inputs = interface.inputs.iter().map(|(name, width)| (name.clone(), builder.new_bus(*width))).collect::<Vec<_>>();
+        let outputs = interface.outputs.iter().map(|(name, width)| (name.clone(), builder.new_bus(*width))).collect::<Vec<_>>();
+        builder.instantiate(
+            name,
+            inputs.iter().map(|(_, bus)| bus.clone()).collect(),
+            outputs.iter().map(|(_, bus)| bus.clone()).collect(),
+            0).map_err(|e| if e.chip.is_empty() { e.in_chip(name) } else { e })?;
+        let mut wires = vec![false; builder.wires];
+        wires[TRUE] = true;
+        let mut simulator = Self{ name: name.to_string(), wires, components: builder.components, inputs, outputs };
+        simulator.eval()?;
+        Ok(simulator)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn input_pins(&self) -> impl Iterator<Item = (&str, usize)> + '_ {
+        self.inputs.iter().map(|(name, bus)| (name.as_str(), bus.len()))
+    }
+    pub fn output_pins(&self) -> impl Iterator<Item = (&str, usize)> + '_ {
+        self.outputs.iter().map(|(name, bus)| (name.as_str(), bus.len()))
+    }
+    // number of the built-in chips in the netlist
+    pub fn component_count(&self) -> usize {
+        self.components.iter().filter(|component| matches!(component.kind, Kind::Builtin(_))).count()
+    }
+
+    fn read(&self, bus: &[usize]) -> i16 {
+        bus.iter().enumerate().fold(0, |n, (i, &wire)| if self.wires[wire] { n | (1 << i) } else { n })
+    }
+
+    // set a value to an input pin. call eval() or clock() to propagate it
+    pub fn set(&mut self, pin: &str, value: i16) -> Result<(), HdlError> {
+        let bus = match self.inputs.iter().find(|(name, _)| name == pin) {
+            Some((_, bus)) => bus,
+            None => return Err(HdlError::new(format!("no input pin '{}'", pin), 0, 0).in_chip(&self.name))
+        };
+        for (i, &wire) in bus.iter().enumerate() {
+            self.wires[wire] = value & (1 << i) != 0;
+        }
+        Ok(())
+    }
+
+    // value of an input or output pin
+    pub fn get(&self, pin: &str) -> Result<i16, HdlError> {
+        match self.inputs.iter().chain(self.outputs.iter()).find(|(name, _)| name == pin) {
+            Some((_, bus)) => Ok(self.read(bus)),
+            None => Err(HdlError::new(format!("no pin '{}'", pin), 0, 0).in_chip(&self.name))
+        }
+    }
+
+    // propagate the signals until all the wires settle
+    pub fn eval(&mut self) -> Result<(), HdlError> {
+        for _ in 0 ..= self.components.len() {
+            let mut changed = false;
+            for i in 0 .. self.components.len() {
+                let component = &self.components[i];
+                let inputs = component.inputs.iter().map(|bus| self.read(bus)).collect::<Vec<_>>();
+                let outputs = match &component.kind {
+                    Kind::Builtin(chip) => chip.eval(&inputs),
+                    Kind::Copy => inputs,
+                };
+                for (bus, value) in component.outputs.iter().zip(outputs) {
+                    for (bit, &wire) in bus.iter().enumerate() {
+                        let value = value & (1 << bit) != 0;
+                        if self.wires[wire] != value {
+                            self.wires[wire] = value;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+            if !changed { return Ok(()); }
+        }
+        Err(HdlError::new("signals do not settle; the chip may have a combinational loop", 0, 0).in_chip(&self.name))
+    }
+
+    // a clock cycle (tick and tock): all the clocked chips latch their inputs at once, then the signals propagate
+    pub fn clock(&mut self) -> Result<(), HdlError> {
+        self.eval()?;
+        let inputs = self.components.iter()
+            .map(|component| if component.clocked { component.inputs.iter().map(|bus| self.read(bus)).collect() } else { Vec::new() })
+            .collect::<Vec<Vec<_>>>();
+        for (component, inputs) in self.components.iter_mut().zip(inputs) {
+            if let (true, Kind::Builtin(chip)) = (component.clocked, &mut component.kind) {
+                chip.clock(&inputs);
+            }
+        }
+        self.eval()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library(sources: &[&str]) -> HdlLibrary {
+        let mut library = HdlLibrary::new();
+        for source in sources { library.add(source).unwrap(); }
+        library
+    }
+
+    const XOR: &str = "
+        CHIP Xor {
+            IN a, b;
+            OUT out;
+            PARTS:
+            Nand(a=a, b=b, out=nand);
+            Nand(a=a, b=nand, out=x);
+            Nand(a=nand, b=b, out=y);
+            Nand(a=x, b=y, out=out);
+        }";
+
+    #[test]
+    fn test_xor() {
+        let mut sim = library(&[XOR]).build("Xor").unwrap();
+        assert_eq!(sim.component_count(), 4);
+        for &(a, b) in &[(0, 0), (0, 1), (1, 0), (1, 1)] {
+            sim.set("a", a).unwrap();
+            sim.set("b", b).unwrap();
+            sim.eval().unwrap();
+            assert_eq!(sim.get("out").unwrap(), a ^ b);
+        }
+    }
+
+    #[test]
+    fn test_bus() {
+        // swap the bytes, and use the parts written in reverse order
+        let mut sim = library(&["
+            CHIP Swap {
+                IN in[16], zero;
+                OUT out[16], lo[8], one;
+                PARTS:
+                Or(a=zero, b=true, out=one);
+                Not16(in=x, out[0..7]=out[8..15], out[8..15]=out[0..7], out[0..7]=lo);
+                Not16(in=in, out=x);
+            }"]).build("Swap").unwrap();
+        sim.set("in", 0x12f4).unwrap();
+        sim.eval().unwrap();
+        assert_eq!(sim.get("out").unwrap(), 0xf412u16 as i16);
+        assert_eq!(sim.get("lo").unwrap(), 0xf4);
+        assert_eq!(sim.get("one").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_constants() {
+        let mut sim = library(&["
+            CHIP Const {
+                IN in[4];
+                OUT out[16];
+                PARTS:
+                Or16(a[0..3]=in, a[4..7]=true, a[15]=true, b=false, out=out);
+            }"]).build("Const").unwrap();
+        sim.set("in", 0b1010).unwrap();
+        sim.eval().unwrap();
+        assert_eq!(sim.get("out").unwrap(), 0x80fau16 as i16);
+    }
+
+    #[test]
+    fn test_sequential() {
+        // Bit made of DFF, and a counter with a feedback loop through a register
+        let mut library = library(&["
+            CHIP Bit {
+                IN in, load;
+                OUT out;
+                PARTS:
+                Mux(a=dffout, b=in, sel=load, out=muxout);
+                DFF(in=muxout, out=out, out=dffout);
+            }", "
+            CHIP Counter {
+                IN inc;
+                OUT out[16];
+                PARTS:
+                Inc16(in=current, out=next);
+                Register(in=next, load=inc, out=out, out=current);
+            }"]);
+        let mut sim = library.build("Bit").unwrap();
+        let mut expected = 0;
+        for &(input, load) in &[(1, 0), (1, 1), (0, 0), (0, 1), (1, 1)] {
+            sim.set("in", input).unwrap();
+            sim.set("load", load).unwrap();
+            sim.eval().unwrap();
+            assert_eq!(sim.get("out").unwrap(), expected);
+            sim.clock().unwrap();
+            if load == 1 { expected = input; }
+            assert_eq!(sim.get("out").unwrap(), expected);
+        }
+
+        let mut sim = library.build("Counter").unwrap();
+        sim.set("inc", 1).unwrap();
+        for _ in 0 .. 3 { sim.clock().unwrap(); }
+        sim.set("inc", 0).unwrap();
+        sim.clock().unwrap();
+        assert_eq!(sim.get("out").unwrap(), 3);
+
+        // user definitions override the built-in chips
+        library.add("CHIP Inc16 { IN in[16]; OUT out[16]; PARTS: Add16(a=in, b[1]=true, out=out); }").unwrap();
+        let mut sim = library.build("Counter").unwrap();
+        sim.set("inc", 1).unwrap();
+        for _ in 0 .. 3 { sim.clock().unwrap(); }
+        assert_eq!(sim.get("out").unwrap(), 6);
+    }
+
+    #[test]
+    fn test_builtin() {
+        let mut sim = HdlLibrary::new().build("RAM8").unwrap();
+        sim.set("in", 1234).unwrap();
+        sim.set("load", 1).unwrap();
+        sim.set("address", 5).unwrap();
+        sim.clock().unwrap();
+        sim.set("load", 0).unwrap();
+        sim.set("address", 4).unwrap();
+        sim.eval().unwrap();
+        assert_eq!(sim.get("out").unwrap(), 0);
+        sim.set("address", 5).unwrap();
+        sim.eval().unwrap();
+        assert_eq!(sim.get("out").unwrap(), 1234);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |source: &str| library(&[source]).build("Foo").err().expect(source).to_string();
+        assert_eq!(error("CHIP Foo { IN a; OUT out; PARTS:\n  Nor(a=a, b=a, out=out); }"), "Foo.hdl:2:3: unknown chip 'Nor'");
+        assert_eq!(error("CHIP Foo { IN a; OUT out; PARTS:\n  Not(a=a, out=out); }"), "Foo.hdl:2:7: 'Not' has no pin 'a'");
+        assert_eq!(error("CHIP Foo { IN a[2]; OUT out; PARTS:\n  Not(in=a, out=out); }"), "Foo.hdl:2:7: width mismatch: 2 bits to 1 bits");
+        assert_eq!(error("CHIP Foo { IN a; OUT out; PARTS:\n  Not(in=x, out=out); }"), "Foo.hdl:2:10: undefined pin 'x'");
+        assert_eq!(error("CHIP Foo { IN a; OUT out; PARTS:\n  Not(in=a, out=out);\n  Not(in=out, out=x); }"), "Foo.hdl:3:10: cannot read the output pin 'out'");
+        assert_eq!(error("CHIP Foo { IN a; OUT out; PARTS:\n  Not(in=a, out=a); }"), "Foo.hdl:2:17: cannot write to the input pin 'a'");
+        assert_eq!(error("CHIP Foo { IN a; OUT out; PARTS:\n  Not(in=a[1], out=out); }"), "Foo.hdl:2:10: sub bus index 1 of 'a' is out of the width 1");
+        assert_eq!(error("CHIP Foo { IN a; OUT out; PARTS:\n  Foo(a=a, out=out); }"), "Foo.hdl: too deep nesting of chips; the definition may be recursive");
+        assert_eq!(error("CHIP Foo { IN a; OUT out; PARTS:\n  Not(in=x, out=x, out=out); }"), "Foo.hdl: signals do not settle; the chip may have a combinational loop");
+    }
+}
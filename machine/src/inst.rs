@@ -1,5 +1,6 @@
 // Machine Language Specification
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Computation {
     Zero,               // 0
     One,                // 1
@@ -19,6 +20,25 @@ pub enum Computation {
     XMinusD(bool),      // A - D or M - D
     DAndX(bool),        // D & A or D & M
     DOrX(bool),         // D | A or D | M
+    #[cfg(feature = "extended")]
+    ShiftLeftD,         // D<<
+    #[cfg(feature = "extended")]
+    ShiftLeftX(bool),   // A<< or M<<
+    #[cfg(feature = "extended")]
+    ShiftRightD,        // D>>
+    #[cfg(feature = "extended")]
+    ShiftRightX(bool),  // A>> or M>>
+}
+
+// the shift instructions of the extended instruction set are '101a cccc ccdd djjj',
+// while the C-instructions are '1xxa cccc ccdd djjj' with xx != 01 in the extended one
+// (only the shifts are implemented; there are no other extended ALU operations yet)
+#[cfg(feature = "extended")]
+impl Computation {
+    pub fn is_shift(&self) -> bool {
+        use Computation::*;
+        matches!(self, ShiftLeftD | ShiftLeftX(_) | ShiftRightD | ShiftRightX(_))
+    }
 }
 
 pub mod dest {
@@ -27,7 +47,7 @@ pub mod dest {
     pub static M: u8 = 0b001;
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Jump {
     Null,   // No jump
     JGT,    // if out >  0 jump
@@ -39,6 +59,7 @@ pub enum Jump {
     JMP     // jump
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Instruction {
     AInstruction(i16),
     CInstruction(Computation, u8, Jump)
@@ -46,6 +67,29 @@ pub enum Instruction {
 
 pub use Instruction::*;
 
+// a C-instruction whose comp field is not defined in the specification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeError {
+    pub instruction: i16,
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let bits = self.instruction as u16;
+        write!(f, "invalid instruction {:016b}: undefined computation (a = {}, c1..c6 = {:06b})", bits, (bits >> 12) & 1, (bits >> 6) & 0b111111)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+// an instruction in the assembly language, or '.word 0x....' if it cannot be decoded
+pub fn disassemble(word: i16) -> String {
+    match Instruction::decode(word) {
+        Ok(instruction) => instruction.to_string(),
+        Err(_) => format!(".word 0x{:04x}", word as u16)
+    }
+}
+
 impl Instruction {
     pub fn encode(&self) -> i16 {
         match self {
@@ -55,7 +99,9 @@ impl Instruction {
             },
             CInstruction(comp, dest, jump) => {
                 use Computation::*;
-                let mut bits = 1 << 15; // C-instruction's MSB = 1
+                #[cfg(feature = "extended")]
+                let self_comp = *comp;
+                let mut bits = (0b111u16 << 13) as i16;   // C-instruction's MSB = 1, and 2 unused bits = 1
                 let (comp, a) = match comp {
                     Zero        => (0b101010, false),
                     One         => (0b111111, false),
@@ -75,7 +121,17 @@ impl Instruction {
                     XMinusD(a)  => (0b000111, *a),
                     DAndX(a)    => (0b000000, *a),
                     DOrX(a)     => (0b010101, *a),
+                    #[cfg(feature = "extended")]
+                    ShiftLeftD      => (0b110000, false),
+                    #[cfg(feature = "extended")]
+                    ShiftLeftX(a)   => (0b100000, *a),
+                    #[cfg(feature = "extended")]
+                    ShiftRightD     => (0b010000, false),
+                    #[cfg(feature = "extended")]
+                    ShiftRightX(a)  => (0b000000, *a),
                 };
+                #[cfg(feature = "extended")]
+                if self_comp.is_shift() { bits &= !(1 << 14); }
                 bits |= comp << 6;
                 if a { bits |= 1 << 12; }
                 bits |= (*dest as i16) << 3;
@@ -93,11 +149,19 @@ impl Instruction {
             }
         }
     }
-    pub fn decode(instruction: i16) -> Self {
-        if instruction >= 0 { AInstruction(instruction) } else {
+    pub fn decode(instruction: i16) -> Result<Self, DecodeError> {
+        Self::try_decode(instruction).ok_or(DecodeError{ instruction })
+    }
+    // None if the comp field of a C-instruction is not defined in the specification
+    pub fn try_decode(instruction: i16) -> Option<Self> {
+        if instruction >= 0 { Some(AInstruction(instruction)) } else {
             use Computation::*;
             let a = instruction & (1 << 12) != 0;
-            let comp = match (instruction >> 6) & 0b111111 {
+            #[cfg(feature = "extended")]
+            let extended = instruction & 0x6000 == 0x2000;
+            #[cfg(not(feature = "extended"))]
+            let extended = false;
+            let comp = if extended { Self::decode_shift(instruction)? } else { match (instruction >> 6) & 0b111111 {
                 0b101010 => Zero,
                 0b111111 => One,
                 0b111010 => MinusOne,
@@ -116,8 +180,8 @@ impl Instruction {
                 0b000111 => XMinusD(a),
                 0b000000 => DAndX(a),
                 0b010101 => DOrX(a),
-                _ => panic!("invalid instruction")
-            };
+                _ => return None
+            } };
             let jump = match instruction & 0b111 {
                 0b000 => Jump::Null,
                 0b001 => Jump::JGT,
@@ -130,9 +194,25 @@ impl Instruction {
                 _ => panic!("invalid instruction")
             };
             let dest = (instruction >> 3) & 0b111;
-            CInstruction(comp, dest as u8, jump)
+            Some(CInstruction(comp, dest as u8, jump))
+        }
+    }
+    #[cfg(feature = "extended")]
+    fn decode_shift(instruction: i16) -> Option<Computation> {
+        use Computation::*;
+        let a = instruction & (1 << 12) != 0;
+        match ((instruction >> 6) & 0b111111, a) {
+            (0b110000, false) => Some(ShiftLeftD),
+            (0b100000, a) => Some(ShiftLeftX(a)),
+            (0b010000, false) => Some(ShiftRightD),
+            (0b000000, a) => Some(ShiftRightX(a)),
+            _ => None
         }
     }
+    #[cfg(not(feature = "extended"))]
+    fn decode_shift(_: i16) -> Option<Computation> {
+        None
+    }
 }
 
 impl std::fmt::Display for Instruction {
@@ -162,7 +242,15 @@ impl std::fmt::Display for Instruction {
                     DMinusX(a)  => if *a { "D-M" } else { "D-A" },
                     XMinusD(a)  => if *a { "M-D" } else { "A-D" },
                     DAndX(a)    => if *a { "D&M" } else { "D&A" },
-                    DOrX(a)     => if *a { "D|M" } else { "D|A" }
+                    DOrX(a)     => if *a { "D|M" } else { "D|A" },
+                    #[cfg(feature = "extended")]
+                    ShiftLeftD      => "D<<",
+                    #[cfg(feature = "extended")]
+                    ShiftLeftX(a)   => if *a { "M<<" } else { "A<<" },
+                    #[cfg(feature = "extended")]
+                    ShiftRightD     => "D>>",
+                    #[cfg(feature = "extended")]
+                    ShiftRightX(a)  => if *a { "M>>" } else { "A>>" },
                 };
                 let dest = match dest {
                     0b000 => None,
@@ -204,7 +292,7 @@ mod tests {
 
     fn assert_instruction(inst: Instruction) {
         let a = inst.encode();
-        let b = Instruction::decode(a).encode();
+        let b = Instruction::decode(a).unwrap().encode();
         assert_eq!(a, b);
     }
 
@@ -215,4 +303,38 @@ mod tests {
         assert_instruction(CInstruction(Computation::One, dest::A, Jump::Null));
         assert_instruction(CInstruction(Computation::DPlusX(true), dest::M, Jump::JGT));
     }
+
+    #[test]
+    fn test_encoding() {
+        // the unused bits of C-instructions are 1, as the official assembler writes them
+        assert_eq!(CInstruction(Computation::X(false), dest::D, Jump::Null).encode(), 0b1110_1100_0001_0000u16 as i16);     // D=A
+        assert_eq!(CInstruction(Computation::DPlusX(true), dest::M, Jump::JGT).encode(), 0b1111_0000_1000_1001u16 as i16); // M=D+M;JGT
+        assert_eq!(CInstruction(Computation::Zero, 0, Jump::JMP).encode(), 0b1110_1010_1000_0111u16 as i16);               // 0;JMP
+        assert_eq!(AInstruction(21).encode(), 21);
+    }
+
+    #[test]
+    fn test_invalid_instruction() {
+        let word = 0b1110_1110_0000_0000u16 as i16;    // comp = 0111000
+        assert_eq!(Instruction::decode(word), Err(DecodeError{ instruction: word }));
+        assert_eq!(DecodeError{ instruction: word }.to_string(),
+            "invalid instruction 1110111000000000: undefined computation (a = 0, c1..c6 = 111000)");
+        assert_eq!(disassemble(word), ".word 0xee00");
+        assert_eq!(disassemble(CInstruction(Computation::One, dest::D, Jump::JMP).encode()), "D=1;JMP");
+    }
+
+    #[cfg(feature = "extended")]
+    #[test]
+    fn test_shifts() {
+        use Computation::*;
+        for &(comp, text) in &[(ShiftLeftD, "D<<"), (ShiftLeftX(false), "A<<"), (ShiftLeftX(true), "M<<"),
+                                (ShiftRightD, "D>>"), (ShiftRightX(false), "A>>"), (ShiftRightX(true), "M>>")] {
+            let inst = CInstruction(comp, dest::D, Jump::Null);
+            assert_eq!(inst.encode() as u16 & 0xe000, 0xa000);
+            assert_eq!(Instruction::decode(inst.encode()), Ok(inst));
+            assert_eq!(inst.to_string(), format!("D={}", text));
+        }
+        // D shifts with the a-bit are not defined
+        assert_eq!(Instruction::try_decode(CInstruction(ShiftLeftD, 0, Jump::Null).encode() | (1 << 12)), None);
+    }
 }
\ No newline at end of file
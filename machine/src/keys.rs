@@ -0,0 +1,177 @@
+// key codes of the Hack keyboard: the printable ASCII characters as they are, and the special keys below
+use crate::Computer;
+
+pub const NEWLINE: i16 = 128;
+pub const BACKSPACE: i16 = 129;
+pub const LEFT: i16 = 130;
+pub const UP: i16 = 131;
+pub const RIGHT: i16 = 132;
+pub const DOWN: i16 = 133;
+pub const HOME: i16 = 134;
+pub const END: i16 = 135;
+pub const PAGE_UP: i16 = 136;
+pub const PAGE_DOWN: i16 = 137;
+pub const INSERT: i16 = 138;
+pub const DELETE: i16 = 139;
+pub const ESC: i16 = 140;
+
+// F1 to F12
+pub fn function_key(n: i16) -> i16 {
+    assert!((1 ..= 12).contains(&n));
+    140 + n
+}
+
+// code of a printable ASCII character
+pub fn char_code(c: char) -> Option<i16> {
+    if (' ' ..= '~').contains(&c) { Some(c as i16) } else { None }
+}
+
+// a key by name ('newline', 'left', 'f1', 'space' etc.) or a printable character
+pub fn from_name(name: &str) -> Option<i16> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return char_code(c);
+    }
+    let code = match name.to_lowercase().as_str() {
+        "newline" | "enter" => NEWLINE,
+        "backspace" => BACKSPACE,
+        "left" => LEFT,
+        "up" => UP,
+        "right" => RIGHT,
+        "down" => DOWN,
+        "home" => HOME,
+        "end" => END,
+        "pageup" => PAGE_UP,
+        "pagedown" => PAGE_DOWN,
+        "insert" => INSERT,
+        "delete" => DELETE,
+        "esc" | "escape" => ESC,
+        "space" => ' ' as i16,
+        name => match name.strip_prefix('f').and_then(|n| n.parse::<i16>().ok()) {
+            Some(n) if (1 ..= 12).contains(&n) => function_key(n),
+            _ => return None
+        }
+    };
+    Some(code)
+}
+
+// keys held down, identified by e.g. the scan codes. KBD is the key pressed last among them, or 0.
+#[derive(Debug, Clone, Default)]
+pub struct HeldKeys {
+    keys: Vec<(u32, i16)>,  // (id, code) in order of press
+}
+
+impl HeldKeys {
+    pub fn press(&mut self, id: u32, code: i16) {
+        self.keys.retain(|&(held, _)| held != id);   // auto-repeat
+        self.keys.push((id, code));
+    }
+    pub fn release(&mut self, id: u32) {
+        self.keys.retain(|&(held, _)| held != id);
+    }
+    pub fn code(&self) -> i16 {
+        self.keys.last().map_or(0, |&(_, code)| code)
+    }
+}
+
+// keys to input at given cycles, for running programs without a keyboard. each line is
+//
+//   CYCLE KEY      (e.g. '1000 A' or '2000 newline': the key is held from the cycle)
+//   CYCLE release  (no key is held from the cycle)
+//
+// and '//' starts a comment.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyScript {
+    events: Vec<(u64, i16)>,    // (cycle, code) sorted by cycle
+}
+
+impl KeyScript {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut events = Vec::new();
+        for (i, line) in source.lines().enumerate() {
+            let line = line.split("//").next().unwrap_or_default().trim();
+            if line.is_empty() { continue; }
+            let error = |message: String| Err(format!("line {}: {}", i + 1, message));
+            let (cycle, key) = match line.find(char::is_whitespace) {
+                Some(j) => (&line[.. j], line[j ..].trim()),
+                None => return error(format!("expected 'CYCLE KEY', found '{}'", line))
+            };
+            let cycle = match cycle.parse::<u64>() {
+                Ok(cycle) => cycle,
+                Err(_) => return error(format!("invalid cycle '{}'", cycle))
+            };
+            let code = match key {
+                "release" => 0,
+                key => match from_name(key) {
+                    Some(code) => code,
+                    None => return error(format!("unknown key '{}'", key))
+                }
+            };
+            events.push((cycle, code));
+        }
+        events.sort_by_key(|&(cycle, _)| cycle);   // stable, so the later line wins at the same cycle
+        Ok(Self{ events })
+    }
+
+    // the key code which is set at the cycle, if any
+    pub fn key_at(&self, cycle: u64) -> Option<i16> {
+        let start = self.events.partition_point(|&(c, _)| c < cycle);
+        self.events[start ..].iter().take_while(|&&(c, _)| c == cycle).last().map(|&(_, code)| code)
+    }
+
+    // input the key of the cycle to the computer. call this before each clock
+    pub fn apply(&self, cycle: u64, computer: &mut dyn Computer) {
+        if let Some(code) = self.key_at(cycle) {
+            computer.keyboard_input(code);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert_eq!(from_name("A"), Some(65));
+        assert_eq!(from_name("~"), Some(126));
+        assert_eq!(from_name("newline"), Some(128));
+        assert_eq!(from_name("Down"), Some(133));
+        assert_eq!(from_name("esc"), Some(140));
+        assert_eq!(from_name("F1"), Some(141));
+        assert_eq!(from_name("f12"), Some(152));
+        assert_eq!(from_name("f13"), None);
+        assert_eq!(from_name("\t"), None);
+    }
+
+    #[test]
+    fn test_held_keys() {
+        let mut keys = HeldKeys::default();
+        keys.press(30, 'a' as i16);
+        keys.press(42, LEFT);
+        assert_eq!(keys.code(), LEFT);
+        keys.release(42);
+        assert_eq!(keys.code(), 'a' as i16);
+        keys.press(30, 'a' as i16);
+        keys.release(30);
+        assert_eq!(keys.code(), 0);
+    }
+
+    #[test]
+    fn test_script() {
+        let script = KeyScript::parse("// type 'H' and enter\n10 H\n20 release\n30 newline  // enter\n20 space\n").unwrap();
+        assert_eq!(script.key_at(10), Some('H' as i16));
+        assert_eq!(script.key_at(11), None);
+        assert_eq!(script.key_at(20), Some(' ' as i16));
+        assert_eq!(script.key_at(30), Some(NEWLINE));
+        assert_eq!(KeyScript::parse("10\n"), Err("line 1: expected 'CYCLE KEY', found '10'".to_string()));
+        assert_eq!(KeyScript::parse("\nx A\n"), Err("line 2: invalid cycle 'x'".to_string()));
+        assert_eq!(KeyScript::parse("1 ctrl\n"), Err("line 1: unknown key 'ctrl'".to_string()));
+
+        let mut emulator = crate::Emulator::new(&[]);
+        script.apply(10, &mut emulator);
+        assert_eq!(emulator.read_memory(0x6000), 'H' as i16);
+        script.apply(11, &mut emulator);
+        assert_eq!(emulator.read_memory(0x6000), 'H' as i16);
+    }
+}
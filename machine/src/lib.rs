@@ -4,14 +4,88 @@ mod adder;
 mod alu;
 mod ram;
 mod cpu;
+#[cfg(feature = "extended")]
+mod shifter;
 mod blackbox;
+mod emulator;
 pub mod inst;
+pub mod difftest;
+pub mod debugger;
+pub mod hdl;
+pub mod analysis;
+pub mod vcd;
+pub mod hack_format;
+pub mod screen_image;
+pub mod screen_recorder;
+pub mod keys;
 
 use given::*;
 use gate::*;
 use ram::*;
 use cpu::*;
 use blackbox::*;
+pub use emulator::Emulator;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuRegister { A, D, PC }
+
+// snapshot of the CPU for debuggers and test harnesses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MachineState {
+    pub a: i16,
+    pub d: i16,
+    pub pc: i16,
+    pub instruction: Option<inst::Instruction>, // instruction at PC; None if it is not a valid instruction
+    pub cycle: u64,                             // number of clocks since the machine was created
+}
+
+impl std::fmt::Display for MachineState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "cycle {}: PC={} A={} D={}", self.cycle, self.pc, self.a, self.d)?;
+        match &self.instruction {
+            Some(instruction) => write!(f, " next: {}", instruction),
+            None => write!(f, " next: (invalid instruction)")
+        }
+    }
+}
+
+// common interface of the gate-level Machine and the word-level Emulator
+pub trait Computer {
+    fn clock(&mut self, reset: bool);
+    fn pc(&self) -> i16;
+    fn next_instruction(&self) -> i16;
+    fn is_terminated(&self) -> bool;
+    fn read_memory(&self, address: i16) -> i16;
+    fn write_memory(&mut self, address: i16, value: i16);  // writes to the keyboard are ignored
+    fn set_register(&mut self, register: CpuRegister, value: i16);
+    fn state(&self) -> MachineState;
+    fn screen_image(&self) -> &[i16; 32 * 256];
+    fn keyboard_input(&mut self, key: i16);
+    // finish the trace started by Machine::start_trace(), and return the first error of writing it.
+    // the computers without the trace have nothing to do.
+    fn finish_trace(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+    fn print_status_header(&self) {
+        println!("{:4}: [{:4}] {:5}, [{:4}] {:5}, [{:4}] {:5}, [{:4}] {:5}, [{:4}] {:5}",
+            "PC",
+            "SP", "*SP",
+            "LCL", "*LCL",
+            "ARG", "*ARG",
+            "THIS", "*THIS",
+            "THAT", "*THAT");
+    }
+    fn print_status(&self) {
+        let (sp, lcl, arg, this, that) = (self.read_memory(0), self.read_memory(1), self.read_memory(2), self.read_memory(3), self.read_memory(4));
+        print!("{:4}: [{:>04x}] {:>5}, [{:04x}] {:>5}, [{:04x}] {:>5}, [{:04x}] {:>5}, [{:04x}] {:>5}\r",
+            self.pc(),
+            sp, self.read_memory(sp),
+            lcl, self.read_memory(lcl),
+            arg, self.read_memory(arg),
+            this, self.read_memory(this),
+            that, self.read_memory(that));
+    }
+}
 
 struct Memory {
     ram: RAM16K,
@@ -61,10 +135,22 @@ impl Memory {
     }
 }
 
+// what the Machine does when PC reaches a C-instruction which cannot be decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidInstructionPolicy {
+    Execute,                // execute it as the hardware would (the default)
+    Halt,                   // stop clocking; is_terminated() becomes true until reset
+    Trap{ handler: i16 },   // jump to the handler with the address of the instruction in A
+}
+
 pub struct Machine {
     instruction_memory: Box<ROM32K>,
     data_memory: Box<Memory>,
-    cpu: Cpu
+    cpu: Cpu,
+    cycle: u64,
+    tracer: Option<vcd::CpuTracer>,
+    policy: InvalidInstructionPolicy,
+    halted: bool,
 }
 
 impl Machine {
@@ -72,52 +158,117 @@ impl Machine {
         Self{
             instruction_memory: Box::new(ROM32K::new(instructions)),
             data_memory: Box::new(Memory::new()),
-            cpu: Cpu::new()
+            cpu: Cpu::new(),
+            cycle: 0,
+            tracer: None,
+            policy: InvalidInstructionPolicy::Execute,
+            halted: false,
         }
     }
-    pub fn clock(&mut self, reset: bool) {
+    pub fn set_invalid_instruction_policy(&mut self, policy: InvalidInstructionPolicy) {
+        self.policy = policy;
+    }
+    // true if the machine is halted by an invalid instruction with InvalidInstructionPolicy::Halt
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+    // record the signals of the CPU of each clock into a VCD file, until finish_trace() is called
+    pub fn start_trace(&mut self, out: Box<dyn std::io::Write>) -> std::io::Result<()> {
+        self.tracer = Some(vcd::CpuTracer::new(out)?);
+        Ok(())
+    }
+}
+
+impl Machine {
+    // send a clock signal, and return the address and the value written to the data memory if any
+    fn clock_and_trace_write(&mut self, reset: bool) -> Option<(i16, i16)> {
+        if reset {
+            self.halted = false;
+        } else if self.policy != InvalidInstructionPolicy::Execute && inst::Instruction::decode(self.next_instruction()).is_err() {
+            match self.policy {
+                InvalidInstructionPolicy::Trap{ handler } => {
+                    self.cpu.set_a(self.cpu.pc());
+                    self.cpu.set_pc(debug::int2word(handler));
+                    self.cycle += 1;
+                },
+                _ => self.halted = true
+            }
+            return None;
+        }
         let cpu_input = CpuInput{
             instruction: self.instruction_memory.out(self.cpu.pc()),
             inM: self.data_memory.out(self.cpu.addressM()),
-            reset: reset
+            reset
         };
         let cpu_out = self.cpu.out(cpu_input);
+        if let Some(tracer) = &mut self.tracer {
+            tracer.record(self.cycle, &self.cpu, cpu_input);
+        }
+        let write = if cpu_out.writeM {
+            Some((debug::word2int(self.cpu.addressM()), debug::word2int(cpu_out.outM)))
+        } else { None };
         self.data_memory.clock(self.cpu.addressM(), cpu_out.outM, cpu_out.writeM);
         self.cpu.clock(cpu_input);
+        self.cycle += 1;
+        write
+    }
+}
+
+impl difftest::Target for Machine {
+    fn step(&mut self) -> difftest::Snapshot {
+        let write = self.clock_and_trace_write(false);
+        let state = self.state();
+        difftest::Snapshot{ pc: state.pc, a: state.a, d: state.d, write }
+    }
+}
+
+impl Computer for Machine {
+    fn clock(&mut self, reset: bool) {
+        self.clock_and_trace_write(reset);
+    }
+    fn finish_trace(&mut self) -> std::io::Result<()> {
+        match self.tracer.take() {
+            Some(tracer) => tracer.finish(),
+            None => Ok(())
+        }
     }
-    pub fn next_instruction(&self) -> i16 {
+    fn pc(&self) -> i16 {
+        debug::word2int(self.cpu.pc())
+    }
+    fn next_instruction(&self) -> i16 {
         debug::word2int(self.instruction_memory.out(self.cpu.pc()))
     }
-    pub fn is_terminated(&self) -> bool {
-        self.instruction_memory.is_terminated(self.cpu.pc())
+    fn is_terminated(&self) -> bool {
+        self.halted || self.instruction_memory.is_terminated(self.cpu.pc())
     }
-    pub fn read_memory(&self, address: i16) -> i16 {
+    fn read_memory(&self, address: i16) -> i16 {
         debug::word2int(self.data_memory.out(debug::int2word(address)))
     }
-    pub fn screen_image(&self) -> &[i16; 32 * 256] {
-        self.data_memory.screen.raw_image()
+    fn write_memory(&mut self, address: i16, value: i16) {
+        self.data_memory.clock(debug::int2word(address), debug::int2word(value), true);
     }
-    pub fn keyboard_input(&mut self, key: i16) {
-        self.data_memory.keyboard.input(key);
+    fn set_register(&mut self, register: CpuRegister, value: i16) {
+        let value = debug::int2word(value);
+        match register {
+            CpuRegister::A => self.cpu.set_a(value),
+            CpuRegister::D => self.cpu.set_d(value),
+            CpuRegister::PC => self.cpu.set_pc(value),
+        }
     }
-    pub fn print_status_header(&self) {
-        println!("{:4}: [{:4}] {:5}, [{:4}] {:5}, [{:4}] {:5}, [{:4}] {:5}, [{:4}] {:5}",
-            "PC",
-            "SP", "*SP",
-            "LCL", "*LCL",
-            "ARG", "*ARG",
-            "THIS", "*THIS",
-            "THAT", "*THAT");
+    fn state(&self) -> MachineState {
+        MachineState{
+            a: debug::word2int(self.cpu.addressM()),
+            d: debug::word2int(self.cpu.d()),
+            pc: self.pc(),
+            instruction: inst::Instruction::try_decode(self.next_instruction()),
+            cycle: self.cycle
+        }
     }
-    pub fn print_status(&self) {
-        let (sp, lcl, arg, this, that) = (self.read_memory(0), self.read_memory(1), self.read_memory(2), self.read_memory(3), self.read_memory(4));
-        print!("{:4}: [{:>04x}] {:>5}, [{:04x}] {:>5}, [{:04x}] {:>5}, [{:04x}] {:>5}, [{:04x}] {:>5}\r",
-            debug::word2int(self.cpu.pc()),
-            sp, self.read_memory(sp),
-            lcl, self.read_memory(lcl),
-            arg, self.read_memory(arg),
-            this, self.read_memory(this),
-            that, self.read_memory(that));
+    fn screen_image(&self) -> &[i16; 32 * 256] {
+        self.data_memory.screen.raw_image()
+    }
+    fn keyboard_input(&mut self, key: i16) {
+        self.data_memory.keyboard.input(key);
     }
 }
 
@@ -193,4 +344,68 @@ mod tests {
         ];
         assert_eq!(run_machine(&asm, asm.len() * 10, sum), 10 * (10 + 1) / 2);
     }
+
+    #[test]
+    fn test_state_and_poke() {
+        use Computation::*;
+        let asm = [
+            /* D=D+M    */  CInstruction(DPlusX(true), dest::D, Jump::Null),
+            /* @SCREEN  */  AInstruction(0x4000),
+            /* M=D      */  CInstruction(D, dest::M, Jump::Null),
+        ];
+        let bin = asm.iter().map(|inst| inst.encode()).collect::<Vec<_>>();
+        let mut machine = Machine::new(&bin);
+        machine.set_register(CpuRegister::A, 100);
+        machine.set_register(CpuRegister::D, 23);
+        machine.write_memory(100, 1000);
+        assert_eq!(machine.read_memory(100), 1000);
+        assert_eq!(machine.state(), MachineState{ a: 100, d: 23, pc: 0, instruction: Some(asm[0]), cycle: 0 });
+
+        machine.clock(false);
+        assert_eq!(machine.state(), MachineState{ a: 100, d: 1023, pc: 1, instruction: Some(asm[1]), cycle: 1 });
+
+        // jump back to the first instruction
+        machine.set_register(CpuRegister::PC, 0);
+        machine.clock(false);
+        assert_eq!(machine.state().d, 2023);
+        machine.clock(false);
+        machine.clock(false);
+        assert_eq!(machine.screen_image()[0], 2023);
+        assert_eq!(machine.state().cycle, 4);
+        assert_eq!(machine.state().instruction, Some(AInstruction(0)));
+    }
+
+    #[test]
+    fn test_invalid_instruction_policy() {
+        use Computation::*;
+        let invalid = 0b1110_1110_0000_0000u16 as i16;
+        let bin = [
+            /* @7       */  AInstruction(7).encode(),
+            /* D=A      */  CInstruction(X(false), dest::D, Jump::Null).encode(),
+            /* (bad)    */  invalid,
+            /* @100     */  AInstruction(100).encode(),
+            /* (TRAP)   */  // address is 4
+            /* D=A      */  CInstruction(X(false), dest::D, Jump::Null).encode(),
+        ];
+
+        // executed as the hardware would: the comp bits 111000 compute 0, which is stored nowhere
+        let mut machine = Machine::new(&bin);
+        for _ in 0 .. 4 { machine.clock(false); }
+        assert_eq!(machine.state().a, 100);
+
+        let mut machine = Machine::new(&bin);
+        machine.set_invalid_instruction_policy(InvalidInstructionPolicy::Halt);
+        while !machine.is_terminated() { machine.clock(false); }
+        assert!(machine.is_halted());
+        assert_eq!((machine.pc(), machine.state().cycle, machine.state().instruction), (2, 2, None));
+        machine.clock(true);
+        assert!(!machine.is_halted());
+        assert_eq!(machine.pc(), 0);
+
+        let mut machine = Machine::new(&bin);
+        machine.set_invalid_instruction_policy(InvalidInstructionPolicy::Trap{ handler: 4 });
+        for _ in 0 .. 4 { machine.clock(false); }
+        assert!(!machine.is_halted());
+        assert_eq!(machine.state(), MachineState{ a: 2, d: 2, pc: 5, instruction: Some(AInstruction(0)), cycle: 4 });
+    }
 }
\ No newline at end of file
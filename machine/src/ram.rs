@@ -3,22 +3,22 @@ use crate::gate::*;
 use crate::adder::*;
 
 // single bit register
-pub struct BitRegister { flipflop: Flipflop }
-impl BitRegister {
+pub struct BitRegister<S: Signal = bool> { flipflop: Flipflop<S> }
+impl<S: Signal> BitRegister<S> {
     pub fn new() -> Self {
         BitRegister{ flipflop: Flipflop::new() }
     }
-    pub fn out(&self) -> bool {
+    pub fn out(&self) -> S {
         self.flipflop.out()
     }
-    pub fn clock(&mut self, input: bool, load: bool)  {
+    pub fn clock(&mut self, input: S, load: S)  {
         self.flipflop.clock(mux(self.out(), input, load));
     }
 }
 
 // 16bit register
-pub struct Register { bits: [BitRegister; 16] }
-impl Register {
+pub struct Register<S: Signal = bool> { bits: [BitRegister<S>; 16] }
+impl<S: Signal> Register<S> {
     pub fn new() -> Self {
         Register{ bits: [
             BitRegister::new(), BitRegister::new(), BitRegister::new(), BitRegister::new(),
@@ -27,14 +27,14 @@ impl Register {
             BitRegister::new(), BitRegister::new(), BitRegister::new(), BitRegister::new(),
         ] }
     }
-    pub fn out(&self) -> Word {
+    pub fn out(&self) -> Word<S> {
         [ self.bits[ 0].out(), self.bits[ 1].out(), self.bits[ 2].out(), self.bits[ 3].out(),
           self.bits[ 4].out(), self.bits[ 5].out(), self.bits[ 6].out(), self.bits[ 7].out(),
           self.bits[ 8].out(), self.bits[ 9].out(), self.bits[10].out(), self.bits[11].out(),
           self.bits[12].out(), self.bits[13].out(), self.bits[14].out(), self.bits[15].out(),
         ]
     }
-    pub fn clock(&mut self, input: Word, load: bool) {
+    pub fn clock(&mut self, input: Word<S>, load: S) {
         self.bits[ 0].clock(input[ 0], load);
         self.bits[ 1].clock(input[ 1], load);
         self.bits[ 2].clock(input[ 2], load);
@@ -54,21 +54,21 @@ impl Register {
     }
 }
 
-pub struct RAM8 { registers: [Register; 8] }
-impl RAM8 {
+pub struct RAM8<S: Signal = bool> { registers: [Register<S>; 8] }
+impl<S: Signal> RAM8<S> {
     pub fn new() -> Self {
         RAM8 { registers: [
             Register::new(), Register::new(), Register::new(), Register::new(),
             Register::new(), Register::new(), Register::new(), Register::new(),
         ]}
     }
-    pub fn out(&self, address: [bool; 3]) -> Word {
+    pub fn out(&self, address: [S; 3]) -> Word<S> {
         mux8way16(
             self.registers[0].out(), self.registers[1].out(), self.registers[2].out(), self.registers[3].out(),
             self.registers[4].out(), self.registers[5].out(), self.registers[6].out(), self.registers[7].out(),
             address)
     }
-    pub fn clock(&mut self, address: [bool; 3], input: Word, load: bool) {
+    pub fn clock(&mut self, address: [S; 3], input: Word<S>, load: S) {
         let load = dmux8way(load, address);
         self.registers[0].clock(input, load[0]);
         self.registers[1].clock(input, load[1]);
@@ -81,15 +81,15 @@ impl RAM8 {
     }
 }
 
-pub struct RAM64 { rams: [RAM8; 8] }
-impl RAM64 {
+pub struct RAM64<S: Signal = bool> { rams: [RAM8<S>; 8] }
+impl<S: Signal> RAM64<S> {
     pub fn new() -> Self {
         Self { rams: [
             RAM8::new(), RAM8::new(), RAM8::new(), RAM8::new(),
             RAM8::new(), RAM8::new(), RAM8::new(), RAM8::new(),
         ]}
     }
-    pub fn out(&self, address: [bool; 6]) -> Word {
+    pub fn out(&self, address: [S; 6]) -> Word<S> {
         let lo = [address[0], address[1], address[2]];
         let hi = [address[3], address[4], address[5]];
         mux8way16(
@@ -97,7 +97,7 @@ impl RAM64 {
             self.rams[4].out(lo), self.rams[5].out(lo), self.rams[6].out(lo), self.rams[7].out(lo),
             hi)
     }
-    pub fn clock(&mut self, address: [bool; 6], input: Word, load: bool) {
+    pub fn clock(&mut self, address: [S; 6], input: Word<S>, load: S) {
         let lo = [address[0], address[1], address[2]];
         let hi = [address[3], address[4], address[5]];
         let load = dmux8way(load, hi);
@@ -112,15 +112,15 @@ impl RAM64 {
     }
 }
 
-pub struct RAM512 { rams: Box<[RAM64; 8]> }
-impl RAM512 {
+pub struct RAM512<S: Signal = bool> { rams: Box<[RAM64<S>; 8]> }
+impl<S: Signal> RAM512<S> {
     pub fn new() -> Self {
         Self { rams: Box::new([
             RAM64::new(), RAM64::new(), RAM64::new(), RAM64::new(),
             RAM64::new(), RAM64::new(), RAM64::new(), RAM64::new(),
         ]) }
     }
-    pub fn out(&self, address: [bool; 9]) -> Word {
+    pub fn out(&self, address: [S; 9]) -> Word<S> {
         let lo = [address[0], address[1], address[2], address[3], address[4], address[5]];
         let hi = [address[6], address[7], address[8]];
         mux8way16(
@@ -128,7 +128,7 @@ impl RAM512 {
             self.rams[4].out(lo), self.rams[5].out(lo), self.rams[6].out(lo), self.rams[7].out(lo),
             hi)
     }
-    pub fn clock(&mut self, address: [bool; 9], input: Word, load: bool) {
+    pub fn clock(&mut self, address: [S; 9], input: Word<S>, load: S) {
         let lo = [address[0], address[1], address[2], address[3], address[4], address[5]];
         let hi = [address[6], address[7], address[8]];
         let load = dmux8way(load, hi);
@@ -143,15 +143,15 @@ impl RAM512 {
     }
 }
 
-pub struct RAM4K { rams: [RAM512; 8] }
-impl RAM4K {
+pub struct RAM4K<S: Signal = bool> { rams: [RAM512<S>; 8] }
+impl<S: Signal> RAM4K<S> {
     pub fn new() -> Self {
         Self { rams: [
             RAM512::new(), RAM512::new(), RAM512::new(), RAM512::new(),
             RAM512::new(), RAM512::new(), RAM512::new(), RAM512::new(),
         ] }
     }
-    pub fn out(&self, address: [bool; 12]) -> Word {
+    pub fn out(&self, address: [S; 12]) -> Word<S> {
         let lo = [
             address[0], address[1], address[2],
             address[3], address[4], address[5],
@@ -162,7 +162,7 @@ impl RAM4K {
             self.rams[4].out(lo), self.rams[5].out(lo), self.rams[6].out(lo), self.rams[7].out(lo),
             hi)
     }
-    pub fn clock(&mut self, address: [bool; 12], input: Word, load: bool) {
+    pub fn clock(&mut self, address: [S; 12], input: Word<S>, load: S) {
         let lo = [
             address[0], address[1], address[2],
             address[3], address[4], address[5],
@@ -180,13 +180,13 @@ impl RAM4K {
     }
 }
 
-pub struct RAM16K { rams: [RAM4K; 4] }
+pub struct RAM16K<S: Signal = bool> { rams: [RAM4K<S>; 4] }
 //pub struct RAM16K { rams: Vec<RAM4K> }
-impl RAM16K {
+impl<S: Signal> RAM16K<S> {
     pub fn new() -> Self {
         Self { rams: [RAM4K::new(), RAM4K::new(), RAM4K::new(), RAM4K::new()] }
     }
-    pub fn out(&self, address: [bool; 14]) -> Word {
+    pub fn out(&self, address: [S; 14]) -> Word<S> {
         let lo = [
             address[0], address[ 1], address[ 2],
             address[3], address[ 4], address[ 5],
@@ -197,7 +197,7 @@ impl RAM16K {
             self.rams[0].out(lo), self.rams[1].out(lo), self.rams[2].out(lo), self.rams[3].out(lo),
             hi)
     }
-    pub fn clock(&mut self, address: [bool; 14], input: Word, load: bool) {
+    pub fn clock(&mut self, address: [S; 14], input: Word<S>, load: S) {
         let lo = [
             address[0], address[ 1], address[ 2],
             address[3], address[ 4], address[ 5],
@@ -212,15 +212,15 @@ impl RAM16K {
     }
 }
 
-pub struct Counter { register: Register }
-impl Counter {
+pub struct Counter<S: Signal = bool> { register: Register<S> }
+impl<S: Signal> Counter<S> {
     pub fn new() -> Self { Self { register: Register::new() } }
-    pub fn out(&self) -> Word {
+    pub fn out(&self) -> Word<S> {
         self.register.out()
     }
-    pub fn clock(&mut self, input: Word, inc: bool, load: bool, reset: bool) {
+    pub fn clock(&mut self, input: Word<S>, inc: S, load: S, reset: S) {
         self.register.clock(
-            mux16(mux16(inc16(self.register.out()), input, load), [false; 16], reset),
+            mux16(mux16(inc16(self.register.out()), input, load), [S::constant(false); 16], reset),
             or(inc, or(load, reset)));
     }
 }
@@ -0,0 +1,239 @@
+// images of the screen (512 x 256 pixels, 16 pixels per word, the LSB is the leftmost pixel, 1 = black)
+use std::path::Path;
+
+pub const WIDTH: usize = 512;
+pub const HEIGHT: usize = 256;
+
+pub fn pixel(image: &[i16; 32 * 256], x: usize, y: usize) -> bool {
+    image[y * 32 + x / 16] & (1 << (x % 16)) != 0
+}
+
+// text art where a character is a block of scale x scale pixels: '#' if any of them is black
+pub fn to_ascii(image: &[i16; 32 * 256], scale: usize) -> String {
+    let mut text = String::new();
+    for y in (0 .. HEIGHT).step_by(scale) {
+        for x in (0 .. WIDTH).step_by(scale) {
+            let black = (y .. (y + scale).min(HEIGHT)).any(|y| (x .. (x + scale).min(WIDTH)).any(|x| pixel(image, x, y)));
+            text.push(if black { '#' } else { '.' });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+// binary portable bitmap (P4), where 1 is black as well
+pub fn to_pbm(image: &[i16; 32 * 256]) -> Vec<u8> {
+    let mut bytes = format!("P4\n{} {}\n", WIDTH, HEIGHT).into_bytes();
+    for &word in image.iter() {
+        // the leftmost pixel is the MSB of a byte in PBM
+        let word = (word as u16).reverse_bits();
+        bytes.extend(&word.to_be_bytes());
+    }
+    bytes
+}
+
+// read a binary portable bitmap (P4) of 512 x 256 pixels
+pub fn from_pbm(bytes: &[u8]) -> Result<Box<[i16; 32 * 256]>, String> {
+    // the header is 'P4', width and height separated by whitespaces and comments, and a whitespace
+    let mut fields = Vec::new();
+    let mut i = 0;
+    while fields.len() < 3 {
+        match bytes.get(i) {
+            Some(b'#') => while i < bytes.len() && bytes[i] != b'\n' { i += 1; },
+            Some(c) if c.is_ascii_whitespace() => i += 1,
+            Some(_) => {
+                let start = i;
+                while i < bytes.len() && !bytes[i].is_ascii_whitespace() { i += 1; }
+                fields.push(String::from_utf8_lossy(&bytes[start .. i]).into_owned());
+            },
+            None => return Err("incomplete PBM header".to_string())
+        }
+    }
+    if fields[0] != "P4" {
+        return Err(format!("not a binary PBM ('{}')", fields[0]));
+    }
+    if fields[1] != WIDTH.to_string() || fields[2] != HEIGHT.to_string() {
+        return Err(format!("the image is {} x {}, not {} x {}", fields[1], fields[2], WIDTH, HEIGHT));
+    }
+    let data = bytes.get(i + 1 ..).unwrap_or_default();
+    if data.len() != WIDTH / 8 * HEIGHT {
+        return Err(format!("expected {} bytes of pixels, found {}", WIDTH / 8 * HEIGHT, data.len()));
+    }
+    let mut image = Box::new([0; 32 * 256]);
+    for (word, bytes) in image.iter_mut().zip(data.chunks(2)) {
+        *word = u16::from_be_bytes([bytes[0], bytes[1]]).reverse_bits() as i16;
+    }
+    Ok(image)
+}
+
+// PNG of 1-bit grayscale, where 1 is white unlike the screen. the pixels are stored without compression.
+pub fn to_png(image: &[i16; 32 * 256]) -> Vec<u8> {
+    let mut rows = Vec::with_capacity(HEIGHT * (1 + WIDTH / 8));
+    for row in image.chunks(32) {
+        rows.push(0);   // filter type: none
+        for &word in row {
+            rows.extend(&(!(word as u16).reverse_bits()).to_be_bytes());
+        }
+    }
+    png(WIDTH, HEIGHT, 1, 0, &rows)
+}
+
+// PNG of RGB pixels in rows from the top
+pub fn rgb_to_png(width: usize, height: usize, pixels: &[[u8; 3]]) -> Vec<u8> {
+    assert_eq!(pixels.len(), width * height);
+    let mut rows = Vec::with_capacity(height * (1 + width * 3));
+    for row in pixels.chunks(width) {
+        rows.push(0);
+        for pixel in row {
+            rows.extend(pixel);
+        }
+    }
+    png(width, height, 8, 2, &rows)
+}
+
+// 'rows' are the scanlines, each of which starts with its filter type
+fn png(width: usize, height: usize, bit_depth: u8, color_type: u8, rows: &[u8]) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend(&(width as u32).to_be_bytes());
+    header.extend(&(height as u32).to_be_bytes());
+    header.extend(&[bit_depth, color_type, 0, 0, 0]);   // compression, filter and interlace methods
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    png_chunk(&mut png, b"IHDR", &header);
+    png_chunk(&mut png, b"IDAT", &zlib_stored(rows));
+    png_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start ..]);
+    png.extend(&crc.to_be_bytes());
+}
+
+// zlib stream of the deflate blocks which are stored as they are
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xffff).peekable();
+    while let Some(block) = blocks.next() {
+        zlib.push(if blocks.peek().is_none() { 1 } else { 0 });   // BFINAL, and BTYPE = 00 (stored)
+        let len = block.len() as u16;
+        zlib.extend(&len.to_le_bytes());
+        zlib.extend(&(!len).to_le_bytes());
+        zlib.extend(block);
+    }
+    zlib.extend(&adler32(data).to_be_bytes());
+    zlib
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0 .. 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+// write a .png or .pbm file
+pub fn write_file(path: &Path, image: &[i16; 32 * 256]) -> Result<(), String> {
+    let bytes = match path.extension().and_then(|s| s.to_str()) {
+        Some("png") => to_png(image),
+        Some("pbm") => to_pbm(image),
+        _ => return Err(format!("{}: unknown image format (expected .png or .pbm)", path.display()))
+    };
+    std::fs::write(path, bytes).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image() -> Box<[i16; 32 * 256]> {
+        let mut image = Box::new([0; 32 * 256]);
+        image[0] = 0b101;           // (0, 0) and (2, 0)
+        image[32 * 9 + 1] = -0x8000; // (31, 9)
+        image
+    }
+
+    #[test]
+    fn test_pixel() {
+        let image = image();
+        assert!(pixel(&image, 0, 0));
+        assert!(!pixel(&image, 1, 0));
+        assert!(pixel(&image, 2, 0));
+        assert!(pixel(&image, 31, 9));
+        assert!(!pixel(&image, 32, 9));
+    }
+
+    #[test]
+    fn test_ascii() {
+        let text = to_ascii(&image(), 8);
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 32);
+        assert_eq!(&lines[0][.. 5], "#....");
+        assert_eq!(&lines[1][.. 5], "...#.");
+        assert_eq!(lines[2], ".".repeat(64));
+    }
+
+    #[test]
+    fn test_pbm() {
+        let pbm = to_pbm(&image());
+        let header = b"P4\n512 256\n";
+        assert_eq!(&pbm[.. header.len()], header);
+        assert_eq!(pbm.len(), header.len() + 512 / 8 * 256);
+        assert_eq!(&pbm[header.len() ..][.. 2], &[0b1010_0000, 0]);
+        assert_eq!(pbm[header.len() + 64 * 9 + 3], 0b0000_0001);
+    }
+
+    #[test]
+    fn test_read_pbm() {
+        assert_eq!(from_pbm(&to_pbm(&image())).unwrap(), image());
+        let mut pbm = b"P4 # comment\n512\n256 ".to_vec();
+        pbm.extend(vec![0xff; 64 * 256]);
+        assert!(from_pbm(&pbm).unwrap().iter().all(|&word| word == -1));
+        assert_eq!(from_pbm(b"P1\n512 256\n"), Err("not a binary PBM ('P1')".to_string()));
+        assert_eq!(from_pbm(b"P4\n16 16\n"), Err("the image is 16 x 16, not 512 x 256".to_string()));
+        assert_eq!(from_pbm(b"P4\n512 256\n\0\0"), Err("expected 16384 bytes of pixels, found 2".to_string()));
+        assert_eq!(from_pbm(b"P4\n512"), Err("incomplete PBM header".to_string()));
+        assert!(from_pbm(b"P4\n512 256").is_err());
+    }
+
+    #[test]
+    fn test_png() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+
+        let png = to_png(&image());
+        assert_eq!(&png[.. 8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12 .. 24], b"IHDR\0\0\x02\0\0\0\x01\0");
+        assert_eq!(&png[png.len() - 12 ..], b"\0\0\0\0IEND\xae\x42\x60\x82");
+        // IDAT: zlib header, a stored block of 256 rows of (filter + 64 bytes), the first pixels
+        let idat = &png[8 + 25 ..];
+        assert_eq!(&idat[4 .. 8], b"IDAT");
+        assert_eq!(u32::from_be_bytes([idat[0], idat[1], idat[2], idat[3]]), 2 + 5 + 256 * 65 + 4);
+        assert_eq!(&idat[8 .. 15], &[0x78, 0x01, 1, 0x00, 0x41, 0xff, 0xbe]);
+        assert_eq!(&idat[15 .. 18], &[0, 0b0101_1111, 0xff]);
+    }
+
+    #[test]
+    fn test_rgb_png() {
+        let png = rgb_to_png(2, 1, &[[255, 0, 0], [0, 0, 255]]);
+        assert_eq!(&png[16 .. 29], &[0, 0, 0, 2, 0, 0, 0, 1, 8, 2, 0, 0, 0]);
+        let idat = &png[8 + 25 ..];
+        assert_eq!(&idat[8 .. 20], &[0x78, 0x01, 1, 7, 0, 0xf8, 0xff, 0, 255, 0, 0, 0]);
+    }
+}
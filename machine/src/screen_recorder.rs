@@ -0,0 +1,231 @@
+// recording of the screen as an animated GIF, or as numbered images (e.g. shot-0000.png, shot-0001.png, ...)
+use crate::screen_image::{self, WIDTH, HEIGHT};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub struct Recorder {
+    path: PathBuf,
+    delay: u16,                                 // centiseconds per frame of GIF
+    frames: Vec<(Box<[i16; 32 * 256]>, u16)>,   // frames of GIF and how long each is shown
+    count: usize,
+}
+
+impl Recorder {
+    // '.gif' records an animated GIF, and '.png' or '.pbm' numbered images
+    pub fn new(path: &Path, delay: u16) -> Result<Self, String> {
+        match path.extension().and_then(|s| s.to_str()) {
+            Some("gif") | Some("png") | Some("pbm") => Ok(Self{ path: path.to_path_buf(), delay, frames: Vec::new(), count: 0 }),
+            _ => Err(format!("{}: unknown recording format (expected .gif, .png or .pbm)", path.display()))
+        }
+    }
+
+    fn is_gif(&self) -> bool {
+        self.path.extension().and_then(|s| s.to_str()) == Some("gif")
+    }
+
+    // the path of a numbered image
+    pub fn frame_path(&self, n: usize) -> PathBuf {
+        let stem = self.path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        let extension = self.path.extension().and_then(|s| s.to_str()).unwrap_or_default();
+        self.path.with_file_name(format!("{}-{:04}.{}", stem, n, extension))
+    }
+
+    pub fn record(&mut self, image: &[i16; 32 * 256]) -> Result<(), String> {
+        if self.is_gif() {
+            // a frame same as the last one just makes it longer
+            match self.frames.last_mut() {
+                Some((last, delay)) if **last == *image => *delay = delay.saturating_add(self.delay),
+                _ => self.frames.push((Box::new(*image), self.delay))
+            }
+        } else {
+            screen_image::write_file(&self.frame_path(self.count), image)?;
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    // the number of frames recorded
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    // write the GIF, and return the number of frames recorded
+    pub fn finish(self) -> Result<usize, String> {
+        if self.is_gif() {
+            std::fs::write(&self.path, to_gif(&self.frames)).map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        }
+        Ok(self.count)
+    }
+}
+
+// animated GIF which loops forever. each frame is shown for the centiseconds.
+pub fn to_gif(frames: &[(Box<[i16; 32 * 256]>, u16)]) -> Vec<u8> {
+    let mut gif = b"GIF89a".to_vec();
+    gif.extend(&(WIDTH as u16).to_le_bytes());
+    gif.extend(&(HEIGHT as u16).to_le_bytes());
+    gif.extend(&[0x80, 0, 0]);              // global color table of 2 colors, background color, aspect ratio
+    gif.extend(&[255, 255, 255, 0, 0, 0]);  // 0: white, 1: black
+    gif.extend(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00");
+    for (image, delay) in frames {
+        gif.extend(&[0x21, 0xf9, 4, 0x04]); // graphic control extension: do not dispose
+        gif.extend(&delay.to_le_bytes());
+        gif.extend(&[0, 0]);
+        gif.push(0x2c);                     // image descriptor
+        gif.extend(&[0, 0, 0, 0]);
+        gif.extend(&(WIDTH as u16).to_le_bytes());
+        gif.extend(&(HEIGHT as u16).to_le_bytes());
+        gif.push(0);
+        let pixels = (0 .. HEIGHT).flat_map(|y| (0 .. WIDTH).map(move |x| (x, y)))
+            .map(|(x, y)| screen_image::pixel(image, x, y) as u8)
+            .collect::<Vec<_>>();
+        gif.push(MIN_CODE_SIZE as u8);
+        for block in lzw(&pixels).chunks(255) {
+            gif.push(block.len() as u8);
+            gif.extend(block);
+        }
+        gif.push(0);
+    }
+    gif.push(0x3b);
+    gif
+}
+
+// codes of the pixels are 0 and 1, but the minimum code size of GIF is 2
+const MIN_CODE_SIZE: u32 = 2;
+const CLEAR: u16 = 1 << MIN_CODE_SIZE;
+const END: u16 = CLEAR + 1;
+const MAX_CODES: u16 = 4096;
+
+// variable-length LZW codes of GIF, packed from the LSB
+fn lzw(pixels: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    let mut write = |code: u16, size: u32| {
+        buffer |= (code as u32) << bits;
+        bits += size;
+        while bits >= 8 {
+            bytes.push(buffer as u8);
+            buffer >>= 8;
+            bits -= 8;
+        }
+    };
+
+    let mut table = HashMap::new();
+    let (mut size, mut next) = (MIN_CODE_SIZE + 1, END + 1);
+    write(CLEAR, size);
+    let mut prefix: Option<u16> = None;
+    for &pixel in pixels {
+        prefix = Some(match prefix {
+            None => pixel as u16,
+            Some(code) => match table.get(&(code, pixel)) {
+                Some(&code) => code,
+                None => {
+                    write(code, size);
+                    if next == MAX_CODES {
+                        write(CLEAR, size);
+                        table.clear();
+                        size = MIN_CODE_SIZE + 1;
+                        next = END + 1;
+                    } else {
+                        table.insert((code, pixel), next);
+                        // the decoder needs one more bit from the code after this
+                        if next == 1 << size { size += 1; }
+                        next += 1;
+                    }
+                    pixel as u16
+                }
+            }
+        });
+    }
+    if let Some(code) = prefix {
+        write(code, size);
+    }
+    write(END, size);
+    if bits > 0 {
+        bytes.push(buffer as u8);
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // decoder of lzw(), as GIF viewers do
+    fn unlzw(bytes: &[u8]) -> Vec<u8> {
+        let (mut buffer, mut bits, mut bytes) = (0u32, 0u32, bytes.iter());
+        let mut size = MIN_CODE_SIZE + 1;
+        let mut read = |size: u32| {
+            while bits < size {
+                buffer |= (*bytes.next().unwrap() as u32) << bits;
+                bits += 8;
+            }
+            let code = (buffer & ((1 << size) - 1)) as u16;
+            buffer >>= size;
+            bits -= size;
+            code
+        };
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut pixels = Vec::new();
+        let mut last: Option<Vec<u8>> = None;
+        loop {
+            let code = read(size);
+            if code == CLEAR {
+                table = vec![vec![0], vec![1], vec![2], vec![3], vec![], vec![]];  // and CLEAR, END
+                size = MIN_CODE_SIZE + 1;
+                last = None;
+                continue;
+            }
+            if code == END { return pixels; }
+            let entry = match (table.get(code as usize), &last) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(last)) => [&last[..], &last[.. 1]].concat(),
+                (None, None) => panic!("invalid code")
+            };
+            if let Some(last) = last {
+                table.push([&last[..], &entry[.. 1]].concat());
+                if table.len() == 1 << size && size < 12 { size += 1; }
+            }
+            pixels.extend(&entry);
+            last = Some(entry);
+        }
+    }
+
+    #[test]
+    fn test_lzw() {
+        let patterns: Vec<Vec<u8>> = vec![
+            vec![0; 10000],
+            (0 .. 10000).map(|i| (i % 3 == 0) as u8).collect(),
+            (0u32 .. 100000).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8 & 1).collect(),
+        ];
+        for pixels in patterns {
+            assert_eq!(unlzw(&lzw(&pixels)), pixels);
+        }
+    }
+
+    #[test]
+    fn test_recorder() {
+        let dir = std::env::temp_dir().join(format!("screen_recorder_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut image = Box::new([0; 32 * 256]);
+
+        let mut recorder = Recorder::new(&dir.join("anim.gif"), 5).unwrap();
+        recorder.record(&image).unwrap();
+        recorder.record(&image).unwrap();
+        image[0] = 1;
+        recorder.record(&image).unwrap();
+        assert_eq!(recorder.frames.iter().map(|&(_, delay)| delay).collect::<Vec<_>>(), vec![10, 5]);
+        assert_eq!(recorder.finish(), Ok(3));
+        let gif = std::fs::read(dir.join("anim.gif")).unwrap();
+        assert_eq!(&gif[.. 6], b"GIF89a");
+        assert_eq!(gif.last(), Some(&0x3b));
+
+        let mut recorder = Recorder::new(&dir.join("shot.pbm"), 5).unwrap();
+        recorder.record(&image).unwrap();
+        recorder.record(&image).unwrap();
+        assert_eq!(recorder.finish(), Ok(2));
+        assert_eq!(std::fs::read(dir.join("shot-0001.pbm")).unwrap(), screen_image::to_pbm(&image));
+
+        assert!(Recorder::new(&dir.join("anim.mp4"), 5).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
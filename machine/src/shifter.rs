@@ -0,0 +1,31 @@
+// shifter of the extended Hack instruction set (D<<, A>>, M<< etc.)
+use crate::given::*;
+use crate::gate::*;
+
+// shift left by 1 bit
+pub fn shift_left<S: Signal>(a: Word<S>) -> Word<S> {
+    [S::constant(false), a[0], a[1], a[2], a[3], a[4], a[5], a[6], a[7], a[8], a[9], a[10], a[11], a[12], a[13], a[14]]
+}
+
+// arithmetic shift right by 1 bit, which keeps the sign bit
+pub fn shift_right<S: Signal>(a: Word<S>) -> Word<S> {
+    [a[1], a[2], a[3], a[4], a[5], a[6], a[7], a[8], a[9], a[10], a[11], a[12], a[13], a[14], a[15], a[15]]
+}
+
+pub fn shifter<S: Signal>(a: Word<S>, left: S) -> Word<S> {
+    mux16(shift_right(a), shift_left(a), left)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use debug::*;
+
+    #[test]
+    fn test_shifter() {
+        for &x in &[0, 1, 7, -1, -8, 0x4000, 0x7fff, -0x8000, 12345, -12345] {
+            assert_eq!(word2int(shifter(int2word(x), true)), x << 1);
+            assert_eq!(word2int(shifter(int2word(x), false)), x >> 1);
+        }
+    }
+}
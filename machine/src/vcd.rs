@@ -0,0 +1,207 @@
+// Value Change Dump (VCD) files, which waveform viewers such as GTKWave can open
+use std::io::{self, Write};
+use crate::given::*;
+use crate::given::debug::word2int;
+use crate::cpu::*;
+
+pub struct VcdWriter {
+    out: Box<dyn Write>,
+    widths: Vec<usize>,
+    last: Vec<Option<u16>>,     // last written values, to write only the changes
+}
+
+// short identifier of the i-th signal, made of the printable characters '!' to '~'
+fn identifier(mut i: usize) -> String {
+    let mut id = String::new();
+    loop {
+        id.push((b'!' + (i % 94) as u8) as char);
+        i /= 94;
+        if i == 0 { return id; }
+        i -= 1;
+    }
+}
+
+impl VcdWriter {
+    // write the header which declares the signals (name, width) in a scope
+    pub fn new(mut out: Box<dyn Write>, scope: &str, signals: &[(&str, usize)]) -> io::Result<Self> {
+        writeln!(out, "$version nand2tetris machine $end")?;
+        writeln!(out, "$timescale 1 ns $end")?;
+        writeln!(out, "$scope module {} $end", scope)?;
+        for (i, &(name, width)) in signals.iter().enumerate() {
+            writeln!(out, "$var wire {} {} {} $end", width, identifier(i), name)?;
+        }
+        writeln!(out, "$upscope $end")?;
+        writeln!(out, "$enddefinitions $end")?;
+        Ok(Self{ out, widths: signals.iter().map(|&(_, width)| width).collect(), last: vec![None; signals.len()] })
+    }
+
+    // values of all the signals at the time. only the changed ones are written
+    pub fn change(&mut self, time: u64, values: &[u16]) -> io::Result<()> {
+        assert_eq!(values.len(), self.widths.len());
+        let first = self.last[0].is_none();
+        writeln!(self.out, "#{}", time)?;
+        if first { writeln!(self.out, "$dumpvars")?; }
+        for (i, &value) in values.iter().enumerate() {
+            if self.last[i] == Some(value) { continue; }
+            self.last[i] = Some(value);
+            if self.widths[i] == 1 {
+                writeln!(self.out, "{}{}", value & 1, identifier(i))?;
+            } else {
+                writeln!(self.out, "b{:0width$b} {}", value, identifier(i), width = self.widths[i])?;
+            }
+        }
+        if first { writeln!(self.out, "$end")?; }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+const CPU_SIGNALS: [(&str, usize); 16] = [
+    ("pc", 15), ("a", 16), ("d", 16), ("instruction", 16), ("inM", 16), ("reset", 1),
+    ("alu_x", 16), ("alu_y", 16), ("alu_out", 16), ("zr", 1), ("ng", 1),
+    ("load_a", 1), ("load_d", 1), ("jump", 1), ("writeM", 1), ("addressM", 15),
+];
+
+// records the signals of the CPU per clock. the time is the cycle count
+pub struct CpuTracer {
+    vcd: VcdWriter,
+    error: Option<io::Error>,   // the first error, which is reported by finish()
+}
+
+impl CpuTracer {
+    pub fn new(out: Box<dyn Write>) -> io::Result<Self> {
+        Ok(Self{ vcd: VcdWriter::new(out, "cpu", &CPU_SIGNALS)?, error: None })
+    }
+    pub fn record(&mut self, cycle: u64, cpu: &Cpu, input: CpuInput) {
+        if self.error.is_some() { return; }
+        let signals = cpu.signals(input);
+        let word = |w: Word| word2int(w) as u16;
+        let bit = |b: bool| b as u16;
+        let values = [
+            word(cpu.pc()) & 0x7fff, word(cpu.addressM()), word(cpu.d()), word(input.instruction), word(input.inM), bit(input.reset),
+            word(signals.alu_x), word(signals.alu_y), word(signals.alu_out), bit(signals.zr), bit(signals.ng),
+            bit(signals.load_a), bit(signals.load_d), bit(signals.jump), bit(signals.writeM), word(cpu.addressM()) & 0x7fff,
+        ];
+        if let Err(e) = self.vcd.change(cycle, &values) {
+            self.error = Some(e);
+        }
+    }
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.vcd.flush()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // a writer whose contents can be read after it is boxed
+    #[derive(Clone, Default)]
+    struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // a writer which fails after the limit of bytes, like a full disk
+    struct Limited(usize);
+
+    impl Write for Limited {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf.len() > self.0 { return Err(io::Error::other("no space left")); }
+            self.0 -= buf.len();
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_identifier() {
+        assert_eq!(identifier(0), "!");
+        assert_eq!(identifier(93), "~");
+        assert_eq!(identifier(94), "!!");
+        assert_eq!(identifier(95), "\"!");
+    }
+
+    #[test]
+    fn test_writer() {
+        let buffer = Buffer::default();
+        let mut vcd = VcdWriter::new(Box::new(buffer.clone()), "top", &[("clk", 1), ("bus", 4)]).unwrap();
+        vcd.change(0, &[0, 5]).unwrap();
+        vcd.change(1, &[1, 5]).unwrap();
+        vcd.change(2, &[1, 12]).unwrap();
+        let text = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+        assert_eq!(text, "\
+$version nand2tetris machine $end
+$timescale 1 ns $end
+$scope module top $end
+$var wire 1 ! clk $end
+$var wire 4 \" bus $end
+$upscope $end
+$enddefinitions $end
+#0
+$dumpvars
+0!
+b0101 \"
+$end
+#1
+1!
+#2
+b1100 \"
+");
+    }
+
+    #[test]
+    fn test_machine() {
+        use crate::{Computer, Machine};
+        use crate::inst::*;
+        let program = [
+            AInstruction(5),
+            CInstruction(Computation::X(false), dest::D, Jump::Null),   // D=A
+            AInstruction(0),
+            CInstruction(Computation::Zero, 0, Jump::JMP),
+        ];
+        let buffer = Buffer::default();
+        let mut machine = Machine::new(&program.iter().map(|inst| inst.encode()).collect::<Vec<_>>());
+        machine.start_trace(Box::new(buffer.clone())).unwrap();
+        for _ in 0 .. 4 { machine.clock(false); }
+        machine.finish_trace().unwrap();
+        machine.clock(false);
+
+        // changes of each clock
+        let text = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+        assert!(text.contains("$var wire 1 . jump $end"));
+        let clocks = text.split("\n#").skip(1).map(|block| block.lines().collect::<Vec<_>>()).collect::<Vec<_>>();
+        assert_eq!(clocks.len(), 4);
+        assert!(clocks[1].contains(&"1-"));                     // load_d
+        assert!(clocks[1].contains(&"b0000000000000101 )"));    // alu_out
+        assert!(clocks[2].contains(&"0-"));
+        assert!(clocks[3].contains(&"1."));                     // jump
+        assert!(!clocks[3].contains(&"0-"));
+
+        // the error of writing is reported by finish_trace(), also through the Computer trait
+        let mut machine: Box<dyn Computer> = {
+            let mut machine = Machine::new(&program.iter().map(|inst| inst.encode()).collect::<Vec<_>>());
+            machine.start_trace(Box::new(Limited(text.find("\n#").unwrap() + 10))).unwrap();
+            Box::new(machine)
+        };
+        for _ in 0 .. 4 { machine.clock(false); }
+        assert_eq!(machine.finish_trace().unwrap_err().to_string(), "no space left");
+        assert!(machine.finish_trace().is_ok());
+    }
+}
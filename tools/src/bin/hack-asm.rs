@@ -0,0 +1,72 @@
+extern crate asm;
+extern crate machine;
+use machine::hack_format;
+use std::env;
+use std::path::{Path, PathBuf};
+
+fn usage(program: &str) -> ! {
+    eprintln!("usage: {} [-o output.hack] [--listing] [--symbols] filename.asm", program);
+    eprintln!("  -o file      write the machine code to the file (.hack or .bin). default: filename.hack");
+    eprintln!("  --listing    write the listing (ROM address, binary, hex and source) to filename.lst");
+    eprintln!("  --symbols    print the symbol table (labels and variables)");
+    std::process::exit(2);
+}
+
+fn write(path: &Path, contents: &str) {
+    if let Err(e) = std::fs::write(path, contents) {
+        eprintln!("{}: {}", path.display(), e);
+        std::process::exit(2);
+    }
+}
+
+// assemble a .asm file into a .hack file
+fn main() {
+    let mut args = env::args().collect::<Vec<_>>();
+    let listing = args.iter().any(|arg| arg == "--listing");
+    let symbols = args.iter().any(|arg| arg == "--symbols");
+    args.retain(|arg| arg != "--listing" && arg != "--symbols");
+    let output = match args.iter().position(|arg| arg == "-o") {
+        Some(i) if i + 1 < args.len() => {
+            let file = args.remove(i + 1);
+            args.remove(i);
+            Some(PathBuf::from(file))
+        },
+        Some(_) => usage(&args[0]),
+        None => None
+    };
+    if args.len() != 2 || args[1].starts_with('-') {
+        usage(&args[0]);
+    }
+
+    let path = Path::new(&args[1]);
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("{}: {}", path.display(), e);
+            std::process::exit(2);
+        }
+    };
+    let instructions = match asm::asm_all(&source) {
+        Ok(instructions) => instructions,
+        Err(errors) => {
+            for e in &errors {
+                eprintln!("{}", e.render(&args[1]));
+            }
+            eprintln!("{} error(s) in {}", errors.len(), path.display());
+            std::process::exit(1);
+        }
+    };
+
+    let output = output.unwrap_or_else(|| path.with_extension("hack"));
+    if let Err(e) = hack_format::write_file(&output, &instructions) {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    }
+    if listing {
+        write(&path.with_extension("lst"), &asm::listing(&source).expect("already assembled"));
+    }
+    if symbols {
+        print!("{}", asm::symbol_table(&source));
+    }
+    println!("{} instructions written to {}", instructions.len(), output.display());
+}
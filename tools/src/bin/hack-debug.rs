@@ -0,0 +1,219 @@
+extern crate machine;
+extern crate tools;
+use machine::*;
+use machine::debugger::*;
+use std::env;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::path::Path;
+
+// cycles executed by 'continue' and 'next' before giving the control back to the user
+const MAX_CYCLES: u64 = 1_000_000;
+
+const HELP: &str = "\
+commands:
+  s, step [n]           execute n instructions (default 1)
+  n, next               step over a function call of VM code
+  c, continue           run until a breakpoint or a watchpoint
+  b, break <addr|label> set a breakpoint
+  d, delete <addr|label>
+                        delete a breakpoint
+  w, watch <addr>       stop when RAM[addr] is written
+  unwatch <addr>        delete a watchpoint
+  l, list [n]           disassemble n instructions around PC (default 5)
+  r, regs               print A, D and PC
+  where                 print the source location of PC
+  x <addr> [n]          print n words of RAM from addr (default 1)
+  set <A|D|PC|addr> <value>
+                        set a register or RAM[addr]
+  info                  list breakpoints and watchpoints
+  q, quit
+an empty line repeats the previous command.";
+
+fn parse_number(s: &str) -> Option<i16> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok().map(|n| n as i16),
+        None => s.parse::<i16>().ok()
+    }
+}
+
+fn parse_address(debugger: &Debugger, s: &str) -> Option<i16> {
+    parse_number(s).or_else(|| debugger.label_address(s))
+}
+
+fn print_location(debugger: &Debugger) {
+    if let Some(location) = debugger.location(debugger.state().pc) {
+        println!("at {}", location);
+    }
+}
+
+fn print_stop(debugger: &Debugger, reason: &StopReason) {
+    if *reason != StopReason::Step { println!("{}", reason); }
+    println!("{}", debugger.state());
+    print_location(debugger);
+}
+
+// execute a command, and return false to quit
+fn execute(debugger: &mut Debugger, line: &str) -> bool {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    let arg = |i: usize| words.get(i).cloned();
+    match words.first().cloned().unwrap_or("") {
+        "s" | "step" => {
+            let n = arg(1).and_then(|s| s.parse::<usize>().ok()).unwrap_or(1);
+            let mut reason = StopReason::Step;
+            for _ in 0 .. n {
+                reason = debugger.step();
+                if reason != StopReason::Step { break; }
+            }
+            print_stop(debugger, &reason);
+        },
+        "n" | "next" => {
+            let reason = debugger.step_over(MAX_CYCLES);
+            print_stop(debugger, &reason);
+        },
+        "c" | "continue" => {
+            let reason = debugger.cont(MAX_CYCLES);
+            print_stop(debugger, &reason);
+        },
+        "b" | "break" => match arg(1).and_then(|s| parse_address(debugger, s)) {
+            Some(address) => {
+                debugger.add_breakpoint(address);
+                println!("breakpoint at {}", address);
+            },
+            None => println!("usage: break <addr|label>")
+        },
+        "d" | "delete" => match arg(1).and_then(|s| parse_address(debugger, s)) {
+            Some(address) if debugger.remove_breakpoint(address) => println!("deleted breakpoint at {}", address),
+            Some(address) => println!("no breakpoint at {}", address),
+            None => println!("usage: delete <addr|label>")
+        },
+        "w" | "watch" => match arg(1).and_then(parse_number) {
+            Some(address) => {
+                debugger.add_watchpoint(address);
+                println!("watchpoint at RAM[{}]", address);
+            },
+            None => println!("usage: watch <addr>")
+        },
+        "unwatch" => match arg(1).and_then(parse_number) {
+            Some(address) if debugger.remove_watchpoint(address) => println!("deleted watchpoint at RAM[{}]", address),
+            Some(address) => println!("no watchpoint at RAM[{}]", address),
+            None => println!("usage: unwatch <addr>")
+        },
+        "l" | "list" => {
+            let radius = arg(1).and_then(parse_number).unwrap_or(5);
+            for line in debugger.disassemble_around_pc(radius) {
+                println!("{}", line);
+            }
+        },
+        "r" | "regs" => println!("{}", debugger.state()),
+        "where" => print_location(debugger),
+        "x" => match arg(1).and_then(parse_number) {
+            Some(address) => {
+                let n = arg(2).and_then(parse_number).unwrap_or(1);
+                for i in 0 .. n {
+                    let address = address.wrapping_add(i);
+                    println!("RAM[{}] = {}", address, debugger.computer().read_memory(address));
+                }
+            },
+            None => println!("usage: x <addr> [n]")
+        },
+        "set" => match (arg(1), arg(2).and_then(parse_number)) {
+            (Some(target), Some(value)) => {
+                let computer = debugger.computer_mut();
+                match target {
+                    "A" => computer.set_register(CpuRegister::A, value),
+                    "D" => computer.set_register(CpuRegister::D, value),
+                    "PC" => computer.set_register(CpuRegister::PC, value),
+                    _ => match parse_number(target) {
+                        Some(address) => computer.write_memory(address, value),
+                        None => println!("unknown register '{}'", target)
+                    }
+                }
+            },
+            _ => println!("usage: set <A|D|PC|addr> <value>")
+        },
+        "info" => {
+            println!("breakpoints: {:?}", debugger.breakpoints().collect::<Vec<_>>());
+            println!("watchpoints: {:?}", debugger.watchpoints().collect::<Vec<_>>());
+        },
+        "h" | "help" => println!("{}", HELP),
+        "q" | "quit" => return false,
+        "" => (),
+        command => println!("unknown command '{}'. type 'help' for the list of commands.", command)
+    }
+    true
+}
+
+fn main() {
+    let args = env::args().collect::<Vec<_>>();
+
+    // '--fast' debugs the program on the word-level emulator instead of the gate-level machine
+    let fast = args.iter().any(|arg| arg == "--fast");
+    let mut args = args.into_iter().filter(|arg| arg != "--fast").collect::<Vec<_>>();
+
+    // '--vcd file' records the signals of the CPU into a VCD file
+    let vcd = match args.iter().position(|arg| arg == "--vcd") {
+        Some(i) if i + 1 < args.len() && !fast => {
+            let file = args.remove(i + 1);
+            args.remove(i);
+            Some(file)
+        },
+        Some(_) => {
+            eprintln!("--vcd needs a filename, and cannot be used with --fast");
+            std::process::exit(2);
+        },
+        None => None
+    };
+    if args.len() < 2 {
+        eprintln!("usage: {} [--fast | --vcd trace.vcd] (filename.hack | filename.bin | filename.asm | filename.vm | directory)", args[0]);
+        std::process::exit(2);
+    }
+
+    let path = Path::new(&args[1]);
+    let program = match tools::load(path) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    let computer: Box<dyn Computer> = if fast {
+        Box::new(Emulator::new(&program.instructions))
+    } else {
+        let mut machine = Machine::new(&program.instructions);
+        if let Some(vcd) = &vcd {
+            // the buffered file is flushed by finish_trace() at the end
+            let started = File::create(vcd).and_then(|file| machine.start_trace(Box::new(BufWriter::new(file))));
+            if let Err(e) = started {
+                eprintln!("{}: {}", vcd, e);
+                std::process::exit(2);
+            }
+        }
+        Box::new(machine)
+    };
+    let mut debugger = Debugger::new(computer, &program.instructions);
+    debugger.set_labels(program.labels);
+    debugger.set_locations(program.locations);
+    println!("{} instructions loaded. type 'help' for the list of commands.", program.instructions.len());
+    println!("{}", debugger.state());
+
+    let stdin = std::io::stdin();
+    let mut previous = String::new();
+    loop {
+        print!("(hack) ");
+        std::io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 { break; }
+        let line = if line.trim().is_empty() { previous.clone() } else { line.trim().to_string() };
+        if !execute(&mut debugger, &line) { break; }
+        previous = line;
+    }
+
+    // otherwise a trace truncated by an error of writing (e.g. the disk is full) would go unnoticed
+    if let Some(vcd) = &vcd {
+        if let Err(e) = debugger.computer_mut().finish_trace() {
+            eprintln!("{}: {}", vcd, e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,35 @@
+extern crate machine;
+extern crate tools;
+use std::env;
+use std::path::Path;
+
+// run a program on the gate-level Machine and the reference interpreter in lock-step
+fn main() {
+    let args = env::args().collect::<Vec<_>>();
+    if args.len() < 2 {
+        eprintln!("usage: {} (filename.hack | filename.bin | filename.asm | filename.vm | directory) [max_cycles]", args[0]);
+        std::process::exit(2);
+    }
+    let max_cycles = match args.get(2).map(|s| s.parse::<usize>()) {
+        None => 10000,
+        Some(Ok(n)) => n,
+        Some(Err(_)) => {
+            eprintln!("invalid number of cycles: '{}'", args[2]);
+            std::process::exit(2);
+        }
+    };
+    let instructions = match tools::load_program(Path::new(&args[1])) {
+        Ok(instructions) => instructions,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    match machine::difftest::diff_machine(&instructions, max_cycles) {
+        Ok(cycles) => println!("ok: no divergence in {} cycles", cycles),
+        Err(divergence) => {
+            println!("{}", divergence);
+            std::process::exit(1);
+        }
+    }
+}
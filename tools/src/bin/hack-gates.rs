@@ -0,0 +1,14 @@
+extern crate machine;
+use machine::analysis;
+
+// print the NAND counts, flip-flop counts and critical path depths of the hand-wired chips
+fn main() {
+    for report in analysis::report() {
+        println!("{}", report);
+    }
+    // the adders are not parts of each other, so they share a table
+    println!("{:<24} {:>10} {:>10} {:>6}", "add16 design", "nands", "flipflops", "depth");
+    for report in analysis::adder_reports() {
+        println!("{:<24} {:>10} {:>10} {:>6}", report.name, report.cost.nands, report.cost.flipflops, report.cost.depth);
+    }
+}
@@ -0,0 +1,123 @@
+extern crate machine;
+extern crate tools;
+use machine::{Computer, Emulator, Machine, screen_image};
+use machine::screen_recorder::Recorder;
+use machine::keys::KeyScript;
+use std::env;
+use std::path::Path;
+use tools::run::*;
+
+// the limit of cycles when --cycles is not given
+const DEFAULT_MAX_CYCLES: u64 = 10_000_000;
+
+// cycles between the frames of --record when --every is not given
+const DEFAULT_FRAME_CYCLES: u64 = 50_000;
+
+// centiseconds per frame of a recorded GIF
+const FRAME_DELAY: u16 = 5;
+
+fn usage(program: &str) -> ! {
+    eprintln!("usage: {} [options] (filename.hack | filename.bin | filename.asm | filename.vm | directory)", program);
+    eprintln!("  --fast             run on the word-level emulator instead of the gate-level machine");
+    eprintln!("  --cycles N         run N cycles at most (default: until the program stops)");
+    eprintln!("  --set ADDR=VALUE   set a RAM cell before running, e.g. --set R0=100 (repeatable)");
+    eprintln!("  --ram FROM..TO     print RAM[FROM] to RAM[TO - 1] after running, or FROM..=TO or ADDR (repeatable)");
+    eprintln!("  --keys FILE        type the keys of a key script (lines of 'CYCLE KEY' or 'CYCLE release')");
+    eprintln!("  --screen           print the screen as ASCII art (a character per 4x4 pixels)");
+    eprintln!("  --pbm FILE         write the screen to a PBM image file");
+    eprintln!("  --png FILE         write the screen to a PNG image file");
+    eprintln!("  --record FILE      record the screen to an animated GIF (.gif) or numbered images (.png, .pbm)");
+    eprintln!("  --every N          record a frame every N cycles (default: {})", DEFAULT_FRAME_CYCLES);
+    std::process::exit(2);
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(2);
+}
+
+// remove all '--option value' from args, and return the values
+fn take_values(args: &mut Vec<String>, option: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    while let Some(i) = args.iter().position(|arg| arg == option) {
+        if i + 1 >= args.len() { fail(format!("{} needs a value", option)); }
+        values.push(args.remove(i + 1));
+        args.remove(i);
+    }
+    values
+}
+
+// run a program without the window, and print the RAM or the screen after it stops
+fn main() {
+    let mut args = env::args().collect::<Vec<_>>();
+    let fast = args.iter().any(|arg| arg == "--fast");
+    let screen = args.iter().any(|arg| arg == "--screen");
+    args.retain(|arg| arg != "--fast" && arg != "--screen");
+    let cycles = take_values(&mut args, "--cycles").pop().map(|n| {
+        n.parse::<u64>().unwrap_or_else(|_| fail(format!("invalid number of cycles: '{}'", n)))
+    });
+    let assignments = take_values(&mut args, "--set").iter().map(|s| {
+        parse_assignment(s).unwrap_or_else(|| fail(format!("invalid assignment: '{}' (expected ADDR=VALUE, where ADDR is 0 to {})", s, LAST_ADDRESS)))
+    }).collect::<Vec<_>>();
+    let ranges = take_values(&mut args, "--ram").iter().map(|s| {
+        parse_range(s).unwrap_or_else(|| fail(format!("invalid RAM range: '{}' (the addresses are 0 to {})", s, LAST_ADDRESS)))
+    }).collect::<Vec<_>>();
+    let pbm = take_values(&mut args, "--pbm").pop();
+    let png = take_values(&mut args, "--png").pop();
+    let mut recorder = take_values(&mut args, "--record").pop().map(|file| {
+        Recorder::new(Path::new(&file), FRAME_DELAY).unwrap_or_else(|e| fail(e))
+    });
+    let every = take_values(&mut args, "--every").pop().map_or(DEFAULT_FRAME_CYCLES, |n| match n.parse::<u64>() {
+        Ok(n) if n > 0 => n,
+        _ => fail(format!("invalid number of cycles: '{}'", n))
+    });
+    let keys = match take_values(&mut args, "--keys").pop() {
+        Some(file) => std::fs::read_to_string(&file).map_err(|e| e.to_string())
+            .and_then(|source| KeyScript::parse(&source))
+            .unwrap_or_else(|e| fail(format!("{}: {}", file, e))),
+        None => KeyScript::default()
+    };
+    if args.len() != 2 || args[1].starts_with('-') {
+        usage(&args[0]);
+    }
+
+    let rom = tools::load_program(Path::new(&args[1])).unwrap_or_else(|e| fail(e));
+    let mut computer: Box<dyn Computer> = if fast { Box::new(Emulator::new(&rom)) } else { Box::new(Machine::new(&rom)) };
+    for &(address, value) in &assignments {
+        computer.write_memory(address, value);
+    }
+    let (stop, n) = run_with(computer.as_mut(), &rom, cycles.unwrap_or(DEFAULT_MAX_CYCLES), |cycle, computer| {
+        keys.apply(cycle, computer);
+        if let Some(recorder) = &mut recorder {
+            if cycle % every == 0 {
+                recorder.record(computer.screen_image()).unwrap_or_else(|e| fail(e));
+            }
+        }
+    });
+    println!("{} after {} cycles", stop, n);
+    if let Some(mut recorder) = recorder {
+        // and the screen when it stopped
+        recorder.record(computer.screen_image()).unwrap_or_else(|e| fail(e));
+        let frames = recorder.finish().unwrap_or_else(|e| fail(e));
+        println!("recorded {} frames", frames);
+    }
+
+    for range in ranges {
+        for address in range {
+            println!("RAM[{}] = {}", address, computer.read_memory(address));
+        }
+    }
+    if screen {
+        print!("{}", screen_image::to_ascii(computer.screen_image(), 4));
+    }
+    if let Some(pbm) = pbm {
+        std::fs::write(&pbm, screen_image::to_pbm(computer.screen_image())).unwrap_or_else(|e| fail(format!("{}: {}", pbm, e)));
+    }
+    if let Some(png) = png {
+        std::fs::write(&png, screen_image::to_png(computer.screen_image())).unwrap_or_else(|e| fail(format!("{}: {}", png, e)));
+    }
+    // running out of the default limit means the program did not stop
+    if stop == Stop::Cycles && cycles.is_none() {
+        std::process::exit(1);
+    }
+}
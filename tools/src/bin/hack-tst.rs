@@ -0,0 +1,37 @@
+extern crate tools;
+use std::env;
+use std::path::Path;
+
+// run nand2tetris test scripts (.tst), and compare the outputs with the compare files (.cmp)
+fn main() {
+    let args = env::args().collect::<Vec<_>>();
+
+    // '--fast' runs the scripts on the word-level emulator instead of the gate-level machine
+    let fast = args.iter().any(|arg| arg == "--fast");
+    let args = args.into_iter().filter(|arg| arg != "--fast").collect::<Vec<_>>();
+    if args.len() < 2 {
+        eprintln!("usage: {} [--fast] filename.tst ...", args[0]);
+        std::process::exit(2);
+    }
+
+    let mut failures = 0;
+    for script in &args[1..] {
+        match tools::tst::run_file(Path::new(script), fast) {
+            Ok(report) => {
+                for message in &report.messages { println!("{}: {}", script, message); }
+                if report.compared {
+                    println!("{}: End of script - Comparison ended successfully", script);
+                } else {
+                    println!("{}: End of script", script);
+                }
+            },
+            Err(e) => {
+                println!("{}: {}", script, e);
+                failures += 1;
+            }
+        }
+    }
+    if failures > 0 {
+        std::process::exit(1);
+    }
+}
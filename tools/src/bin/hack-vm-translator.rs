@@ -0,0 +1,79 @@
+extern crate asm;
+extern crate machine;
+extern crate vm_translator;
+use machine::hack_format;
+use std::env;
+use std::path::{Path, PathBuf};
+use vm_translator::VmModule;
+
+fn usage(program: &str) -> ! {
+    eprintln!("usage: {} [--no-bootstrap] [--hack] (filename.vm | directory)", program);
+    eprintln!("  --no-bootstrap   do not emit the bootstrap code (SP = 256, call Sys.init)");
+    eprintln!("  --hack           also assemble the output into a .hack file");
+    std::process::exit(2);
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(2);
+}
+
+// the modules to translate and the output file, by the convention of the course:
+// 'Foo.vm' is translated into 'Foo.asm', and 'Foo/*.vm' into 'Foo/Foo.asm'
+fn read_input(path: &Path) -> Result<(Vec<VmModule>, PathBuf), String> {
+    let error = |e: std::io::Error| format!("{}: {}", path.display(), e);
+    if path.is_dir() {
+        let modules = vm_translator::read_dir(path).map_err(error)?;
+        if modules.is_empty() {
+            return Err(format!("{}: no .vm files in the directory", path.display()));
+        }
+        let dir = path.canonicalize().map_err(error)?;
+        let name = dir.file_name().and_then(|s| s.to_str()).unwrap_or("Prog").to_string();
+        Ok((modules, dir.join(name + ".asm")))
+    } else if path.extension().and_then(|s| s.to_str()) == Some("vm") {
+        let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string();
+        let source = std::fs::read_to_string(path).map_err(error)?;
+        Ok((vec![VmModule{ name, source }], path.with_extension("asm")))
+    } else {
+        Err(format!("{}: expected a .vm file or a directory", path.display()))
+    }
+}
+
+// translate VM code into a .asm file
+fn main() {
+    let mut args = env::args().collect::<Vec<_>>();
+    let bootstrap = !args.iter().any(|arg| arg == "--no-bootstrap");
+    let hack = args.iter().any(|arg| arg == "--hack");
+    args.retain(|arg| arg != "--no-bootstrap" && arg != "--hack");
+    if args.len() != 2 || args[1].starts_with('-') {
+        usage(&args[0]);
+    }
+
+    let (modules, output) = read_input(Path::new(&args[1])).unwrap_or_else(|e| fail(e));
+    let mut asm_source = String::new();
+    let translated = if bootstrap {
+        vm_translator::compile_modules(&mut asm_source, &modules)
+    } else {
+        vm_translator::compile_modules_without_bootstrap(&mut asm_source, &modules)
+    };
+    if let Err(e) = translated {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    std::fs::write(&output, &asm_source).unwrap_or_else(|e| fail(format!("{}: {}", output.display(), e)));
+    println!("{} VM file(s) translated into {}", modules.len(), output.display());
+
+    if hack {
+        let instructions = match asm::asm_all(&asm_source) {
+            Ok(instructions) => instructions,
+            Err(errors) => {
+                let filename = output.display().to_string();
+                for e in &errors { eprintln!("{}", e.render(&filename)); }
+                std::process::exit(1);
+            }
+        };
+        let output = output.with_extension("hack");
+        hack_format::write_file(&output, &instructions).unwrap_or_else(|e| fail(e));
+        println!("{} instructions written to {}", instructions.len(), output.display());
+    }
+}
@@ -0,0 +1,173 @@
+// golden-screen tests: run a graphical program and compare the screen with a reference image (PBM).
+// setting the environment variable UPDATE_GOLDEN makes check() write the screens to the reference images instead.
+use crate::run::run;
+use machine::{Computer, Emulator, Machine, screen_image};
+use std::path::{Path, PathBuf};
+
+pub struct ScreenTest<'a> {
+    pub program: &'a Path,      // .asm, .hack etc. (see load())
+    pub ram: &'a [(i16, i16)],  // (address, value) set before running, e.g. (0, 100) for R0=100
+    pub cycles: u64,            // it stops earlier at a halt loop or the end of the program
+    pub fast: bool,             // run on the word-level emulator instead of the gate-level machine
+}
+
+impl ScreenTest<'_> {
+    // the screen after running the program
+    pub fn screen(&self) -> Result<Box<[i16; 32 * 256]>, String> {
+        let rom = crate::load_program(self.program)?;
+        let mut computer: Box<dyn Computer> = if self.fast { Box::new(Emulator::new(&rom)) } else { Box::new(Machine::new(&rom)) };
+        for &(address, value) in self.ram {
+            computer.write_memory(address, value);
+        }
+        run(computer.as_mut(), &rom, self.cycles);
+        Ok(Box::new(*computer.screen_image()))
+    }
+
+    // verify() the screen, or write it to the reference if UPDATE_GOLDEN is set
+    pub fn check(&self, reference: &Path) -> Result<(), String> {
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            return screen_image::write_file(reference, &*self.screen()?);
+        }
+        self.verify(reference)
+    }
+
+    // compare the screen with the reference. on a mismatch, the error has the report of the pixels,
+    // and the screen and the diff image are written to 'failure_dir' for inspection.
+    pub fn verify(&self, reference: &Path) -> Result<(), String> {
+        let actual = self.screen()?;
+        let bytes = std::fs::read(reference).map_err(|e| format!("{}: {} (set UPDATE_GOLDEN=1 to create it)", reference.display(), e))?;
+        let expected = screen_image::from_pbm(&bytes).map_err(|e| format!("{}: {}", reference.display(), e))?;
+        let mismatch = match compare(&actual, &expected) {
+            Some(mismatch) => mismatch,
+            None => return Ok(())
+        };
+        let name = reference.file_stem().and_then(|s| s.to_str()).unwrap_or("screen");
+        let dir = failure_dir();
+        let (actual_path, diff_path) = (dir.join(format!("{}.actual.pbm", name)), dir.join(format!("{}.diff.png", name)));
+        std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        screen_image::write_file(&actual_path, &actual)?;
+        std::fs::write(&diff_path, diff_png(&actual, &expected)).map_err(|e| format!("{}: {}", diff_path.display(), e))?;
+        Err(format!("{}: {}\nscreen: {}\ndiff: {}", reference.display(), mismatch, actual_path.display(), diff_path.display()))
+    }
+}
+
+// where the screens of the failed tests are written, separately for each test process
+pub fn failure_dir() -> PathBuf {
+    std::env::temp_dir().join("hack-golden").join(std::process::id().to_string())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub missing: Vec<(usize, usize)>,   // (x, y) of the pixels which should be black
+    pub extra: Vec<(usize, usize)>,     // (x, y) of the pixels which should be white
+}
+
+impl Mismatch {
+    // the smallest rectangle which has all the pixels: ((left, top), (right, bottom))
+    pub fn bounds(&self) -> ((usize, usize), (usize, usize)) {
+        let pixels = self.missing.iter().chain(&self.extra);
+        let (xs, ys) = (pixels.clone().map(|&(x, _)| x), pixels.map(|&(_, y)| y));
+        ((xs.clone().min().unwrap_or(0), ys.clone().min().unwrap_or(0)), (xs.max().unwrap_or(0), ys.max().unwrap_or(0)))
+    }
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let ((left, top), (right, bottom)) = self.bounds();
+        write!(f, "{} pixels differ ({} missing, {} extra) in ({}, {})..=({}, {})",
+            self.missing.len() + self.extra.len(), self.missing.len(), self.extra.len(), left, top, right, bottom)?;
+        // the first rows of the differences
+        let mut pixels = self.missing.iter().map(|&p| (p, "missing")).chain(self.extra.iter().map(|&p| (p, "extra"))).collect::<Vec<_>>();
+        pixels.sort_by_key(|&((x, y), _)| (y, x));
+        for &((x, y), kind) in pixels.iter().take(8) {
+            write!(f, "\n  ({}, {}) {}", x, y, kind)?;
+        }
+        if pixels.len() > 8 {
+            write!(f, "\n  ...")?;
+        }
+        Ok(())
+    }
+}
+
+// the pixels which differ, or None if the screens are the same
+pub fn compare(actual: &[i16; 32 * 256], expected: &[i16; 32 * 256]) -> Option<Mismatch> {
+    let mut mismatch = Mismatch{ missing: Vec::new(), extra: Vec::new() };
+    for y in 0 .. screen_image::HEIGHT {
+        for x in 0 .. screen_image::WIDTH {
+            match (screen_image::pixel(actual, x, y), screen_image::pixel(expected, x, y)) {
+                (false, true) => mismatch.missing.push((x, y)),
+                (true, false) => mismatch.extra.push((x, y)),
+                _ => ()
+            }
+        }
+    }
+    if mismatch.missing.is_empty() && mismatch.extra.is_empty() { None } else { Some(mismatch) }
+}
+
+// PNG of the screens: the missing pixels are red, the extra ones are blue, and the others are as they are
+pub fn diff_png(actual: &[i16; 32 * 256], expected: &[i16; 32 * 256]) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(screen_image::WIDTH * screen_image::HEIGHT);
+    for y in 0 .. screen_image::HEIGHT {
+        for x in 0 .. screen_image::WIDTH {
+            pixels.push(match (screen_image::pixel(actual, x, y), screen_image::pixel(expected, x, y)) {
+                (false, false) => [255, 255, 255],
+                (true, true) => [0, 0, 0],
+                (false, true) => [255, 0, 0],
+                (true, false) => [0, 0, 255],
+            });
+        }
+    }
+    screen_image::rgb_to_png(screen_image::WIDTH, screen_image::HEIGHT, &pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rect() {
+        // Rect.asm sets R0 = 100 by itself, and RectL.asm draws R0 rows
+        let test = ScreenTest{ program: Path::new("../data/Rect.asm"), ram: &[], cycles: 10000, fast: true };
+        test.check(Path::new("../data/golden/Rect.pbm")).unwrap();
+        let test = ScreenTest{ program: Path::new("../data/RectL.asm"), ram: &[(0, 50)], cycles: 10000, fast: true };
+        test.check(Path::new("../data/golden/RectL.pbm")).unwrap();
+    }
+
+    #[test]
+    fn test_mismatch() {
+        // the reference is made here, so that the golden images are never touched
+        let dir = std::env::temp_dir().join(format!("golden_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let reference = dir.join("mismatch.pbm");
+        let rows = |n| ScreenTest{ program: Path::new("../data/RectL.asm"), ram: &[(0, n)], cycles: 10000, fast: true }.screen().unwrap();
+        screen_image::write_file(&reference, &rows(50)).unwrap();
+
+        let test = ScreenTest{ program: Path::new("../data/RectL.asm"), ram: &[(0, 52)], cycles: 10000, fast: true };
+        let error = test.verify(&reference).unwrap_err();
+        let report = "32 pixels differ (0 missing, 32 extra) in (0, 50)..=(15, 51)\n  (0, 50) extra\n";
+        assert!(error.starts_with(&format!("{}: {}", reference.display(), report)), "{}", error);
+        let (screen_path, diff_path) = (failure_dir().join("mismatch.actual.pbm"), failure_dir().join("mismatch.diff.png"));
+        assert_eq!(screen_image::from_pbm(&std::fs::read(&screen_path).unwrap()).unwrap(), test.screen().unwrap());
+        assert_eq!(std::fs::read(&diff_path).unwrap(), diff_png(&test.screen().unwrap(), &rows(50)));
+        std::fs::remove_file(screen_path).unwrap();
+        std::fs::remove_file(diff_path).unwrap();
+        std::fs::remove_dir(failure_dir()).ok();    // unless the other tests failed
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mut expected = test.screen().unwrap();
+        expected[32 * 10 + 1] = 1;  // (16, 10)
+        expected[32 * 51] = 0;      // (0, 51) to (15, 51)
+        let mismatch = compare(&test.screen().unwrap(), &expected).unwrap();
+        assert_eq!(mismatch.missing, vec![(16, 10)]);
+        assert_eq!(mismatch.extra.len(), 16);
+        assert_eq!(mismatch.bounds(), ((0, 10), (16, 51)));
+        assert_ne!(diff_png(&test.screen().unwrap(), &expected), diff_png(&expected, &expected));
+    }
+
+    #[test]
+    fn test_machine() {
+        // the gate-level machine is slow without optimization, so only a few rows
+        let test = |fast| ScreenTest{ program: Path::new("../data/RectL.asm"), ram: &[(0, 2)], cycles: 100, fast }.screen().unwrap();
+        assert_eq!(compare(&test(false), &test(true)), None);
+    }
+}
@@ -0,0 +1,105 @@
+extern crate machine;
+extern crate asm;
+extern crate vm_translator;
+pub mod tst;
+pub mod run;
+pub mod golden;
+use std::path::Path;
+
+// a program loaded from a file with the information for debugging, which is not available for .hack files
+pub struct Program {
+    pub instructions: Vec<i16>,
+    pub labels: Vec<(String, i16)>,
+    pub locations: Vec<Option<String>>,    // source location of each ROM address
+}
+
+fn read_to_string(path: &Path) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// assemble the program, and locate each instruction by the asm line or by the VM command which produced it
+fn assemble(filename: &str, source: &str, source_map: Option<&vm_translator::SourceMap>) -> Result<Program, String> {
+    let (instructions, debug_info) = asm::asm_with_debug_info(source).map_err(|errors| {
+        errors.iter().map(|e| e.render(filename)).collect::<Vec<_>>().join("\n")
+    })?;
+    let locations = debug_info.iter().map(|info| match source_map {
+        Some(source_map) => source_map.find(info.line).map(|location| location.to_string()),
+        None => Some(match info.label {
+            Some(label) => format!("{}:{} ({})", filename, info.line, label),
+            None => format!("{}:{}", filename, info.line)
+        })
+    }).collect();
+    let labels = asm::labels(source).into_iter().map(|(label, address)| (label.to_string(), address)).collect();
+    Ok(Program{ instructions, labels, locations })
+}
+
+fn translate(modules: &[vm_translator::VmModule]) -> Result<Program, String> {
+    let mut asm_source = String::new();
+    let source_map = vm_translator::compile_modules_with_source_map(&mut asm_source, modules).map_err(|e| e.to_string())?;
+    assemble("(translated VM code)", &asm_source, Some(&source_map))
+}
+
+// read a program from a .hack (text), .bin (binary), .asm or .vm file, or a directory of .vm files.
+// the error is a message to be shown to the user.
+pub fn load(path: &Path) -> Result<Program, String> {
+    let filename = path.display().to_string();
+    if path.is_dir() {
+        let modules = vm_translator::read_dir(path).map_err(|e| format!("{}: {}", filename, e))?;
+        return translate(&modules);
+    }
+    match path.extension().and_then(|s| s.to_str()) {
+        Some("hack") | Some("bin") => {
+            let instructions = machine::hack_format::read_file(path)?;
+            let locations = vec![None; instructions.len()];
+            Ok(Program{ instructions, labels: Vec::new(), locations })
+        },
+        Some("asm") => assemble(&filename, &read_to_string(path)?, None),
+        Some("vm") => {
+            let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string();
+            translate(&[vm_translator::VmModule{ name, source: read_to_string(path)? }])
+        },
+        _ => Err(format!("{}: unsupported file type (expected .hack, .bin, .asm, .vm or a directory)", filename))
+    }
+}
+
+// same as load(), but only the instructions
+pub fn load_program(path: &Path) -> Result<Vec<i16>, String> {
+    load(path).map(|program| program.instructions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_asm() {
+        let instructions = load_program(Path::new("../data/Rect.asm")).unwrap();
+        assert_eq!(instructions[0], 100);   // @100
+        assert!(load_program(Path::new("../data/Rect.txt")).is_err());
+
+        // the machine code written as a .hack file is read back
+        let dir = std::env::temp_dir().join(format!("tools_test_load_asm_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Rect.hack");
+        machine::hack_format::write_file(&path, &instructions).unwrap();
+        assert_eq!(load_program(&path).unwrap(), instructions);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let program = load(Path::new("../data/Rect.asm")).unwrap();
+        assert!(program.labels.contains(&("LOOP".to_string(), 14)));
+        assert_eq!(program.locations[14].as_ref().unwrap(), "../data/Rect.asm:25 (LOOP)");
+    }
+
+    #[test]
+    fn test_load_vm() {
+        let dir = std::env::temp_dir().join(format!("tools_test_load_vm_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Sys.vm"), "function Sys.init 0\npush constant 7\nreturn\n").unwrap();
+        let program = load(&dir).unwrap();
+        let address = program.instructions.iter().position(|&inst| inst == 7).unwrap();
+        assert_eq!(program.locations[0], None);    // bootstrap code
+        assert_eq!(program.locations[address].as_ref().unwrap(), "Sys.vm:2: push constant 7 (in Sys.init)");
+        assert_eq!(load(&dir.join("Sys.vm")).unwrap().instructions, program.instructions);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,176 @@
+// headless execution of a program, for hack-run
+use machine::Computer;
+use machine::inst::*;
+use machine::keys::KeyScript;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Terminated,     // PC went past the end of the program
+    HaltLoop(i16),  // PC reached a '(LABEL) @LABEL 0;JMP' loop at the address
+    Cycles,         // the number of cycles ran out
+}
+
+impl std::fmt::Display for Stop {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Stop::Terminated => write!(f, "terminated"),
+            Stop::HaltLoop(pc) => write!(f, "halt loop at PC={}", pc),
+            Stop::Cycles => write!(f, "cycle limit reached"),
+        }
+    }
+}
+
+// whether the instructions at the address are '@address' and an unconditional jump, which loop forever
+pub fn is_halt_loop(rom: &[i16], address: i16) -> bool {
+    let inst = |address: i16| rom.get(address as usize).and_then(|&word| Instruction::decode(word).ok());
+    address >= 0 && inst(address) == Some(AInstruction(address)) &&
+        matches!(address.checked_add(1).and_then(inst), Some(CInstruction(_, 0, Jump::JMP)))
+}
+
+// run the program until it stops, or for max_cycles. returns why it stopped and the number of cycles
+pub fn run(computer: &mut dyn Computer, rom: &[i16], max_cycles: u64) -> (Stop, u64) {
+    run_with_keys(computer, rom, max_cycles, &KeyScript::default())
+}
+
+// same as run(), typing the keys of the script
+pub fn run_with_keys(computer: &mut dyn Computer, rom: &[i16], max_cycles: u64, keys: &KeyScript) -> (Stop, u64) {
+    run_with(computer, rom, max_cycles, |cycle, computer| keys.apply(cycle, computer))
+}
+
+// same as run(), calling 'before_clock' with the number of cycles so far before each clock
+pub fn run_with(computer: &mut dyn Computer, rom: &[i16], max_cycles: u64, mut before_clock: impl FnMut(u64, &mut dyn Computer)) -> (Stop, u64) {
+    let mut cycles = 0;
+    loop {
+        if computer.is_terminated() { return (Stop::Terminated, cycles); }
+        let pc = computer.pc();
+        if is_halt_loop(rom, pc) { return (Stop::HaltLoop(pc), cycles); }
+        if cycles == max_cycles { return (Stop::Cycles, cycles); }
+        before_clock(cycles, computer);
+        computer.clock(false);
+        cycles += 1;
+    }
+}
+
+// the last address of the memory (KBD)
+pub const LAST_ADDRESS: i16 = 0x6000;
+
+// a number (decimal or 0x hex) or a predefined symbol such as 'R0', 'SP' and 'SCREEN'
+fn parse_number(s: &str) -> Option<i16> {
+    let predefined = [("SP", 0), ("LCL", 1), ("ARG", 2), ("THIS", 3), ("THAT", 4), ("SCREEN", 0x4000), ("KBD", 0x6000)];
+    if let Some(&(_, address)) = predefined.iter().find(|&&(name, _)| name == s) {
+        return Some(address);
+    }
+    if let Some(r) = s.strip_prefix('R') {
+        return r.parse::<i16>().ok().filter(|r| (0 .. 16).contains(r));
+    }
+    let address = match s.strip_prefix("0x") {
+        Some(hex) => i16::from_str_radix(hex, 16).ok(),
+        None => s.parse::<i16>().ok()
+    };
+    address.filter(|&address| address >= 0)
+}
+
+// a RAM address from 0 to KBD
+pub fn parse_address(s: &str) -> Option<i16> {
+    parse_number(s).filter(|&address| address <= LAST_ADDRESS)
+}
+
+// 'address=value', e.g. 'R0=100' or '0x4000=-1'
+pub fn parse_assignment(s: &str) -> Option<(i16, i16)> {
+    let i = s.find('=')?;
+    let value = s[i + 1 ..].parse::<i16>().ok()?;
+    Some((parse_address(&s[.. i])?, value))
+}
+
+// 'from..to' (exclusive), 'from..=to' or a single address
+pub fn parse_range(s: &str) -> Option<std::ops::RangeInclusive<i16>> {
+    if let Some(i) = s.find("..=") {
+        let (from, to) = (parse_address(&s[.. i])?, parse_address(&s[i + 3 ..])?);
+        return if from <= to { Some(from ..= to) } else { None };
+    }
+    match s.find("..") {
+        Some(i) => {
+            // the exclusive end may be one past KBD
+            let from = parse_address(&s[.. i])?;
+            let to = parse_number(&s[i + 2 ..]).filter(|&to| to <= LAST_ADDRESS + 1)?;
+            if from < to { Some(from ..= to - 1) } else { None }
+        },
+        None => parse_address(s).map(|address| address ..= address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use machine::Emulator;
+    use std::path::Path;
+
+    #[test]
+    fn test_run() {
+        // Rect.asm draws 100 lines, and ends with '(INFINITE_LOOP) @INFINITE_LOOP 0;JMP'
+        let rom = crate::load_program(Path::new("../data/Rect.asm")).unwrap();
+        let mut emulator = Emulator::new(&rom);
+        let (stop, cycles) = run(&mut emulator, &rom, 100000);
+        assert_eq!(stop, Stop::HaltLoop(27));
+        assert!(cycles > 100 * 10);
+        assert_eq!(emulator.screen_image()[32 * 99], -1);
+        assert_eq!(emulator.screen_image()[32 * 100], 0);
+
+        let mut emulator = Emulator::new(&rom);
+        assert_eq!(run(&mut emulator, &rom, 10), (Stop::Cycles, 10));
+
+        let mut emulator = Emulator::new(&rom);
+        let mut screens = Vec::new();
+        run_with(&mut emulator, &rom, 100000, |cycle, computer| if cycle % 500 == 0 { screens.push(computer.screen_image()[32 * 50]) });
+        assert_eq!(screens, vec![0, 0, -1]);
+
+        let rom = asm::asm("@1\nD=A\n").unwrap();
+        assert_eq!(run(&mut Emulator::new(&rom), &rom, 10), (Stop::Terminated, 2));
+    }
+
+    #[test]
+    fn test_halt_loop() {
+        let rom = asm::asm("@1\n(HALT)\n@HALT\n0;JMP\n").unwrap();
+        assert!(is_halt_loop(&rom, 1));
+        assert!(!is_halt_loop(&rom, 0));
+        // '@32767' at the last address of a full ROM has no instruction after it
+        let mut rom = vec![0; 32768];
+        rom[32767] = 32767;
+        assert!(!is_halt_loop(&rom, 32767));
+    }
+
+    #[test]
+    fn test_keys() {
+        // wait for a key, and store it to R0
+        let rom = asm::asm("(WAIT)\n@KBD\nD=M\n@WAIT\nD;JEQ\n@R0\nM=D\n(END)\n@END\n0;JMP\n").unwrap();
+        let keys = KeyScript::parse("100 newline\n").unwrap();
+        let mut emulator = Emulator::new(&rom);
+        assert_eq!(run_with_keys(&mut emulator, &rom, 1000, &keys).0, Stop::HaltLoop(6));
+        assert_eq!(emulator.read_memory(0), 128);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse_address("R15"), Some(15));
+        assert_eq!(parse_address("R16"), None);
+        assert_eq!(parse_address("0x4000"), Some(0x4000));
+        assert_eq!(parse_address("-1"), None);
+        assert_eq!(parse_assignment("R0=100"), Some((0, 100)));
+        assert_eq!(parse_assignment("SP=-1"), Some((0, -1)));
+        assert_eq!(parse_assignment("R0"), None);
+        assert_eq!(parse_address("KBD"), Some(0x6000));
+        assert_eq!(parse_address("0x6001"), None);
+        assert_eq!(parse_address("32767"), None);
+        assert_eq!(parse_assignment("0x7fff=1"), None);
+        assert_eq!(parse_range("256..260"), Some(256 ..= 259));
+        assert_eq!(parse_range("256..=260"), Some(256 ..= 260));
+        assert_eq!(parse_range("THAT"), Some(4 ..= 4));
+        assert_eq!(parse_range("KBD"), Some(0x6000 ..= 0x6000));
+        assert_eq!(parse_range("0x5fff..0x6001"), Some(0x5fff ..= 0x6000));
+        assert_eq!(parse_range("0x5fff..0x6002"), None);
+        assert_eq!(parse_range("0x7fff"), None);
+        assert_eq!(parse_range("32767"), None);
+        assert_eq!(parse_range("5..5"), None);
+        assert_eq!(parse_range("5..4"), None);
+    }
+}
@@ -0,0 +1,542 @@
+// Interpreter of the test scripts (.tst) of the nand2tetris CPU emulator, e.g.
+//
+//   load Max.asm, output-file Max.out, compare-to Max.cmp,
+//   output-list RAM[0]%D2.6.2 RAM[1]%D2.6.2 RAM[2]%D2.6.2;
+//   set RAM[0] 3, set RAM[1] 5;
+//   repeat 14 { ticktock; }
+//   output;
+//
+// the output file is written in the same column format as the official tools,
+// and every output line is compared with the compare file ('*' in the compare file matches any character).
+use machine::{Computer, CpuRegister, Emulator, Machine};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TstError {
+    Script{ line: usize, message: String },                         // syntax or runtime error at a line of the script
+    Comparison{ line: usize, expected: String, actual: String },    // mismatch at a line of the output file
+}
+
+pub type Result<T> = std::result::Result<T, TstError>;
+
+impl std::fmt::Display for TstError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TstError::Script{ line, message } => write!(f, "line {}: {}", line, message),
+            TstError::Comparison{ line, expected, actual } =>
+                write!(f, "comparison failure at line {}\n  expected: {}\n  actual:   {}", line, expected, actual),
+        }
+    }
+}
+
+impl std::error::Error for TstError {}
+
+fn script_error<T>(line: usize, message: impl Into<String>) -> Result<T> {
+    Err(TstError::Script{ line, message: message.into() })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),   // including string literals
+    Terminator,     // ',', ';' or '!'
+    Open,           // '{'
+    Close,          // '}'
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>> {
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => (),
+            ',' | ';' | '!' => tokens.push((Token::Terminator, line)),
+            '{' => tokens.push((Token::Open, line)),
+            '}' => tokens.push((Token::Close, line)),
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.peek().is_some_and(|&c| c != '\n') { chars.next(); }
+            },
+            '/' if chars.peek() == Some(&'*') => {
+                let start = line;
+                chars.next();
+                let mut prev = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if prev == '*' => break,
+                        Some(c) => {
+                            if c == '\n' { line += 1; }
+                            prev = c;
+                        },
+                        None => return script_error(start, "unterminated comment")
+                    }
+                }
+            },
+            '"' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\n') | None => return script_error(line, "unterminated string"),
+                        Some(c) => s.push(c)
+                    }
+                }
+                tokens.push((Token::Word(s), line));
+            },
+            c => {
+                let mut s = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || ",;!{}\"".contains(c) { break; }
+                    s.push(c);
+                    chars.next();
+                }
+                tokens.push((Token::Word(s), line));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Statement {
+    Command{ words: Vec<String>, line: usize },
+    Repeat{ count: usize, body: Vec<Statement>, line: usize },
+    While{ condition: Vec<String>, body: Vec<Statement>, line: usize },
+}
+
+fn parse_block(tokens: &mut std::iter::Peekable<std::vec::IntoIter<(Token, usize)>>, nested: Option<usize>) -> Result<Vec<Statement>> {
+    let mut statements = Vec::new();
+    loop {
+        let (token, line) = match tokens.next() {
+            Some(token) => token,
+            None => match nested {
+                Some(line) => return script_error(line, "missing '}'"),
+                None => return Ok(statements)
+            }
+        };
+        match token {
+            Token::Close if nested.is_some() => return Ok(statements),
+            Token::Close | Token::Open => return script_error(line, "unexpected brace"),
+            Token::Terminator => (),
+            Token::Word(word) if word == "repeat" || word == "while" => {
+                let mut header = Vec::new();
+                loop {
+                    match tokens.next() {
+                        Some((Token::Word(word), _)) => header.push(word),
+                        Some((Token::Open, _)) => break,
+                        _ => return script_error(line, "missing '{'")
+                    }
+                }
+                let body = parse_block(tokens, Some(line))?;
+                if word == "repeat" {
+                    let count = match header.as_slice() {
+                        [count] => count.parse::<usize>().or_else(|_| script_error(line, format!("invalid repeat count '{}'", count)))?,
+                        _ => return script_error(line, "repeat needs a count")
+                    };
+                    statements.push(Statement::Repeat{ count, body, line });
+                } else {
+                    statements.push(Statement::While{ condition: header, body, line });
+                }
+            },
+            Token::Word(word) => {
+                let mut words = vec![word];
+                while let Some((Token::Word(_), _)) = tokens.peek() {
+                    if let Some((Token::Word(word), _)) = tokens.next() { words.push(word); }
+                }
+                statements.push(Statement::Command{ words, line });
+            }
+        }
+    }
+}
+
+fn parse(source: &str) -> Result<Vec<Statement>> {
+    parse_block(&mut tokenize(source)?.into_iter().peekable(), None)
+}
+
+// a column of output-list, e.g. 'RAM[0]%D2.6.2' (format, left padding, width, right padding)
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Column {
+    name: String,
+    format: char,
+    left: usize,
+    width: usize,
+    right: usize,
+}
+
+impl Column {
+    fn parse(spec: &str, line: usize) -> Result<Self> {
+        let (name, format) = match spec.find('%') {
+            Some(i) => (&spec[..i], &spec[i + 1 ..]),
+            None => (spec, "B1.16.1")
+        };
+        let invalid = || script_error(line, format!("invalid output format '{}'", spec));
+        let mut chars = format.chars();
+        let kind = match chars.next() {
+            Some(c) if "BDXS".contains(c) => c,
+            _ => return invalid()
+        };
+        let numbers = chars.as_str().split('.').map(|s| s.parse::<usize>()).collect::<std::result::Result<Vec<_>, _>>();
+        match numbers.as_deref() {
+            Ok(&[left, width, right]) => Ok(Self{ name: name.to_string(), format: kind, left, width, right }),
+            _ => invalid()
+        }
+    }
+
+    // the name is centered in the column
+    fn header(&self) -> String {
+        let space = self.left + self.width + self.right;
+        let name = self.name.chars().take(space).collect::<String>();
+        let left = (space - name.chars().count()) / 2;
+        let right = space - name.chars().count() - left;
+        format!("{}{}{}", " ".repeat(left), name, " ".repeat(right))
+    }
+
+    fn cell(&self, value: i16) -> String {
+        let width = self.width;
+        let last = |s: String| s.chars().skip(s.chars().count().saturating_sub(width)).collect::<String>();
+        let body = match self.format {
+            'D' => format!("{:>width$}", value, width = width),
+            'X' => last(format!("{:0width$X}", value as u16, width = width)),
+            'B' => last(format!("{:0width$b}", value as u16, width = width)),
+            _   => format!("{:<width$}", value, width = width),
+        };
+        format!("{}{}{}", " ".repeat(self.left), body, " ".repeat(self.right))
+    }
+}
+
+// '*' in the compare file matches any character
+fn matches(expected: &str, actual: &str) -> bool {
+    let (expected, actual) = (expected.trim_end(), actual.trim_end());
+    expected.chars().count() == actual.chars().count() &&
+        expected.chars().zip(actual.chars()).all(|(e, a)| e == '*' || e == a)
+}
+
+// value of 'set', e.g. '-1', '%X7FFF', '%B0101' or '%D100'
+fn parse_value(s: &str) -> Option<i16> {
+    let (radix, digits) = match s.get(..2) {
+        Some("%X") => (16, &s[2..]),
+        Some("%B") => (2, &s[2..]),
+        Some("%D") => (10, &s[2..]),
+        _ => (10, s)
+    };
+    match i32::from_str_radix(digits, radix) {
+        Ok(n) if (-32768 ..= 65535).contains(&n) => Some(n as i16),
+        _ => None
+    }
+}
+
+// 'RAM[16]' => ("RAM", Some(16))
+fn parse_variable(s: &str) -> (&str, Option<i16>) {
+    match (s.find('['), s.strip_suffix(']')) {
+        (Some(i), Some(rest)) => (&s[..i], rest[i + 1 ..].parse::<i16>().ok()),
+        _ => (s, None)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub output: String,                 // contents of the output file
+    pub output_file: Option<PathBuf>,
+    pub compared: bool,                 // whether the output was compared with a compare file
+    pub messages: Vec<String>,          // texts of 'echo'
+}
+
+struct Runner {
+    dir: PathBuf,
+    fast: bool,
+    program: Vec<i16>,
+    computer: Option<Box<dyn Computer>>,
+    time: i16,
+    columns: Vec<Column>,
+    output_lines: usize,
+    compare_lines: Option<Vec<String>>,
+    report: Report,
+}
+
+impl Runner {
+    fn computer(&mut self, line: usize) -> Result<&mut dyn Computer> {
+        match &mut self.computer {
+            Some(computer) => Ok(computer.as_mut()),
+            None => script_error(line, "no program is loaded")
+        }
+    }
+
+    fn get(&mut self, name: &str, line: usize) -> Result<i16> {
+        if name == "time" { return Ok(self.time); }
+        if let Some(value) = parse_value(name) { return Ok(value); }
+        let program = &self.program;
+        let rom = |address: i16| program.get(address as usize).cloned().unwrap_or(0);
+        let value = match parse_variable(name) {
+            ("ROM", Some(address)) if address >= 0 => rom(address),
+            ("RAM", Some(address)) => self.computer(line)?.read_memory(address),
+            ("A", None) => self.computer(line)?.state().a,
+            ("D", None) => self.computer(line)?.state().d,
+            ("PC", None) => self.computer(line)?.state().pc,
+            _ => return script_error(line, format!("unknown variable '{}'", name))
+        };
+        Ok(value)
+    }
+
+    fn set(&mut self, name: &str, value: &str, line: usize) -> Result<()> {
+        let value = match parse_value(value) {
+            Some(value) => value,
+            None => return script_error(line, format!("invalid value '{}'", value))
+        };
+        let computer = self.computer(line)?;
+        match parse_variable(name) {
+            ("RAM", Some(address)) => computer.write_memory(address, value),
+            ("A", None) => computer.set_register(CpuRegister::A, value),
+            ("D", None) => computer.set_register(CpuRegister::D, value),
+            ("PC", None) => computer.set_register(CpuRegister::PC, value),
+            _ => return script_error(line, format!("cannot set '{}'", name))
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, text: String) -> Result<()> {
+        self.output_lines += 1;
+        self.report.output.push_str(&text);
+        self.report.output.push('\n');
+        if let Some(compare_lines) = &self.compare_lines {
+            let expected = compare_lines.get(self.output_lines - 1).cloned().unwrap_or_default();
+            if !matches(&expected, &text) {
+                return Err(TstError::Comparison{ line: self.output_lines, expected, actual: text });
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&self, words: &[String], line: usize) -> Result<PathBuf> {
+        match words {
+            [_, filename] => Ok(self.dir.join(filename)),
+            _ => script_error(line, format!("'{}' needs a file name", words[0]))
+        }
+    }
+
+    fn command(&mut self, words: &[String], line: usize) -> Result<()> {
+        match words[0].as_str() {
+            "load" => {
+                let path = self.path(words, line)?;
+                self.program = crate::load_program(&path).or_else(|e| script_error(line, e))?;
+                self.computer = Some(if self.fast {
+                    Box::new(Emulator::new(&self.program))
+                } else {
+                    Box::new(Machine::new(&self.program))
+                });
+                self.time = 0;
+            },
+            "output-file" => {
+                self.report.output_file = Some(self.path(words, line)?);
+            },
+            "compare-to" => {
+                let path = self.path(words, line)?;
+                let source = std::fs::read_to_string(&path).or_else(|e| script_error(line, format!("{}: {}", path.display(), e)))?;
+                self.compare_lines = Some(source.lines().map(|s| s.to_string()).collect());
+                self.report.compared = true;
+            },
+            "output-list" => {
+                self.columns = words[1..].iter().map(|spec| Column::parse(spec, line)).collect::<Result<Vec<_>>>()?;
+                let header = self.columns.iter().map(|column| column.header() + "|").collect::<String>();
+                self.emit(format!("|{}", header))?;
+            },
+            "output" => {
+                let mut text = "|".to_string();
+                for column in self.columns.clone() {
+                    let value = self.get(&column.name, line)?;
+                    text += &column.cell(value);
+                    text += "|";
+                }
+                self.emit(text)?;
+            },
+            "set" => match words {
+                [_, name, value] => self.set(name, value, line)?,
+                _ => return script_error(line, "usage: set <variable> <value>")
+            },
+            "ticktock" | "tock" => {
+                self.computer(line)?.clock(false);
+                self.time = self.time.wrapping_add(1);
+            },
+            "tick" | "clear-echo" => (),
+            "echo" => self.report.messages.push(words[1..].join(" ")),
+            command => return script_error(line, format!("unknown command '{}'", command))
+        }
+        Ok(())
+    }
+
+    fn condition(&mut self, condition: &[String], line: usize) -> Result<bool> {
+        let (lhs, op, rhs) = match condition {
+            [lhs, op, rhs] => (self.get(lhs, line)?, op.as_str(), self.get(rhs, line)?),
+            _ => return script_error(line, "invalid condition")
+        };
+        let result = match op {
+            "="  => lhs == rhs,
+            "<>" => lhs != rhs,
+            "<"  => lhs <  rhs,
+            ">"  => lhs >  rhs,
+            "<=" => lhs <= rhs,
+            ">=" => lhs >= rhs,
+            _ => return script_error(line, format!("unknown operator '{}'", op))
+        };
+        Ok(result)
+    }
+
+    fn execute(&mut self, statements: &[Statement]) -> Result<()> {
+        for statement in statements {
+            match statement {
+                Statement::Command{ words, line } => self.command(words, *line)?,
+                Statement::Repeat{ count, body, .. } => {
+                    for _ in 0 .. *count { self.execute(body)?; }
+                },
+                Statement::While{ condition, body, line } => {
+                    while self.condition(condition, *line)? { self.execute(body)?; }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// run a test script. files in the script are relative to 'dir'.
+// the output file is written even if the script fails.
+pub fn run(source: &str, dir: &Path, fast: bool) -> Result<Report> {
+    let statements = parse(source)?;
+    let mut runner = Runner{
+        dir: dir.to_path_buf(),
+        fast,
+        program: Vec::new(),
+        computer: None,
+        time: 0,
+        columns: Vec::new(),
+        output_lines: 0,
+        compare_lines: None,
+        report: Report{ output: String::new(), output_file: None, compared: false, messages: Vec::new() }
+    };
+    let result = runner.execute(&statements);
+    if let Some(path) = &runner.report.output_file {
+        if let Err(e) = std::fs::write(path, &runner.report.output) {
+            return script_error(0, format!("{}: {}", path.display(), e));
+        }
+    }
+    result.map(|_| runner.report)
+}
+
+pub fn run_file(path: &Path, fast: bool) -> Result<Report> {
+    let source = std::fs::read_to_string(path).or_else(|e| script_error(0, format!("{}: {}", path.display(), e)))?;
+    run(&source, path.parent().unwrap_or_else(|| Path::new(".")), fast)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_ASM: &str = "
+        @R0
+        D=M
+        @R1
+        D=D-M
+        @FIRST
+        D;JGT
+        @R1
+        D=M
+        @OUTPUT
+        0;JMP
+    (FIRST)
+        @R0
+        D=M
+    (OUTPUT)
+        @R2
+        M=D
+    (END)
+        @END
+        0;JMP
+    ";
+
+    const MAX_TST: &str = "
+        // comment
+        load Max.asm,
+        output-file Max.out,
+        compare-to Max.cmp,
+        output-list RAM[0]%D2.6.2 RAM[1]%D2.6.2 RAM[2]%D2.6.2;
+
+        set RAM[0] 0, set RAM[1] 0;
+        repeat 14 { ticktock; }
+        output;
+
+        set PC 0,
+        set RAM[0] 1,   /* block
+                           comment */
+        set RAM[1] -2;
+        repeat 14 {
+            ticktock;
+        }
+        output;
+
+        set PC 0, set RAM[0] %X7FFF, set RAM[1] 5;
+        while PC <> 14 { ticktock; }
+        output;
+    ";
+
+    const MAX_CMP: &str = "\
+|  RAM[0]  |  RAM[1]  |  RAM[2]  |
+|       0  |       0  |       0  |
+|       1  |      -2  |       1  |
+|   32767  |       5  |   *****  |
+";
+
+    // a directory with Max.asm for each test and process
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Max.asm"), MAX_ASM).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_max() {
+        let dir = temp_dir("tools_test_tst_max");
+        std::fs::write(dir.join("Max.cmp"), MAX_CMP).unwrap();
+        std::fs::write(dir.join("Max.tst"), MAX_TST).unwrap();
+        let _ = std::fs::remove_file(dir.join("Max.out"));
+        let report = run_file(&dir.join("Max.tst"), false).unwrap();
+        assert!(report.compared);
+        assert_eq!(report.output, MAX_CMP.replace("*****", "32767"));
+        assert_eq!(std::fs::read_to_string(dir.join("Max.out")).unwrap(), report.output);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_comparison_failure() {
+        let dir = temp_dir("tools_test_tst_failure");
+        std::fs::write(dir.join("Max.cmp"), MAX_CMP.replace("|       1  |      -2  |       1  |", "|       1  |      -2  |      -2  |")).unwrap();
+        let error = run(MAX_TST, &dir, true).unwrap_err();
+        assert_eq!(error, TstError::Comparison{
+            line: 3,
+            expected: "|       1  |      -2  |      -2  |".to_string(),
+            actual: "|       1  |      -2  |       1  |".to_string()
+        });
+        // the output until the failure is written
+        assert_eq!(std::fs::read_to_string(dir.join("Max.out")).unwrap().lines().count(), 3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_formats() {
+        let column = |spec| Column::parse(spec, 1).unwrap();
+        assert_eq!(column("A%X1.4.1").cell(-1), " FFFF ");
+        assert_eq!(column("D%B0.4.0").cell(6), "0110");
+        assert_eq!(column("PC").cell(5), " 0000000000000101 ");
+        assert_eq!(column("time%S0.4.0").cell(3), "3   ");
+        assert_eq!(column("DRegister[]%D1.6.1").header(), "DRegiste");
+        assert_eq!(column("PC%D0.5.0").header(), " PC  ");
+        assert!(Column::parse("A%Q1.2.3", 1).is_err());
+    }
+
+    #[test]
+    fn test_script_errors() {
+        let dir = std::env::temp_dir();
+        assert_eq!(run("set A 1;", &dir, true), script_error(1, "no program is loaded"));
+        assert_eq!(run("\nrepeat 3 { tick;", &dir, true), script_error(2, "missing '}'"));
+        assert_eq!(run("foo;", &dir, true), script_error(1, "unknown command 'foo'"));
+        assert_eq!(run("echo \"hello world\";", &dir, true).unwrap().messages, vec!["hello world"]);
+    }
+}
@@ -0,0 +1,47 @@
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VmErrorKind {
+    UnknownCommand,
+    UnknownSegment,
+    MissingOperand,
+    UnexpectedOperand,
+    InvalidNumber,
+    NegativeIndex,
+    IndexOutOfRange{ max: i16 },    // for 'temp' and 'pointer', and the number of arguments of 'call'
+    PopConstant,
+    UndefinedFunction,              // called but not defined in any file
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmError {
+    pub kind: VmErrorKind,
+    pub filename: String,
+    pub line: usize,    // 1-origin
+    pub column: usize,  // 1-origin
+    pub token: String,  // the offending token
+}
+
+pub type Result<T> = std::result::Result<T, VmError>;
+
+impl std::fmt::Display for VmErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            VmErrorKind::UnknownCommand => write!(f, "unknown command"),
+            VmErrorKind::UnknownSegment => write!(f, "unknown segment"),
+            VmErrorKind::MissingOperand => write!(f, "missing operand"),
+            VmErrorKind::UnexpectedOperand => write!(f, "unexpected operand"),
+            VmErrorKind::InvalidNumber => write!(f, "invalid number"),
+            VmErrorKind::NegativeIndex => write!(f, "negative index"),
+            VmErrorKind::IndexOutOfRange{ max } => write!(f, "index out of range 0..{}", max),
+            VmErrorKind::PopConstant => write!(f, "cannot pop to constant segment"),
+            VmErrorKind::UndefinedFunction => write!(f, "undefined function"),
+        }
+    }
+}
+
+impl std::fmt::Display for VmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}:{}: {}: '{}'", self.filename, self.line, self.column, self.kind, self.token)
+    }
+}
+
+impl std::error::Error for VmError {}
@@ -1,5 +1,9 @@
 mod writer;
+mod error;
+mod source_map;
 use writer::*;
+pub use error::*;
+pub use source_map::*;
 
 #[derive(Debug)]
 enum Command<'a> {
@@ -11,8 +15,8 @@ enum Command<'a> {
     LogicalOp(Condition), // eq, gt, lt
 
     // memory access commands
-    Push{ segment: &'a str, index: i16 },
-    Pop { segment: &'a str, index: i16 },
+    Push{ segment: Segment, index: i16 },
+    Pop { segment: Segment, index: i16 },
 
     // program flow commands
     Label(&'a str),
@@ -25,48 +29,102 @@ enum Command<'a> {
     Return
 }
 
-fn line_to_command(line: &str) -> Command {
-    assert!(!line.is_empty());
-    let tokens = line.split_whitespace().collect::<Vec<_>>();
-    assert!(tokens.len() != 0);
-    match tokens[0] {
-        "neg" | "not" => {
-            let op = match tokens[0] {
-                "neg" => UnaryOp::Neg,
-                "not" => UnaryOp::Not,
-                _ => panic!("unknown unary operation: {}", tokens[0])
-            };
-            Command::UnaryOp(op)
-        },
-        "add" | "sub" | "and" | "or" => {
-            let op = match tokens[0] {
-                "add" => BinaryOp::Add,
-                "sub" => BinaryOp::Sub,
-                "and" => BinaryOp::And,
-                "or"  => BinaryOp::Or,
-                _ => panic!("unknown binary operation: {}", tokens[0])
-            };
-            Command::BinaryOp(op)
+// a non-empty line of VM source split into tokens with their columns
+struct Line<'a> {
+    filename: &'a str,
+    number: usize,
+    tokens: Vec<(usize, &'a str)>,
+}
+
+impl<'a> Line<'a> {
+    fn new(filename: &'a str, number: usize, line: &'a str) -> Self {
+        // column of a token is computed from its offset in the line
+        let tokens = line.split_whitespace()
+            .map(|token| (token.as_ptr() as usize - line.as_ptr() as usize + 1, token))
+            .collect();
+        Self{ filename, number, tokens }
+    }
+    fn error(&self, kind: VmErrorKind, i: usize) -> VmError {
+        let (column, token) = self.tokens[i];
+        VmError{ kind, filename: self.filename.to_string(), line: self.number, column, token: token.to_string() }
+    }
+    fn operand(&self, i: usize) -> Result<&'a str> {
+        match self.tokens.get(i) {
+            Some(&(_, token)) => Ok(token),
+            None => Err(self.error(VmErrorKind::MissingOperand, 0))
+        }
+    }
+    fn number(&self, i: usize) -> Result<i16> {
+        let n = self.operand(i)?.parse::<i16>().map_err(|_| self.error(VmErrorKind::InvalidNumber, i))?;
+        if n < 0 { Err(self.error(VmErrorKind::NegativeIndex, i)) } else { Ok(n) }
+    }
+    // the number of arguments of 'call', which is added to the 5 words of the frame
+    fn nargs(&self) -> Result<i16> {
+        let nargs = self.number(2)?;
+        let max = i16::MAX - 5;
+        if nargs > max { Err(self.error(VmErrorKind::IndexOutOfRange{ max }, 2)) } else { Ok(nargs) }
+    }
+    fn segment(&self, i: usize) -> Result<Segment> {
+        let segment = match self.operand(i)? {
+            "constant"  => Segment::Constant,
+            "static"    => Segment::Static,
+            "argument"  => Segment::Argument,
+            "local"     => Segment::Local,
+            "this"      => Segment::This,
+            "that"      => Segment::That,
+            "pointer"   => Segment::Pointer,
+            "temp"      => Segment::Temp,
+            _ => return Err(self.error(VmErrorKind::UnknownSegment, i))
+        };
+        Ok(segment)
+    }
+    fn segment_index(&self) -> Result<(Segment, i16)> {
+        let segment = self.segment(1)?;
+        let index = self.number(2)?;
+        let max = match segment {
+            Segment::Pointer => 1,
+            Segment::Temp => 7,
+            _ => i16::MAX
+        };
+        if index > max { Err(self.error(VmErrorKind::IndexOutOfRange{ max }, 2)) } else { Ok((segment, index)) }
+    }
+}
+
+fn line_to_command<'a>(line: &Line<'a>) -> Result<Command<'a>> {
+    assert!(!line.tokens.is_empty());
+    let (command, nargs) = match line.tokens[0].1 {
+        "neg" => (Command::UnaryOp(UnaryOp::Neg), 0),
+        "not" => (Command::UnaryOp(UnaryOp::Not), 0),
+        "add" => (Command::BinaryOp(BinaryOp::Add), 0),
+        "sub" => (Command::BinaryOp(BinaryOp::Sub), 0),
+        "and" => (Command::BinaryOp(BinaryOp::And), 0),
+        "or"  => (Command::BinaryOp(BinaryOp::Or), 0),
+        "eq"  => (Command::LogicalOp(Condition::Eq), 0),
+        "gt"  => (Command::LogicalOp(Condition::Gt), 0),
+        "lt"  => (Command::LogicalOp(Condition::Lt), 0),
+        "push" => {
+            let (segment, index) = line.segment_index()?;
+            (Command::Push{ segment, index }, 2)
         },
-        "eq" | "gt" | "lt" => {
-            let cond = match tokens[0] {
-                "eq" => Condition::Eq,
-                "gt" => Condition::Gt,
-                "lt" => Condition::Lt,
-                _ => panic!("unknown logical operation: {}", tokens[0])
-            };
-            Command::LogicalOp(cond)
+        "pop" => {
+            let (segment, index) = line.segment_index()?;
+            if let Segment::Constant = segment {
+                return Err(line.error(VmErrorKind::PopConstant, 1));
+            }
+            (Command::Pop{ segment, index }, 2)
         },
-        "push"      => Command::Push{ segment: tokens[1], index: tokens[2].parse::<i16>().unwrap() },
-        "pop"       => Command::Pop{ segment: tokens[1], index: tokens[2].parse::<i16>().unwrap() },
-        "label"     => Command::Label(tokens[1]),
-        "if-goto"   => Command::IfGoto(tokens[1]),
-        "goto"      => Command::Goto(tokens[1]),
-        "function"  => Command::Function{ funcname: tokens[1], nlocals: tokens[2].parse::<i16>().unwrap() },
-        "call"      => Command::Call{ funcname: tokens[1], nargs: tokens[2].parse::<i16>().unwrap() },
-        "return"    => Command::Return,
-        _ => panic!("unknown command: {}", line)
+        "label"     => (Command::Label(line.operand(1)?), 1),
+        "if-goto"   => (Command::IfGoto(line.operand(1)?), 1),
+        "goto"      => (Command::Goto(line.operand(1)?), 1),
+        "function"  => (Command::Function{ funcname: line.operand(1)?, nlocals: line.number(2)? }, 2),
+        "call"      => (Command::Call{ funcname: line.operand(1)?, nargs: line.nargs()? }, 2),
+        "return"    => (Command::Return, 0),
+        _ => return Err(line.error(VmErrorKind::UnknownCommand, 0))
+    };
+    if line.tokens.len() > nargs + 1 {
+        return Err(line.error(VmErrorKind::UnexpectedOperand, nargs + 1));
     }
+    Ok(command)
 }
 
 fn translate_command(out: &mut AsmWriter, command: Command) {
@@ -91,14 +149,17 @@ fn translate_command(out: &mut AsmWriter, command: Command) {
             out.pop_segment(segment, index);
         },
         Command::Label(symbol) => {
-            out.label(symbol);
+            let label = out.function_label(symbol);
+            out.label(&label);
         },
         Command::Goto(symbol) => {
-            out.goto(symbol);
+            let label = out.function_label(symbol);
+            out.goto(&label);
         },
         Command::IfGoto(symbol) => {
+            let label = out.function_label(symbol);
             out.pop();
-            out.if_goto(symbol);
+            out.if_goto(&label);
         },
         Command::Function{ funcname, nlocals } => {
             out.func_begin(funcname, nlocals);
@@ -113,22 +174,109 @@ fn translate_command(out: &mut AsmWriter, command: Command) {
     //out.write(&format!("// </{:?}>", command));
 }
 
-fn translate_vm_source(out: &mut AsmWriter, source: &str) {
-    let commands = source.split("\n")
+// non-empty lines of VM source without comments
+fn source_lines<'a>(filename: &'a str, source: &'a str) -> impl Iterator<Item = Line<'a>> {
+    source.split("\n")
         .map(|line| if let Some(i) = line.find("//") { &line[..i] } else { line })  // remove comment
-        .map(|line| line.trim())  // remove white spaces of head and tail
-        .filter(|line| !line.is_empty())    // filter empty line
-        .map(line_to_command)
-        .collect::<Vec<_>>();
-    for command in commands {
+        .enumerate()
+        .map(move |(i, line)| Line::new(filename, i + 1, line))
+        .filter(|line| !line.tokens.is_empty())    // filter empty line
+}
+
+fn translate_vm_source(out: &mut AsmWriter, filename: &str, source: &str, source_map: &mut SourceMap) -> Result<()> {
+    let commands = source_lines(filename, source)
+        .map(|line| line_to_command(&line).map(|command| (line, command)))
+        .collect::<Result<Vec<_>>>()?;
+    for (line, command) in commands {
+        let asm_line = out.next_line();
         translate_command(out, command);
+        source_map.push(VmLocation{
+            asm_line,
+            filename: filename.to_string(),
+            line: line.number,
+            function: out.funcname().map(|s| s.to_string()),
+            command: line.tokens.iter().map(|(_, token)| *token).collect::<Vec<_>>().join(" ")
+        });
     }
+    Ok(())
 }
 
-pub fn compile(out: &mut std::fmt::Write, source_filename: &str, source: &str) {
+pub fn compile(out: &mut dyn std::fmt::Write, source_filename: &str, source: &str) -> Result<()> {
     let mut out = AsmWriter::new(out, source_filename);
-    let _ = out.call_sys_init();
-    translate_vm_source(&mut out, source);
+    out.call_sys_init();
+    translate_vm_source(&mut out, source_filename, source, &mut SourceMap::default())
+}
+
+// a VM source file of a program which consists of multiple files (Main.vm, Sys.vm, ...)
+pub struct VmModule {
+    pub name: String,   // file name without extension, used as the namespace of static variables
+    pub source: String,
+}
+
+// read all .vm files in a directory (sorted by file name)
+pub fn read_dir(dir: &std::path::Path) -> std::io::Result<Vec<VmModule>> {
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    paths.retain(|path| path.extension().and_then(|s| s.to_str()) == Some("vm"));
+    paths.sort();
+    paths.iter().map(|path| {
+        let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string();
+        let source = std::fs::read_to_string(path)?;
+        Ok(VmModule{ name, source })
+    }).collect()
+}
+
+// link multiple VM files into a single program. the bootstrap code is emitted only once.
+pub fn compile_modules(out: &mut dyn std::fmt::Write, modules: &[VmModule]) -> Result<()> {
+    compile_modules_with_source_map(out, modules).map(|_| ())
+}
+
+// same as compile_modules(), and also returns which VM command produced each asm line
+pub fn compile_modules_with_source_map(out: &mut dyn std::fmt::Write, modules: &[VmModule]) -> Result<SourceMap> {
+    translate_modules(out, modules, true)
+}
+
+// same as compile_modules(), but without the bootstrap code (SP = 256, call Sys.init).
+// the program starts from the first command, like the tests of the course which set SP by themselves.
+pub fn compile_modules_without_bootstrap(out: &mut dyn std::fmt::Write, modules: &[VmModule]) -> Result<()> {
+    translate_modules(out, modules, false).map(|_| ())
+}
+
+fn translate_modules(out: &mut dyn std::fmt::Write, modules: &[VmModule], bootstrap: bool) -> Result<SourceMap> {
+    let mut source_map = SourceMap::default();
+    let mut out = AsmWriter::new(out, "");
+    if bootstrap { out.call_sys_init(); }
+    for module in modules {
+        out.set_filename(&module.name);
+        translate_vm_source(&mut out, &format!("{}.vm", module.name), &module.source, &mut source_map)?;
+    }
+    Ok(source_map)
+}
+
+// the first call of a function which is not defined in any of the modules, such as Sys.init called by the bootstrap code.
+// the assembler would take the label of such a function for a variable, and the program would jump into garbage.
+pub fn check_calls(modules: &[VmModule], bootstrap: bool) -> Result<()> {
+    let filenames = modules.iter().map(|module| format!("{}.vm", module.name)).collect::<Vec<_>>();
+    let mut defined = std::collections::HashSet::new();
+    let mut calls = Vec::new();
+    for (module, filename) in modules.iter().zip(&filenames) {
+        for line in source_lines(filename, &module.source) {
+            match line_to_command(&line)? {
+                Command::Function{ funcname, .. } => { defined.insert(funcname); },
+                Command::Call{ funcname, .. } => calls.push((funcname, line.error(VmErrorKind::UndefinedFunction, 1))),
+                _ => ()
+            }
+        }
+    }
+    if bootstrap && !defined.contains("Sys.init") {
+        let kind = VmErrorKind::UndefinedFunction;
+        return Err(VmError{ kind, filename: "(bootstrap)".to_string(), line: 1, column: 1, token: "Sys.init".to_string() });
+    }
+    match calls.into_iter().find(|(funcname, _)| !defined.contains(funcname)) {
+        Some((_, error)) => Err(error),
+        None => Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -165,7 +313,7 @@ mod tests {
             out.set_ram("ARG", 400);
             out.set_ram("THIS", 3000);
             out.set_ram("THAT", 3010);
-            translate_vm_source(&mut out, vm_source);
+            translate_vm_source(&mut out, "test_file", vm_source, &mut SourceMap::default()).unwrap();
         }
         let max_clock = 1000;
         assert_eq!(expected, run_machine(&asm_source, max_clock));
@@ -173,7 +321,7 @@ mod tests {
 
     fn test2(expected: i16, vm_source: &str) {
         let mut asm_source = String::new();
-        compile(&mut asm_source, "test_file", &vm_source);
+        compile(&mut asm_source, "test_file", &vm_source).unwrap();
         let max_clock = 1000;
         assert_eq!(expected, run_machine(&asm_source, max_clock));
     }
@@ -425,6 +573,170 @@ mod tests {
         ");
     }
 
+    #[test]
+    fn function_label() {
+        test2(11, "
+        function Sys.init 0
+            push constant 3
+            call Foo.bar 1
+            return
+        function Foo.bar 0
+            push argument 0
+            label LOOP      // this label is 'Foo.bar$LOOP'
+            push constant 1
+            add
+            push constant 0
+            if-goto LOOP
+            push constant 7
+            add
+            return
+        ");
+    }
+
+    fn compile_modules_to_string(modules: &[(&str, &str)]) -> String {
+        let modules = modules.iter()
+            .map(|&(name, source)| VmModule{ name: name.to_string(), source: source.to_string() })
+            .collect::<Vec<_>>();
+        let mut asm_source = String::new();
+        compile_modules(&mut asm_source, &modules).unwrap();
+        asm_source
+    }
+
+    #[test]
+    fn without_bootstrap() {
+        // like StackTest.vm, which is tested with SP = 256 set by the test script
+        let modules = [VmModule{ name: "StackTest".to_string(), source: "push constant 17\npush constant 17\neq\n".to_string() }];
+        let mut asm_source = String::new();
+        compile_modules_without_bootstrap(&mut asm_source, &modules).unwrap();
+        assert!(!asm_source.contains("@Sys.init\n"));
+        let mut machine = Machine::new(&asm::asm(&asm_source).unwrap());
+        machine.write_memory(0, 256);
+        while !machine.is_terminated() { machine.clock(false); }
+        assert_eq!(machine.read_memory(0), 257);
+        assert_eq!(machine.read_memory(256), -1);
+    }
+
+    #[test]
+    fn static_namespace() {
+        let asm_source = compile_modules_to_string(&[
+            ("Class1", "function Class1.set 0\npush argument 0\npop static 0\npush constant 0\nreturn"),
+            ("Class2", "function Class2.set 0\npush argument 0\npop static 0\npush constant 0\nreturn"),
+        ]);
+        assert!(asm_source.contains("@Class1.0\n"));
+        assert!(asm_source.contains("@Class2.0\n"));
+        assert_eq!(asm_source.matches("@Sys.init\n").count(), 1);
+    }
+
+    #[test]
+    fn statics_test() {
+        let asm_source = compile_modules_to_string(&[
+            ("Class1", "
+            function Class1.set 0
+                push argument 0
+                pop static 0
+                push constant 0
+                return
+            function Class1.get 0
+                push static 0
+                return
+            "),
+            ("Class2", "
+            function Class2.set 0
+                push argument 0
+                pop static 0
+                push constant 0
+                return
+            function Class2.get 0
+                push static 0
+                return
+            "),
+            ("Sys", "
+            function Sys.init 0
+                push constant 6
+                call Class1.set 1
+                pop temp 0
+                push constant 23
+                call Class2.set 1
+                pop temp 0
+                call Class1.get 0
+                call Class2.get 0
+                sub
+                return
+            "),
+        ]);
+        assert_eq!(6 - 23, run_machine(&asm_source, 2000));
+    }
+
+    #[test]
+    fn source_map() {
+        let modules = [
+            ("Main", "function Main.main 0\n  push constant 1234   // comment\nreturn"),
+            ("Sys", "function Sys.init 0\ncall Main.main 0\nreturn"),
+        ].iter().map(|&(name, source)| VmModule{ name: name.to_string(), source: source.to_string() }).collect::<Vec<_>>();
+        let mut asm_source = String::new();
+        let source_map = compile_modules_with_source_map(&mut asm_source, &modules).unwrap();
+        let (instructions, debug_info) = asm::asm_with_debug_info(&asm_source).unwrap();
+        let location = |address: usize| source_map.find(debug_info[address].line);
+
+        // bootstrap code
+        assert_eq!(location(0), None);
+
+        // the block of 'push constant 1234' begins with '@1234'
+        let address = instructions.iter().position(|&inst| inst == 1234).unwrap();
+        assert_eq!(location(address), Some(&VmLocation{
+            asm_line: debug_info[address].line,
+            filename: "Main.vm".to_string(),
+            line: 2,
+            function: Some("Main.main".to_string()),
+            command: "push constant 1234".to_string()
+        }));
+        assert_eq!(location(address + 1).unwrap().to_string(), "Main.vm:2: push constant 1234 (in Main.main)");
+        assert_eq!(location(instructions.len() - 1).unwrap().to_string(), "Sys.vm:3: return (in Sys.init)");
+    }
+
+    fn compile_error(vm_source: &str) -> VmError {
+        let mut asm_source = String::new();
+        compile(&mut asm_source, "Foo.vm", vm_source).unwrap_err()
+    }
+
+    fn error(kind: VmErrorKind, line: usize, column: usize, token: &str) -> VmError {
+        VmError{ kind, filename: "Foo.vm".to_string(), line, column, token: token.to_string() }
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(compile_error("push constant 1\n  foo 1"), error(VmErrorKind::UnknownCommand, 2, 3, "foo"));
+        assert_eq!(compile_error("push local"), error(VmErrorKind::MissingOperand, 1, 1, "push"));
+        assert_eq!(compile_error("goto // comment"), error(VmErrorKind::MissingOperand, 1, 1, "goto"));
+        assert_eq!(compile_error("push heap 0"), error(VmErrorKind::UnknownSegment, 1, 6, "heap"));
+        assert_eq!(compile_error("push local x"), error(VmErrorKind::InvalidNumber, 1, 12, "x"));
+        assert_eq!(compile_error("push local -1"), error(VmErrorKind::NegativeIndex, 1, 12, "-1"));
+        assert_eq!(compile_error("pop temp 8"), error(VmErrorKind::IndexOutOfRange{ max: 7 }, 1, 10, "8"));
+        assert_eq!(compile_error("\n\n\tpush pointer 2"), error(VmErrorKind::IndexOutOfRange{ max: 1 }, 3, 15, "2"));
+        assert_eq!(compile_error("pop constant 0"), error(VmErrorKind::PopConstant, 1, 5, "constant"));
+        assert_eq!(compile_error("add 1"), error(VmErrorKind::UnexpectedOperand, 1, 5, "1"));
+        assert_eq!(compile_error("call f 99999"), error(VmErrorKind::InvalidNumber, 1, 8, "99999"));
+        assert_eq!(compile_error("call f 32767"), error(VmErrorKind::IndexOutOfRange{ max: 32762 }, 1, 8, "32767"));
+        assert_eq!(compile_error("call f 32763"), error(VmErrorKind::IndexOutOfRange{ max: 32762 }, 1, 8, "32763"));
+        assert_eq!(compile_error("push temp 9").to_string(), "Foo.vm:1:11: index out of range 0..7: '9'");
+    }
+
+    #[test]
+    fn undefined_functions() {
+        let modules = |sources: &[(&str, &str)]| sources.iter()
+            .map(|&(name, source)| VmModule{ name: name.to_string(), source: source.to_string() })
+            .collect::<Vec<_>>();
+        let main = ("Main", "function Main.main 0\n  push constant 8\n  call String.new 1\n  return");
+        let sys = ("Sys", "function Sys.init 0\ncall Main.main 0\nreturn");
+        let string = ("String", "function String.new 0\npush constant 0\nreturn");
+        assert_eq!(check_calls(&modules(&[main, sys, string]), true), Ok(()));
+        let error = check_calls(&modules(&[main, sys]), true).unwrap_err();
+        assert_eq!(error.to_string(), "Main.vm:3:8: undefined function: 'String.new'");
+        assert_eq!(check_calls(&modules(&[main, string]), true).unwrap_err().to_string(), "(bootstrap):1:1: undefined function: 'Sys.init'");
+        assert_eq!(check_calls(&modules(&[main, string]), false), Ok(()));
+        assert_eq!(check_calls(&modules(&[("Main", "call")]), false).unwrap_err().kind, VmErrorKind::MissingOperand);
+    }
+
     #[test]
     fn simple_function() {
         test2(3, "
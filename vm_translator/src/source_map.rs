@@ -0,0 +1,40 @@
+// the VM command which produced the asm lines from asm_line until the next location
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmLocation {
+    pub asm_line: usize,            // first asm line of the block (1-origin)
+    pub filename: String,
+    pub line: usize,                // 1-origin
+    pub function: Option<String>,   // the function which contains the command
+    pub command: String,
+}
+
+impl std::fmt::Display for VmLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.filename, self.line, self.command)?;
+        match &self.function {
+            Some(function) => write!(f, " (in {})", function),
+            None => Ok(())
+        }
+    }
+}
+
+// mapping from asm lines to VM commands, sorted by asm_line
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    locations: Vec<VmLocation>,
+}
+
+impl SourceMap {
+    pub fn push(&mut self, location: VmLocation) {
+        assert!(self.locations.last().map(|last| last.asm_line).unwrap_or(0) <= location.asm_line);
+        self.locations.push(location);
+    }
+    pub fn locations(&self) -> &[VmLocation] {
+        &self.locations
+    }
+    // the VM command which produced the asm line. None for the bootstrap code.
+    pub fn find(&self, asm_line: usize) -> Option<&VmLocation> {
+        let i = self.locations.partition_point(|location| location.asm_line <= asm_line);
+        if i == 0 { None } else { Some(&self.locations[i - 1]) }
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt::Write;
+
 
 #[derive(Debug)]
 pub enum UnaryOp { Neg, Not }
@@ -8,6 +10,9 @@ pub enum BinaryOp { Add, Sub, And, Or }
 #[derive(Debug)]
 pub enum Condition { Eq, Gt, Lt }
 
+#[derive(Debug, Clone, Copy)]
+pub enum Segment { Constant, Static, Argument, Local, This, That, Pointer, Temp }
+
 /*
 const VM_TERMINAL_ASM: &str = "
 (INFINITE_LOOP)
@@ -37,9 +42,23 @@ const RETURN_ASM: &str = "
 @R14\nA=M\n0;JMP    // goto RAM[R14]
 ";
 
+// counts lines written to the output, so that the source map can refer to asm lines
+struct LineCounter<'a> {
+    out: &'a mut dyn std::fmt::Write,
+    lines: usize,
+}
+
+impl<'a> std::fmt::Write for LineCounter<'a> {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        self.lines += s.matches('\n').count();
+        self.out.write_str(s)
+    }
+}
+
 pub struct AsmWriter<'a> {
-    out: &'a mut std::fmt::Write,
-    filename: &'a str,
+    out: LineCounter<'a>,
+    filename: String,       // namespace of static variables
+    funcname: Option<String>,   // scope of labels
     label_id: usize
 }
 
@@ -50,8 +69,27 @@ impl<'a> Drop for AsmWriter<'a> {
 }
 
 impl<'a> AsmWriter<'a> {
-    pub fn new(out: &'a mut std::fmt::Write, filename: &'a str) -> Self {
-        Self{ out, filename, label_id: 0 }
+    pub fn new(out: &'a mut dyn std::fmt::Write, filename: &str) -> Self {
+        Self{ out: LineCounter{ out, lines: 0 }, filename: filename.to_string(), funcname: None, label_id: 0 }
+    }
+    // line number (1-origin) of the next asm line to be written
+    pub fn next_line(&self) -> usize {
+        self.out.lines + 1
+    }
+    pub fn funcname(&self) -> Option<&str> {
+        self.funcname.as_deref()
+    }
+    // start translating another VM file, which has its own static segment
+    pub fn set_filename(&mut self, filename: &str) {
+        self.filename = filename.to_string();
+        self.funcname = None;
+    }
+    // labels in VM code are scoped by the function they appear in ('funcname$label')
+    pub fn function_label(&self, label: &str) -> String {
+        match &self.funcname {
+            Some(funcname) => format!("{}${}", funcname, label),
+            None => label.to_string()
+        }
     }
     pub fn label(&mut self, label: &str) {
         writeln!(self.out, "({})", label).unwrap();
@@ -117,38 +155,35 @@ impl<'a> AsmWriter<'a> {
         self.label(&if_end);
     }
 
-    fn set_segment_index_address_to(&mut self, segment: &str, index: i16, dst: char) {
+    fn set_segment_index_address_to(&mut self, segment: Segment, index: i16, dst: char) {
         // write 'index' to D-register
         writeln!(self.out, "@{}\nD=A", index).unwrap();
         match segment {
-            "constant" => panic!("constant is pseudo segment"),
-            "static" => {
+            Segment::Constant => unreachable!("constant is pseudo segment"),
+            Segment::Static => {
                 writeln!(self.out, "@{}.{}\n{}=A", self.filename, index, dst).unwrap();
             },
-            "argument" | "local" | "this" | "that" => {
-                let symbol = match segment { "argument" => "ARG", "local" => "LCL", "this" => "THIS", "that" => "THAT", _ => panic!("invalid segment") };
+            Segment::Argument | Segment::Local | Segment::This | Segment::That => {
+                let symbol = match segment { Segment::Argument => "ARG", Segment::Local => "LCL", Segment::This => "THIS", _ => "THAT" };
                 writeln!(self.out, "@{}\n{}=D+M", symbol, dst).unwrap();
             },
-            "pointer" | "temp" => {
-                let symbol = match segment { "pointer" => "R3", "temp" => "R5", _ => panic!("invalid segment") };
+            Segment::Pointer | Segment::Temp => {
+                let symbol = match segment { Segment::Pointer => "R3", _ => "R5" };
                 writeln!(self.out, "@{}\n{}=D+A", symbol, dst).unwrap();
-            },
-            _ => panic!("unknown segment")
+            }
         }
     }
 
-    pub fn push_segment(&mut self, segment: &str, index: i16) {
-        assert!(index >= 0);
+    pub fn push_segment(&mut self, segment: Segment, index: i16) {
         // D = segment[index]
         match segment {
-            "constant" => writeln!(self.out, "@{}\nD=A", index).unwrap(),
+            Segment::Constant => writeln!(self.out, "@{}\nD=A", index).unwrap(),
             _ => { self.set_segment_index_address_to(segment, index, 'A'); self.out.write_str("D=M\n").unwrap(); }
         }
         self.push();
     }
 
-    pub fn pop_segment(&mut self, segment: &str, index: i16) {
-        assert!(index >= 0);
+    pub fn pop_segment(&mut self, segment: Segment, index: i16) {
         // *R13 = segment + index
         self.set_segment_index_address_to(segment, index, 'D');
         self.out.write_str("@R13\nM=D\n").unwrap();
@@ -157,6 +192,7 @@ impl<'a> AsmWriter<'a> {
     }
 
     pub fn func_begin(&mut self, funcname: &str, nlocals: i16) {
+        self.funcname = Some(funcname.to_string());
         self.label(funcname);
         self.out.write_str("D=0\n").unwrap();
         for _ in 0 .. nlocals { self.push() }
@@ -166,7 +202,7 @@ impl<'a> AsmWriter<'a> {
         writeln!(self.out, "@{}\nD=A", return_label).unwrap();
         self.push();
         for symbol in &["LCL", "ARG", "THIS", "THAT"] {
-            writeln!(self.out, "@{}\nA=M\nD=M", symbol).unwrap();
+            writeln!(self.out, "@{}\nD=M", symbol).unwrap();
             self.push();
         }
         writeln!(self.out, "@{}\nD=-A\n@SP\nD=D+M\n@ARG\nM=D", nargs + 5).unwrap();  // *ARG = *SP - nargs - 5
@@ -175,7 +211,7 @@ impl<'a> AsmWriter<'a> {
         self.label(&return_label);
     }
     pub fn func_return(&mut self) {
-        self.out.write_str("@LCL\nD=M\n@5\nD=D-A\n@R14\nM=D\n").unwrap(); // RAM[R14] = RAM[LCL] - 5 (put the return-address in RAM[R14])
+        self.out.write_str("@LCL\nD=M\n@5\nA=D-A\nD=M\n@R14\nM=D\n").unwrap(); // RAM[R14] = *(RAM[LCL] - 5) (put the return-address in RAM[R14])
         self.pop();
         self.out.write_str(RETURN_ASM).unwrap();
     }